@@ -2,5 +2,5 @@
 echo Running performance profiling...
 set CARGO_PROFILE_RELEASE_DEBUG=true
 cargo build --release
-cargo run --release -- batch 10 1
+cargo run --release -- batch --games 10 --parallel 1
 pause
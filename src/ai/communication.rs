@@ -1,6 +1,217 @@
 ﻿// Neural communication between AIs
+use bevy::prelude::*;
+use std::collections::HashMap;
+use crate::ai::team::AITeam;
+use crate::config::game_config::CommunicationSettings;
+use crate::units::{Team, TeamRoster};
+
+/// A float vector one unit sends to its teammates.
+#[derive(Clone, Debug, PartialEq)]
 pub struct NeuralMessage {
-    pub from: u32,
-    pub to: u32,
+    pub from: Entity,
+    /// A single teammate, or `None` to reach the whole team.
+    pub to: Option<Entity>,
     pub data: Vec<f32>,
 }
+
+impl NeuralMessage {
+    /// To the whole team. `from` is filled in by the engine on send.
+    pub fn broadcast(data: Vec<f32>) -> Self {
+        Self { from: Entity::PLACEHOLDER, to: None, data }
+    }
+
+    /// Whether `unit` is among the recipients.
+    pub fn reaches(&self, unit: Entity) -> bool {
+        self.to.is_none_or(|to| to == unit)
+    }
+}
+
+/// One team's end of the channel.
+struct TeamChannel {
+    team: AITeam,
+    /// Sent last turn, readable this turn.
+    inbox: Vec<NeuralMessage>,
+    /// Sent this turn, delivered at the end of it.
+    outbox: Vec<NeuralMessage>,
+    sent_by: HashMap<Entity, usize>,
+}
+
+/// Per-team message passing with a one-turn delay: what is sent on turn N
+/// shows up in the observations of turn N + 1.
+#[derive(Resource, Default)]
+pub struct CommChannel {
+    /// Indexed by `Team.0`; the roster numbers its teams from zero.
+    channels: Vec<TeamChannel>,
+}
+
+impl CommChannel {
+    pub fn new(roster: &TeamRoster) -> Self {
+        let channels = roster.iter()
+            .map(|info| TeamChannel {
+                team: AITeam::new(info.name.clone(), 0),
+                inbox: Vec::new(),
+                outbox: Vec::new(),
+                sent_by: HashMap::new(),
+            })
+            .collect();
+        Self { channels }
+    }
+
+    fn channel(&self, team: Team) -> Option<&TeamChannel> {
+        self.channels.get(team.0 as usize)
+    }
+
+    fn channel_mut(&mut self, team: Team) -> Option<&mut TeamChannel> {
+        self.channels.get_mut(team.0 as usize)
+    }
+
+    /// Record how many agents `team` has on the field this turn.
+    pub fn begin_turn(&mut self, team: Team, agents: usize) {
+        if let Some(channel) = self.channel_mut(team) {
+            channel.team.ai_count = agents;
+        }
+    }
+
+    /// Messages delivered to `team` this turn.
+    pub fn inbox(&self, team: Team) -> &[NeuralMessage] {
+        self.channel(team).map_or(&[], |c| c.inbox.as_slice())
+    }
+
+    /// Queue `message` from `from` on `team`'s channel, sized to
+    /// `settings.message_size`. Returns `false` when the sender or the team
+    /// is out of bandwidth for the turn, or communication is off.
+    pub fn send(
+        &mut self,
+        team: Team,
+        from: Entity,
+        mut message: NeuralMessage,
+        settings: &CommunicationSettings,
+    ) -> bool {
+        if !settings.enabled {
+            return false;
+        }
+        let Some(channel) = self.channel_mut(team) else {
+            return false;
+        };
+
+        // A team never gets more than its agents could send between them
+        let team_budget = settings.max_per_team.min(channel.team.ai_count * settings.max_per_unit);
+        let sent = channel.sent_by.entry(from).or_default();
+        if *sent >= settings.max_per_unit || channel.outbox.len() >= team_budget {
+            return false;
+        }
+        *sent += 1;
+
+        message.from = from;
+        message.data.resize(settings.message_size, 0.0);
+        channel.outbox.push(message);
+        true
+    }
+
+    /// Messages `team` sent on the turn `deliver` last closed. This is the
+    /// same list as the inbox, seen from the sender's side; the replay
+    /// records it for that turn, so overlays show it under that turn.
+    pub fn sent_last_turn(&self, team: Team) -> &[NeuralMessage] {
+        self.inbox(team)
+    }
+
+    /// Messages `team` sent this turn, before delivery.
+    pub fn outbox(&self, team: Team) -> &[NeuralMessage] {
+        self.channel(team).map_or(&[], |c| c.outbox.as_slice())
+    }
+
+    /// Close the turn: this turn's messages become next turn's inbox.
+    pub fn deliver(&mut self) {
+        for channel in &mut self.channels {
+            channel.inbox = std::mem::take(&mut channel.outbox);
+            channel.sent_by.clear();
+        }
+    }
+
+    /// Per-team name and message count for this turn, for logging.
+    pub fn traffic(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.channels.iter().map(|c| (c.team.name.as_str(), c.outbox.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    fn settings(max_per_unit: usize, max_per_team: usize) -> CommunicationSettings {
+        CommunicationSettings { enabled: true, message_size: 3, max_per_unit, max_per_team }
+    }
+
+    fn channel() -> CommChannel {
+        CommChannel::new(&TeamRoster::from_config(&GameConfig::default()))
+    }
+
+    #[test]
+    fn messages_arrive_after_delivery() {
+        let mut comms = channel();
+        let settings = settings(1, 8);
+        comms.begin_turn(Team(0), 2);
+        assert!(comms.send(Team(0), Entity::from_raw(1), NeuralMessage::broadcast(vec![1.0]), &settings));
+
+        assert!(comms.inbox(Team(0)).is_empty());
+        assert_eq!(comms.outbox(Team(0)).len(), 1);
+
+        comms.deliver();
+        let inbox = comms.inbox(Team(0));
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].from, Entity::from_raw(1));
+        assert_eq!(inbox[0].data, vec![1.0, 0.0, 0.0]);
+        assert!(comms.outbox(Team(0)).is_empty());
+        assert!(comms.inbox(Team(1)).is_empty());
+
+        // Nothing sent on the next turn: the inbox empties again
+        comms.deliver();
+        assert!(comms.inbox(Team(0)).is_empty());
+    }
+
+    #[test]
+    fn each_unit_sends_up_to_its_limit() {
+        let mut comms = channel();
+        let settings = settings(2, 8);
+        comms.begin_turn(Team(0), 2);
+        let unit = Entity::from_raw(1);
+        let sent = (0..4)
+            .filter(|_| comms.send(Team(0), unit, NeuralMessage::broadcast(vec![]), &settings))
+            .count();
+        assert_eq!(sent, 2);
+
+        // The count starts over on the next turn
+        comms.deliver();
+        assert!(comms.send(Team(0), unit, NeuralMessage::broadcast(vec![]), &settings));
+    }
+
+    #[test]
+    fn a_team_sends_no_more_than_its_budget() {
+        let mut comms = channel();
+
+        // max_per_team caps a big team
+        let big = settings(1, 3);
+        comms.begin_turn(Team(0), 5);
+        let sent = (0..5)
+            .filter(|i| comms.send(Team(0), Entity::from_raw(*i), NeuralMessage::broadcast(vec![]), &big))
+            .count();
+        assert_eq!(sent, 3);
+
+        // and agents * max_per_unit caps a small one
+        let small = settings(2, 8);
+        comms.begin_turn(Team(1), 1);
+        let sent = (0..5)
+            .filter(|i| comms.send(Team(1), Entity::from_raw(*i), NeuralMessage::broadcast(vec![]), &small))
+            .count();
+        assert_eq!(sent, 2);
+    }
+
+    #[test]
+    fn nothing_is_sent_with_communication_off() {
+        let mut comms = channel();
+        let settings = CommunicationSettings { enabled: false, ..settings(1, 8) };
+        comms.begin_turn(Team(0), 2);
+        assert!(!comms.send(Team(0), Entity::from_raw(1), NeuralMessage::broadcast(vec![]), &settings));
+    }
+}
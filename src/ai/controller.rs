@@ -1,23 +1,233 @@
-﻿use bevy::prelude::Entity;
+﻿use bevy::prelude::{Color, Entity};
 use crate::world::{
     actions::Action,
     hex_grid::HexCoord,
+    pathfinding::Pathfinder,
+    visibility::{LastKnown, TeamVisibility},
+    spatial_index::SpatialIndex,
+    resource::{Resource, ResourceField, ResourceNode},
 };
-use crate::units::{Unit, Team};
+use crate::game::objectives::CapturePoint;
+use crate::config::game_config::ProductionSettings;
+use crate::ai::communication::NeuralMessage;
+use crate::ai::debug::{AiDebug, DebugShape};
+use std::collections::HashMap;
+use crate::units::{Unit, Team, UnitType, Structure, StructureKind};
+
+/// A structure as one team sees it.
+#[derive(Clone, Copy, Debug)]
+pub struct StructureInfo {
+    pub team: Team,
+    pub kind: StructureKind,
+    pub coord: HexCoord,
+}
 
 /// Read-only snapshot the AI may inspect each turn.
+///
+/// Under fog of war this is one team's observation: its own units, the enemies
+/// it can currently see, and where it last saw the ones it can't.
 #[derive(Clone)]
 pub struct WorldSnapshot {
     pub units: Vec<(Entity, Team, HexCoord)>,
+    /// `UnitType` of every unit in `units`.
+    pub unit_types: HashMap<Entity, UnitType>,
+    pub last_known: Vec<LastKnown>,
+    /// `units` indexed by hex for proximity queries.
+    pub index: SpatialIndex,
+    /// The observing team's own stockpile.
+    pub stockpile: Resource,
+    /// Resource nodes the team can currently see.
+    pub resource_nodes: Vec<ResourceNode>,
+    /// The team's own structures plus enemy ones in sight.
+    pub structures: Vec<StructureInfo>,
+    /// King-of-the-hill points and who holds them; ownership is public.
+    pub capture_points: Vec<CapturePoint>,
+    /// The observing team and everyone on its side.
+    pub allies: Vec<Team>,
+    /// What teammates sent last turn.
+    pub messages: Vec<NeuralMessage>,
+    /// What each structure costs to build under the match's config.
+    pub build_costs: HashMap<StructureKind, Resource>,
+}
+
+impl WorldSnapshot {
+    /// Build `team`'s observation of `units` (every live unit on the map).
+    pub fn for_team(
+        team: Team,
+        units: &[(Entity, Team, HexCoord)],
+        unit_types: &HashMap<Entity, UnitType>,
+        visibility: &TeamVisibility,
+        field: &ResourceField,
+        stockpile: Resource,
+        structures: &[StructureInfo],
+    ) -> Self {
+        let resource_nodes = field.nodes.iter()
+            .filter(|n| visibility.is_visible(team, n.coord))
+            .cloned()
+            .collect();
+        let structures = structures.iter()
+            .filter(|s| s.team == team || visibility.is_visible(team, s.coord))
+            .copied()
+            .collect();
+        
+        if !visibility.enabled {
+            return Self::new(units.to_vec(), unit_types.clone(), Vec::new(), stockpile, resource_nodes, structures)
+                .with_allies(vec![team]);
+        }
+
+        let seen: Vec<_> = units.iter()
+            .filter(|(_, t, coord)| *t == team || visibility.is_visible(team, *coord))
+            .copied()
+            .collect();
+        let last_known = visibility.team(team)
+            .map(|v| v.last_known.values()
+                .filter(|lk| !seen.iter().any(|(e, _, _)| *e == lk.entity))
+                .copied()
+                .collect())
+            .unwrap_or_default();
+
+        let seen_types = seen.iter()
+            .filter_map(|(e, _, _)| unit_types.get(e).map(|t| (*e, *t)))
+            .collect();
+        Self::new(seen, seen_types, last_known, stockpile, resource_nodes, structures)
+            .with_allies(vec![team])
+    }
+
+    fn new(
+        units: Vec<(Entity, Team, HexCoord)>,
+        unit_types: HashMap<Entity, UnitType>,
+        last_known: Vec<LastKnown>,
+        stockpile: Resource,
+        resource_nodes: Vec<ResourceNode>,
+        structures: Vec<StructureInfo>,
+    ) -> Self {
+        let mut index = SpatialIndex::default();
+        index.rebuild(units.iter().copied());
+        Self {
+            units,
+            unit_types,
+            last_known,
+            index,
+            stockpile,
+            resource_nodes,
+            structures,
+            capture_points: Vec::new(),
+            allies: Vec::new(),
+            messages: Vec::new(),
+            build_costs: HashMap::new(),
+        }
+    }
+
+    pub fn with_capture_points(mut self, points: &[CapturePoint]) -> Self {
+        self.capture_points = points.to_vec();
+        self
+    }
+
+    pub fn with_allies(mut self, allies: Vec<Team>) -> Self {
+        self.allies = allies;
+        self
+    }
+
+    pub fn with_messages(mut self, messages: &[NeuralMessage]) -> Self {
+        self.messages = messages.to_vec();
+        self
+    }
+
+    pub fn with_build_costs(mut self, production: &ProductionSettings) -> Self {
+        self.build_costs = [StructureKind::Base, StructureKind::Barracks].into_iter()
+            .map(|kind| (kind, production.structure_cost(kind).resources()))
+            .collect();
+        self
+    }
+
+    /// Whether the stockpile covers building `kind`.
+    pub fn can_afford_build(&self, kind: StructureKind) -> bool {
+        self.build_costs.get(&kind).is_some_and(|cost| self.stockpile.can_afford(cost))
+    }
+
+    /// Last turn's messages addressed to `unit` or to the whole team.
+    pub fn messages_for(&self, unit: Entity) -> impl Iterator<Item = &NeuralMessage> {
+        self.messages.iter().filter(move |m| m.reaches(unit))
+    }
+
+    /// Whether `team` is on an opposing side.
+    pub fn is_hostile(&self, team: Team) -> bool {
+        !self.allies.contains(&team)
+    }
+
+    /// Position of the closest hostile unit in this observation.
+    pub fn nearest_enemy(&self, from: HexCoord) -> Option<HexCoord> {
+        self.index.nearest_enemy(from, |team| self.is_hostile(team)).map(|(_, coord)| coord)
+    }
+}
+
+/// Everything an `AiController` gets to work with for one decision:
+/// the observation plus turn-scoped services owned by the engine.
+pub struct DecisionContext<'w, 'p> {
+    pub world: &'w WorldSnapshot,
+    pub pathfinder: &'w mut Pathfinder<'p>,
+    /// The unit or structure being decided for.
+    pub actor: Entity,
+    pub debug: &'w mut AiDebug,
+}
+
+impl DecisionContext<'_, '_> {
+    /// Whether debug shapes are being kept, to skip building expensive ones.
+    pub fn debugging(&self) -> bool {
+        self.debug.enabled
+    }
+
+    pub fn debug_line(&mut self, from: HexCoord, to: HexCoord, color: Color) {
+        self.debug.push(self.actor, DebugShape::Line { from, to, color });
+    }
+
+    pub fn debug_circle(&mut self, at: HexCoord, color: Color) {
+        self.debug.push(self.actor, DebugShape::Circle { at, color });
+    }
+
+    pub fn debug_note(&mut self, text: impl Into<String>) {
+        if self.debug.enabled {
+            self.debug.push(self.actor, DebugShape::Note(text.into()));
+        }
+    }
 }
 
 /// Behaviour contract for any in-game AI.
 pub trait AiController: Send + Sync {
+    /// Recorded in replays next to the controller's name, so a replay can
+    /// tell which revision of a controller played it.
+    fn version(&self) -> &str {
+        "unversioned"
+    }
+
     /// Decide what *one* unit should do this turn.
     fn decide(
         &mut self,
         unit_id: Entity,
         self_state: &Unit,
-        world: &WorldSnapshot,
+        ctx: &mut DecisionContext,
     ) -> Action;
+
+    /// Decide what a finished structure should do this turn, usually
+    /// `Action::Produce`. Structures stay idle unless a controller opts in.
+    fn decide_structure(
+        &mut self,
+        _structure_id: Entity,
+        _state: &Structure,
+        _ctx: &mut DecisionContext,
+    ) -> Action {
+        Action::Stay
+    }
+
+    /// Messages `unit_id` sends to its teammates this turn, read after
+    /// `decide`. They arrive in next turn's `WorldSnapshot::messages`;
+    /// anything over the configured bandwidth is dropped.
+    fn communicate(
+        &mut self,
+        _unit_id: Entity,
+        _self_state: &Unit,
+        _ctx: &mut DecisionContext,
+    ) -> Vec<NeuralMessage> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,46 @@
+﻿// Debug drawing for controllers
+use bevy::prelude::*;
+use crate::world::HexCoord;
+
+/// Something a controller wants drawn to explain a decision, in hex space.
+#[derive(Clone, Debug)]
+pub enum DebugShape {
+    Line { from: HexCoord, to: HexCoord, color: Color },
+    Circle { at: HexCoord, color: Color },
+    /// Free text, listed in the inspector for the unit that wrote it.
+    Note(String),
+}
+
+/// Shapes emitted through `DecisionContext` this turn, keyed by the unit or
+/// structure being decided for. Nothing is kept unless `enabled`, so
+/// controllers can draw unconditionally at no cost in batch runs.
+#[derive(Resource, Default)]
+pub struct AiDebug {
+    pub enabled: bool,
+    shapes: Vec<(Entity, DebugShape)>,
+}
+
+impl AiDebug {
+    /// Drop last turn's shapes.
+    pub fn begin_turn(&mut self) {
+        self.shapes.clear();
+    }
+
+    pub fn push(&mut self, actor: Entity, shape: DebugShape) {
+        if self.enabled {
+            self.shapes.push((actor, shape));
+        }
+    }
+
+    pub fn shapes(&self) -> &[(Entity, DebugShape)] {
+        &self.shapes
+    }
+
+    /// Notes written for `actor` this turn.
+    pub fn notes(&self, actor: Entity) -> impl Iterator<Item = &str> {
+        self.shapes.iter().filter_map(move |(e, shape)| match shape {
+            DebugShape::Note(text) if *e == actor => Some(text.as_str()),
+            _ => None,
+        })
+    }
+}
@@ -1,17 +1,57 @@
 ﻿use bevy::prelude::*;
 use crate::game::turn_manager::GameAI;
-use crate::ai::RandomAi;
+use crate::ai::{AiController, AiDebug, CommChannel, RandomAi};
+use crate::config::GameConfig;
+use crate::config::game_config::CommunicationSettings;
+use crate::units::TeamRoster;
 
 pub struct AiIntegrationPlugin;
 
 impl Plugin for AiIntegrationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ai);
+        app
+            .init_resource::<AiDebug>()
+            .add_systems(Startup, setup_ai)
+            // Fresh controllers and an empty channel for every new match
+            .add_systems(PreUpdate, setup_ai
+                .in_set(crate::game::session::MatchSetup)
+                .after(crate::game::session::reset_match));
     }
 }
 
-fn setup_ai(mut commands: Commands) {
-    // Initialize with RandomAI for now
-    let ai = RandomAi::new();
-    commands.insert_resource(GameAI(Box::new(ai)));
+/// Build a controller from the name used in a team's `controller` setting.
+pub fn controller_by_name(name: &str, comms: &CommunicationSettings) -> Option<Box<dyn AiController>> {
+    match name {
+        "random" => Some(Box::new(RandomAi::with_message_size(comms.message_size))),
+        _ => None,
+    }
+}
+
+fn setup_ai(
+    mut commands: Commands,
+    roster: Res<TeamRoster>,
+    config: Res<GameConfig>,
+    mut warned: Local<bool>,
+) {
+    let comms = &config.communication;
+    let random_plays = roster.iter().any(|info| info.controller == "random");
+    if comms.enabled && comms.message_size < RandomAi::SIGHTING_SIZE && random_plays && !*warned {
+        eprintln!(
+            "communication.message_size = {} is below the {} floats a random AI sighting needs; its units won't report sightings",
+            comms.message_size, RandomAi::SIGHTING_SIZE,
+        );
+        *warned = true;
+    }
+
+    let controllers = roster.iter()
+        .map(|info| {
+            let controller = controller_by_name(&info.controller, comms).unwrap_or_else(|| {
+                eprintln!("Unknown controller '{}' for team {}, using random", info.controller, info.name);
+                Box::new(RandomAi::with_message_size(comms.message_size))
+            });
+            (info.team, controller)
+        })
+        .collect();
+    commands.insert_resource(GameAI(controllers));
+    commands.insert_resource(CommChannel::new(&roster));
 }
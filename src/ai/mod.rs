@@ -1,10 +1,13 @@
 ﻿pub mod bridge;
 pub mod team;
 pub mod communication;
+pub mod debug;
 pub mod controller;
 pub mod random;
 pub mod integration;
 
-pub use controller::{AiController, WorldSnapshot};
+pub use controller::{AiController, WorldSnapshot, DecisionContext};
+pub use communication::{CommChannel, NeuralMessage};
+pub use debug::{AiDebug, DebugShape};
 pub use random::RandomAi;
 pub use integration::AiIntegrationPlugin;
@@ -1,18 +1,33 @@
 ﻿use rand::{seq::SliceRandom, Rng, SeedableRng, rngs::SmallRng};
-use bevy::prelude::Entity;
+use bevy::prelude::{Color, Entity};
 
 use crate::world::actions::Action;
-use crate::units::Unit;
-use super::{AiController, WorldSnapshot};
+use std::collections::HashSet;
+use crate::world::hex_grid::{HexCoord, HEX_DIRECTIONS};
+use crate::world::pathfinding::PathOptions;
+use crate::units::{Unit, UnitType, Structure, StructureKind};
+use crate::units::movement::hex_distance;
+use super::{AiController, DecisionContext, NeuralMessage};
 
 /// Improved AI: Moves toward nearest enemy, with some randomness
 pub struct RandomAi {
     rng: SmallRng,
+    /// Off when messages are too short to carry a sighting.
+    share_sightings: bool,
 }
 
 impl RandomAi {
+    /// Floats a sighting message needs, see `encode_sighting`.
+    pub const SIGHTING_SIZE: usize = 3;
+
     pub fn new() -> Self { 
-        Self { rng: SmallRng::from_entropy() } 
+        Self { rng: SmallRng::from_entropy(), share_sightings: true } 
+    }
+
+    /// Units only report sightings when `message_size` can hold one; the
+    /// channel would cut anything longer down and the report be lost.
+    pub fn with_message_size(message_size: usize) -> Self {
+        Self { share_sightings: message_size >= Self::SIGHTING_SIZE, ..Self::new() }
     }
 }
 
@@ -22,13 +37,64 @@ impl Default for RandomAi {
     }
 }
 
+impl RandomAi {
+    /// Workers ignore the fight: put up a barracks once the team can afford one,
+    /// harvest if a node is in reach, otherwise walk to the closest one.
+    fn decide_worker(&mut self, my_pos: HexCoord, unit_id: Entity, self_state: &Unit, ctx: &mut DecisionContext) -> Action {
+        let world = ctx.world;
+        let has_barracks = world.structures
+            .iter()
+            .any(|s| s.team == self_state.team && s.kind == StructureKind::Barracks);
+        let has_base = world.structures
+            .iter()
+            .any(|s| s.team == self_state.team && s.kind == StructureKind::Base);
+        if has_base && !has_barracks && world.can_afford_build(StructureKind::Barracks) {
+            // The engine rejects bad sites, so a random neighbour is good enough
+            let (dq, dr) = *HEX_DIRECTIONS.choose(&mut self.rng).unwrap();
+            let site = my_pos.offset(dq, dr);
+            let taken = world.units.iter().any(|(_, _, c)| *c == site)
+                || world.resource_nodes.iter().any(|n| n.coord == site);
+            if !taken {
+                ctx.debug_circle(site, Color::YELLOW);
+                return Action::Build(StructureKind::Barracks, site);
+            }
+        }
+
+        let nodes: Vec<HexCoord> = ctx.world.resource_nodes
+            .iter()
+            .filter(|n| !n.is_depleted())
+            .map(|n| n.coord)
+            .collect();
+
+        if nodes.iter().any(|n| hex_distance(*n, my_pos) <= 1) {
+            return Action::Gather;
+        }
+        if nodes.is_empty() {
+            return Action::Stay;
+        }
+
+        let others: HashSet<HexCoord> = ctx.world.units
+            .iter()
+            .filter(|(e, team, _)| *team == self_state.team && *e != unit_id)
+            .map(|(_, _, pos)| *pos)
+            .collect();
+        ctx.pathfinder.flow_field(&nodes).direction(my_pos, &others)
+    }
+}
+
 impl AiController for RandomAi {
+    fn version(&self) -> &str {
+        "1"
+    }
+
     fn decide(
         &mut self,
         unit_id: Entity,
         self_state: &Unit,
-        world: &WorldSnapshot,
+        ctx: &mut DecisionContext,
     ) -> Action {
+        let world = ctx.world;
+
         // Get this unit's position
         let (_, _, my_pos) = world.units
             .iter()
@@ -36,53 +102,148 @@ impl AiController for RandomAi {
             .copied()
             .unwrap();
 
-        // Find nearest enemy
-        let enemies: Vec<_> = world.units
+        // Workers harvest while the team still has an army, and join the fight once it's gone
+        let army_left = world.units
             .iter()
-            .filter(|(_, team, _)| *team != self_state.team)
+            .any(|(e, team, _)| *team == self_state.team && world.unit_types.get(e) != Some(&UnitType::Worker));
+        if self_state.unit_type == UnitType::Worker && army_left {
+            return self.decide_worker(my_pos, unit_id, self_state, ctx);
+        }
+
+        // Find nearest enemy, falling back to where we last saw one
+        let mut enemies: Vec<_> = world.units
+            .iter()
+            .filter(|(_, team, _)| world.is_hostile(*team))
             .map(|(_, _, pos)| *pos)
             .collect();
+        enemies.extend(world.structures
+            .iter()
+            .filter(|s| world.is_hostile(s.team))
+            .map(|s| s.coord));
+        enemies.extend(world.capture_points
+            .iter()
+            .filter(|p| p.owner.is_none_or(|owner| world.is_hostile(owner)))
+            .map(|p| p.coord));
+        if enemies.is_empty() {
+            enemies = world.last_known
+                .iter()
+                .filter(|seen| world.is_hostile(seen.team))
+                .map(|seen| seen.coord)
+                .collect();
+        }
 
+        // Then on what teammates reported seeing last turn
         if enemies.is_empty() {
-            return Action::Stay;
+            enemies = world.messages_for(unit_id).filter_map(decode_sighting).collect();
         }
 
-        // Find closest enemy
-        let nearest_enemy = enemies
-            .iter()
-            .min_by_key(|enemy_pos| {
-                let dq = enemy_pos.q - my_pos.q;
-                let dr = enemy_pos.r - my_pos.r;
-                dq.abs() + dr.abs() + (dq + dr).abs()
-            })
-            .unwrap();
+        // Nothing known about the enemy: scout the mirror of our own position,
+        // which is where the opposing side started out
+        if enemies.is_empty() {
+            enemies.push(HexCoord::new(-my_pos.q, -my_pos.r));
+        }
+
+        // Find closest enemy: a spatial lookup for visible ones, else the closest remembered target
+        let nearest_enemy = world.nearest_enemy(my_pos).unwrap_or_else(|| {
+            *enemies
+                .iter()
+                .min_by_key(|enemy_pos| hex_distance(my_pos, **enemy_pos))
+                .unwrap()
+        });
+
+        ctx.debug_line(my_pos, nearest_enemy, Color::ORANGE);
+        if ctx.debugging() {
+            ctx.debug_note(format!("target ({}, {}) of {} known", nearest_enemy.q, nearest_enemy.r, enemies.len()));
+        }
 
         // 30% chance to move randomly (exploration)
         if self.rng.gen_bool(0.3) {
-            const DIRS: [(i32,i32);6] = [(1,0),(1,-1),(0,-1),(-1,0),(-1,1),(0,1)];
-            let (dq,dr) = *DIRS.choose(&mut self.rng).unwrap();
+            ctx.debug_note("exploring");
+            let (dq,dr) = *HEX_DIRECTIONS.choose(&mut self.rng).unwrap();
             return Action::Move(dq,dr);
         }
 
-        // Move toward nearest enemy
-        let _dq = (nearest_enemy.q - my_pos.q).signum();
-        let _dr = (nearest_enemy.r - my_pos.r).signum();
+        // Follow the team-wide flow field toward the enemy, stepping around allies
+        let allies: HashSet<HexCoord> = world.units
+            .iter()
+            .filter(|(e, team, _)| *team == self_state.team && *e != unit_id)
+            .map(|(_, _, pos)| *pos)
+            .collect();
+        if let Action::Move(dq, dr) = ctx.pathfinder.flow_field(&enemies).direction(my_pos, &allies) {
+            return Action::Move(dq, dr);
+        }
 
-        // Valid hex moves
-        const DIRS: [(i32,i32);6] = [(1,0),(1,-1),(0,-1),(-1,0),(-1,1),(0,1)];
-        
-        // Find move that gets us closest to target
-        let best_move = DIRS
+        // Boxed in on the field: ask A* for a detour around everyone
+        if let Some(path) = ctx.pathfinder.find_path(my_pos, nearest_enemy, PathOptions::avoiding_units()) {
+            return path.first_move(my_pos);
+        }
+
+        // No route this turn: fall back to the greedy step that gets us closest
+        let best_move = HEX_DIRECTIONS
             .iter()
-            .min_by_key(|(move_dq, move_dr)| {
-                let new_q = my_pos.q + move_dq;
-                let new_r = my_pos.r + move_dr;
-                let dist_q = nearest_enemy.q - new_q;
-                let dist_r = nearest_enemy.r - new_r;
-                dist_q.abs() + dist_r.abs() + (dist_q + dist_r).abs()
-            })
+            .min_by_key(|(move_dq, move_dr)| hex_distance(my_pos.offset(*move_dq, *move_dr), nearest_enemy))
             .unwrap();
 
         Action::Move(best_move.0, best_move.1)
     }
-}
\ No newline at end of file
+
+    /// Fighters and scouts share the closest enemy they can see.
+    fn communicate(
+        &mut self,
+        unit_id: Entity,
+        self_state: &Unit,
+        ctx: &mut DecisionContext,
+    ) -> Vec<NeuralMessage> {
+        if !self.share_sightings || self_state.unit_type == UnitType::Worker {
+            return Vec::new();
+        }
+        let world = ctx.world;
+        let Some((_, _, my_pos)) = world.units.iter().find(|(e, _, _)| *e == unit_id) else {
+            return Vec::new();
+        };
+        world.nearest_enemy(*my_pos)
+            .map(|enemy| vec![NeuralMessage::broadcast(encode_sighting(enemy))])
+            .unwrap_or_default()
+    }
+
+    /// Bases keep a handful of workers around; barracks train mostly fighters.
+    fn decide_structure(
+        &mut self,
+        _structure_id: Entity,
+        state: &Structure,
+        ctx: &mut DecisionContext,
+    ) -> Action {
+        match state.kind {
+            StructureKind::Base => {
+                let workers = ctx.world.units
+                    .iter()
+                    .filter(|(e, team, _)| *team == state.team && ctx.world.unit_types.get(e) == Some(&UnitType::Worker))
+                    .count();
+                if workers < 4 {
+                    Action::Produce(UnitType::Worker)
+                } else {
+                    Action::Stay
+                }
+            }
+            StructureKind::Barracks => {
+                if self.rng.gen_bool(0.25) {
+                    Action::Produce(UnitType::Scout)
+                } else {
+                    Action::Produce(UnitType::Fighter)
+                }
+            }
+        }
+    }
+}
+
+/// `[1, q, r]`: an enemy was seen at (q, r).
+fn encode_sighting(coord: HexCoord) -> Vec<f32> {
+    vec![1.0, coord.q as f32, coord.r as f32]
+}
+
+fn decode_sighting(message: &NeuralMessage) -> Option<HexCoord> {
+    match message.data.as_slice() {
+        [flag, q, r, ..] if *flag > 0.5 => Some(HexCoord::new(q.round() as i32, r.round() as i32)),
+        _ => None,
+    }
+}
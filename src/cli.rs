@@ -0,0 +1,356 @@
+﻿// Command-line parsing
+//
+//   ai_battle_arena [command] [options]
+//
+// With no command the mode comes from simulation_modes.toml, as before.
+use std::error::Error;
+use std::path::PathBuf;
+use crate::config::{GameConfig, SimulationConfig, SimulationMode};
+
+pub const USAGE: &str = "\
+Usage: ai_battle_arena [command] [options]
+
+Commands:
+  play                      Watch a match in a window
+  headless                  Play one match without a window and save its data
+  batch [games] [parallel]  Play many headless matches and export the results
+  tournament                Round-robin the --controllers against each other
+  replay <file>             Open a replay in the viewer
+  verify <file>             Check a replay decodes and its keyframes agree with its deltas
+  convert <in> <out>        Rewrite a replay as .json or binary .abr
+
+Options:
+  --config <path>           Game config (default configs/game_config.toml)
+  --sim-config <path>       Simulation config (default configs/simulation_modes.toml)
+  --mode <visual|headless>  Override the mode when no command picks one
+  --seed <n>                Map seed
+  --output <dir>            Where results, replays, snapshots and event logs go
+  --set <key>=<value>       Override any game config field, e.g. --set combat.base_damage=40;
+                            list entries by index, e.g. --set teams.1.controller=random
+  --games <n>               Games for batch, or per pairing for tournament (default 100)
+  --parallel <n>            Games played at once (default 4)
+  --controllers <a,b,..>    Controllers entered in a tournament
+  -h, --help                Show this message";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// No command: whatever mode the simulation config asks for.
+    Run,
+    Play,
+    Headless,
+    Batch,
+    Tournament,
+    Replay(PathBuf),
+    Verify(PathBuf),
+    Convert(PathBuf, PathBuf),
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cli {
+    pub command: Command,
+    /// Config files given with `--config` / `--sim-config`. Without them the
+    /// default files are read, falling back to built-in defaults.
+    pub config_path: Option<PathBuf>,
+    pub sim_config_path: Option<PathBuf>,
+    pub mode: Option<SimulationMode>,
+    pub seed: Option<u64>,
+    pub output: Option<PathBuf>,
+    /// `--set` overrides as (dotted key, raw value), applied in order.
+    pub overrides: Vec<(String, String)>,
+    pub games: usize,
+    pub parallel: usize,
+    pub controllers: Vec<String>,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            command: Command::Run,
+            config_path: None,
+            sim_config_path: None,
+            mode: None,
+            seed: None,
+            output: None,
+            overrides: Vec::new(),
+            games: 100,
+            parallel: 4,
+            controllers: Vec::new(),
+        }
+    }
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut cli = Cli::default();
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+            match arg.as_str() {
+                "-h" | "--help" => cli.command = Command::Help,
+                "--config" => cli.config_path = Some(value(&arg)?.into()),
+                "--sim-config" => cli.sim_config_path = Some(value(&arg)?.into()),
+                "--mode" => cli.mode = Some(parse_mode(&value(&arg)?)?),
+                "--seed" => cli.seed = Some(parse_number(&arg, &value(&arg)?)?),
+                "--output" => cli.output = Some(value(&arg)?.into()),
+                "--games" => cli.games = parse_number(&arg, &value(&arg)?)?,
+                "--parallel" => cli.parallel = parse_number(&arg, &value(&arg)?)?,
+                "--controllers" => {
+                    cli.controllers = value(&arg)?.split(',').map(|c| c.trim().to_string()).collect();
+                }
+                "--set" => {
+                    let setting = value(&arg)?;
+                    let (key, raw) = setting.split_once('=')
+                        .ok_or_else(|| format!("--set expects key=value, got '{}'", setting))?;
+                    cli.overrides.push((key.trim().to_string(), raw.trim().to_string()));
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag).into()),
+                _ => positional.push(arg),
+            }
+        }
+
+        if cli.command == Command::Help {
+            return Ok(cli);
+        }
+        let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+        cli.command = match positional.as_slice() {
+            [] => Command::Run,
+            ["play"] => Command::Play,
+            ["headless"] => Command::Headless,
+            ["tournament"] => Command::Tournament,
+            ["replay", file] => Command::Replay(file.into()),
+            ["verify", file] => Command::Verify(file.into()),
+            ["convert", input, output] => Command::Convert(input.into(), output.into()),
+            // Still takes the old positional `batch <games> <parallel>`
+            ["batch", rest @ ..] if rest.len() <= 2 => {
+                if let Some(games) = rest.first() {
+                    cli.games = parse_number("games", games)?;
+                }
+                if let Some(parallel) = rest.get(1) {
+                    cli.parallel = parse_number("parallel", parallel)?;
+                }
+                Command::Batch
+            }
+            [command @ ("replay" | "verify" | "convert"), ..] => {
+                return Err(format!("wrong number of files for '{}'", command).into());
+            }
+            [command @ ("play" | "headless" | "tournament" | "batch"), extra, ..] => {
+                return Err(format!("unexpected argument '{}' after '{}'", extra, command).into());
+            }
+            [other, ..] => return Err(format!("unknown command '{}'", other).into()),
+        };
+        Ok(cli)
+    }
+
+    /// Load both configs and apply every flag that overrides them.
+    pub fn load_configs(&self) -> Result<(GameConfig, SimulationConfig), Box<dyn Error>> {
+        let mut config = match &self.config_path {
+            Some(path) => GameConfig::load_from(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+            None => GameConfig::load().unwrap_or_else(|e| {
+                eprintln!("Failed to load config: {}. Using defaults.", e);
+                GameConfig::default()
+            }),
+        };
+        let mut sim_config = match &self.sim_config_path {
+            Some(path) => SimulationConfig::load_from(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+            None => SimulationConfig::load().unwrap_or_default(),
+        };
+
+        for (key, raw) in &self.overrides {
+            config = apply_override(&config, key, raw).map_err(|e| format!("--set {}: {}", key, e))?;
+        }
+        if let Some(seed) = self.seed {
+            config.terrain.seed = Some(seed);
+        }
+        if let Some(output) = &self.output {
+            let dir = |sub: &str| output.join(sub).to_string_lossy().into_owned();
+            config.replay.directory = dir("replays");
+            config.snapshots.directory = dir("states");
+            config.match_log.directory = dir("events");
+            sim_config.batch.output_dir = output.to_string_lossy().into_owned();
+        }
+
+        let mode = match self.command {
+            Command::Play => Some(SimulationMode::Visual),
+            Command::Headless | Command::Batch | Command::Tournament => Some(SimulationMode::Headless),
+            _ => self.mode,
+        };
+        if let Some(mode) = mode {
+            sim_config.modes.default = mode;
+        }
+        Ok((config, sim_config))
+    }
+}
+
+fn parse_mode(value: &str) -> Result<SimulationMode, Box<dyn Error>> {
+    match value {
+        "visual" => Ok(SimulationMode::Visual),
+        "headless" => Ok(SimulationMode::Headless),
+        _ => Err(format!("unknown mode '{}', expected visual or headless", value).into()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(what: &str, value: &str) -> Result<T, Box<dyn Error>> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", what, value).into())
+}
+
+/// Set the dotted `key` of `config` to `raw`, read as a TOML value (so `40`,
+/// `true`, `[1, 2]` and `"text"` all work; anything else is taken as a
+/// string). Numeric parts index into lists, as in `teams.1.controller`. Keys
+/// the config doesn't have are rejected rather than ignored.
+pub fn apply_override(config: &GameConfig, key: &str, raw: &str) -> Result<GameConfig, Box<dyn Error>> {
+    let value = toml::from_str::<toml::Table>(&format!("v = {}", raw))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+
+    let mut root = toml::Value::try_from(config)?;
+    let path: Vec<&str> = key.split('.').collect();
+    let (leaf, parents) = path.split_last().ok_or("empty key")?;
+    let mut node = &mut root;
+    for part in parents {
+        node = child_mut(node, part).ok_or_else(|| format!("no section '{}'", part))?;
+    }
+    match node {
+        toml::Value::Table(table) => {
+            table.insert(leaf.to_string(), value);
+        }
+        toml::Value::Array(_) => {
+            *child_mut(node, leaf).ok_or_else(|| format!("no entry {} in '{}'", leaf, parents.join(".")))? = value;
+        }
+        _ => return Err(format!("'{}' is not a section", parents.join(".")).into()),
+    }
+
+    let updated: GameConfig = root.try_into()?;
+    // Unknown fields are silently dropped on the way back, which is how typos show up
+    let mut check = &toml::Value::try_from(&updated)?;
+    for part in &path {
+        check = child(check, part).ok_or_else(|| format!("unknown setting '{}'", key))?;
+    }
+    Ok(updated)
+}
+
+/// `part` of a table, or the entry it numbers in a list.
+fn child<'v>(node: &'v toml::Value, part: &str) -> Option<&'v toml::Value> {
+    match (node, part.parse::<usize>()) {
+        (toml::Value::Array(items), Ok(index)) => items.get(index),
+        _ => node.get(part),
+    }
+}
+
+fn child_mut<'v>(node: &'v mut toml::Value, part: &str) -> Option<&'v mut toml::Value> {
+    match (node, part.parse::<usize>()) {
+        (toml::Value::Array(items), Ok(index)) => items.get_mut(index),
+        (node, _) => node.get_mut(part),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, Box<dyn Error>> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn no_arguments_runs_the_configured_mode() {
+        let cli = parse("").unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(cli.games, 100);
+        assert_eq!(cli.parallel, 4);
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let cli = parse("headless --seed 42 --output out --set game.max_turns=50 --set combat.base_damage=40").unwrap();
+        assert_eq!(cli.command, Command::Headless);
+        assert_eq!(cli.seed, Some(42));
+        assert_eq!(cli.output, Some(PathBuf::from("out")));
+        assert_eq!(cli.overrides, vec![
+            ("game.max_turns".to_string(), "50".to_string()),
+            ("combat.base_damage".to_string(), "40".to_string()),
+        ]);
+
+        let cli = parse("tournament --controllers random,random --games 3").unwrap();
+        assert_eq!(cli.command, Command::Tournament);
+        assert_eq!(cli.controllers, ["random", "random"]);
+        assert_eq!(cli.games, 3);
+
+        assert_eq!(parse("convert a.json b.abr").unwrap().command, Command::Convert("a.json".into(), "b.abr".into()));
+        assert_eq!(parse("--mode headless").unwrap().mode, Some(SimulationMode::Headless));
+    }
+
+    #[test]
+    fn batch_takes_positional_counts() {
+        let cli = parse("batch 20 2").unwrap();
+        assert_eq!(cli.command, Command::Batch);
+        assert_eq!((cli.games, cli.parallel), (20, 2));
+
+        let cli = parse("batch --games 7").unwrap();
+        assert_eq!((cli.games, cli.parallel), (7, 4));
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        assert_eq!(parse("batch nonsense --help").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse("--frobnicate").is_err());
+        assert!(parse("--seed").is_err());
+        assert!(parse("--seed lots").is_err());
+        assert!(parse("--mode sideways").is_err());
+        assert!(parse("--set game.max_turns").is_err());
+        assert!(parse("replay").is_err());
+        assert!(parse("play extra").is_err());
+        assert!(parse("dance").is_err());
+    }
+
+    #[test]
+    fn overrides_nested_keys() {
+        let config = GameConfig::default();
+        let updated = apply_override(&config, "combat.base_damage", "40").unwrap();
+        assert_eq!(updated.combat.base_damage, 40.0);
+        let updated = apply_override(&updated, "game.max_turns", "50").unwrap();
+        assert_eq!(updated.game.max_turns, 50);
+        assert_eq!(updated.combat.base_damage, 40.0);
+    }
+
+    #[test]
+    fn overrides_list_entries() {
+        let config = GameConfig::default();
+        let updated = apply_override(&config, "teams.1.controller", "scripted").unwrap();
+        assert_eq!(updated.teams[1].controller, "scripted");
+        assert_eq!(updated.teams[0].controller, config.teams[0].controller);
+
+        let updated = apply_override(&config, "teams.0.spawn", "[3, -4]").unwrap();
+        assert_eq!(updated.teams[0].spawn, (3, -4));
+        let updated = apply_override(&updated, "teams.0.spawn.1", "2").unwrap();
+        assert_eq!(updated.teams[0].spawn, (3, 2));
+
+        assert!(apply_override(&config, "teams.5.controller", "random").is_err());
+        assert!(apply_override(&config, "teams.first.controller", "random").is_err());
+    }
+
+    #[test]
+    fn rejects_type_mismatches() {
+        let config = GameConfig::default();
+        assert!(apply_override(&config, "game.max_turns", "lots").is_err());
+        assert!(apply_override(&config, "game.max_turns", "-5").is_err());
+        assert!(apply_override(&config, "teams.0.spawn", "\"north\"").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let config = GameConfig::default();
+        assert!(apply_override(&config, "game.max_turn", "50").is_err());
+        assert!(apply_override(&config, "gaem.max_turns", "50").is_err());
+        assert!(apply_override(&config, "teams.0.colour", "[1, 0, 0]").is_err());
+        assert!(apply_override(&config, "game.max_turns.extra", "1").is_err());
+        assert!(apply_override(&config, "", "1").is_err());
+    }
+}
@@ -1,6 +1,10 @@
 ﻿use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::Path;
 use bevy::prelude::*;
+use crate::units::{StructureKind, UnitType};
+use crate::world::HexCoord;
+use crate::world::resource::Resource as Stockpile;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Resource)]
 pub struct GameConfig {
@@ -8,6 +12,68 @@ pub struct GameConfig {
     pub combat: CombatSettings,
     pub units: UnitSettings,
     pub performance: PerformanceSettings,
+    #[serde(default)]
+    pub terrain: TerrainSettings,
+    #[serde(default)]
+    pub vision: VisionSettings,
+    #[serde(default)]
+    pub economy: EconomySettings,
+    #[serde(default)]
+    pub production: ProductionSettings,
+    #[serde(default)]
+    pub victory: VictorySettings,
+    #[serde(default)]
+    pub objectives: ObjectiveSettings,
+    #[serde(default)]
+    pub communication: CommunicationSettings,
+    #[serde(default)]
+    pub replay: ReplaySettings,
+    #[serde(default)]
+    pub snapshots: SnapshotSettings,
+    #[serde(default)]
+    pub match_log: MatchLogSettings,
+    /// Every side in the match, in `Team` index order.
+    #[serde(default = "default_teams")]
+    pub teams: Vec<TeamSettings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamSettings {
+    pub name: String,
+    /// RGB, 0.0 - 1.0.
+    pub color: [f32; 3],
+    /// Name of the AI controller driving this team, e.g. "random".
+    #[serde(default = "default_controller")]
+    pub controller: String,
+    /// Centre of the team's starting area, axial (q, r).
+    pub spawn: (i32, i32),
+    /// Teams with the same alliance fight and win together when
+    /// `victory.mode = "alliances"`. Unset means the team stands alone.
+    #[serde(default)]
+    pub alliance: Option<u32>,
+}
+
+fn default_controller() -> String {
+    "random".to_string()
+}
+
+fn default_teams() -> Vec<TeamSettings> {
+    vec![
+        TeamSettings {
+            name: "Red".to_string(),
+            color: [0.8, 0.2, 0.2],
+            controller: default_controller(),
+            spawn: (0, -5),
+            alliance: None,
+        },
+        TeamSettings {
+            name: "Blue".to_string(),
+            color: [0.2, 0.2, 0.8],
+            controller: default_controller(),
+            spawn: (0, 5),
+            alliance: None,
+        },
+    ]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,9 +104,446 @@ pub struct PerformanceSettings {
     pub metrics_interval: f32,
 }
 
+/// Random terrain generation. All densities at zero gives the original flat map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerrainSettings {
+    pub forest_density: f32,
+    pub mountain_density: f32,
+    pub water_density: f32,
+    /// Hexes within this distance of a team's spawn point are kept as plains.
+    pub spawn_clearance: i32,
+    /// Fixed seed for the terrain layout; a fresh one is drawn when unset.
+    pub seed: Option<u64>,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            forest_density: 0.0,
+            mountain_density: 0.0,
+            water_density: 0.0,
+            spawn_clearance: 3,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisionSettings {
+    /// When false every team observes the whole map, as before fog of war existed.
+    pub fog_of_war: bool,
+    /// Vision radius in hexes given to every spawned unit.
+    pub unit_range: i32,
+}
+
+impl Default for VisionSettings {
+    fn default() -> Self {
+        Self {
+            fog_of_war: false,
+            unit_range: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EconomySettings {
+    pub enabled: bool,
+    /// Workers spawned per team in addition to `game.units_per_team` fighters.
+    pub workers_per_team: usize,
+    /// Nodes of each resource kind placed for every team, each set the same
+    /// distance from every spawn.
+    pub nodes_per_kind: usize,
+    pub node_amount: i32,
+    /// Taken from a node by one worker's `Gather` action.
+    pub gather_rate: i32,
+    /// Added back to every node at the end of each turn, up to `node_amount`.
+    pub regen_per_turn: i32,
+}
+
+impl Default for EconomySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            workers_per_team: 0,
+            nodes_per_kind: 2,
+            node_amount: 500,
+            gather_rate: 10,
+            regen_per_turn: 1,
+        }
+    }
+}
+
+/// Price and training time of one unit type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitCost {
+    pub energy: i32,
+    pub metal: i32,
+    #[serde(default)]
+    pub knowledge: i32,
+    pub build_turns: u32,
+}
+
+impl UnitCost {
+    pub fn resources(&self) -> Stockpile {
+        Stockpile { energy: self.energy, metal: self.metal, knowledge: self.knowledge }
+    }
+}
+
+/// Price, construction time and durability of one structure kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureCost {
+    pub energy: i32,
+    pub metal: i32,
+    #[serde(default)]
+    pub knowledge: i32,
+    pub build_turns: u32,
+    pub health: f32,
+}
+
+impl StructureCost {
+    pub fn resources(&self) -> Stockpile {
+        Stockpile { energy: self.energy, metal: self.metal, knowledge: self.knowledge }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductionSettings {
+    pub enabled: bool,
+    /// Give each team a finished base behind its spawn line.
+    pub start_with_base: bool,
+    pub worker: UnitCost,
+    pub fighter: UnitCost,
+    pub scout: UnitCost,
+    pub base: StructureCost,
+    pub barracks: StructureCost,
+}
+
+impl ProductionSettings {
+    pub fn unit_cost(&self, unit_type: UnitType) -> &UnitCost {
+        match unit_type {
+            UnitType::Worker => &self.worker,
+            UnitType::Fighter => &self.fighter,
+            UnitType::Scout => &self.scout,
+        }
+    }
+
+    pub fn structure_cost(&self, kind: StructureKind) -> &StructureCost {
+        match kind {
+            StructureKind::Base => &self.base,
+            StructureKind::Barracks => &self.barracks,
+        }
+    }
+}
+
+impl Default for ProductionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_with_base: true,
+            worker: UnitCost { energy: 50, metal: 0, knowledge: 0, build_turns: 10 },
+            fighter: UnitCost { energy: 50, metal: 50, knowledge: 0, build_turns: 15 },
+            scout: UnitCost { energy: 40, metal: 20, knowledge: 10, build_turns: 10 },
+            base: StructureCost { energy: 200, metal: 200, knowledge: 0, build_turns: 40, health: 500.0 },
+            barracks: StructureCost { energy: 100, metal: 150, knowledge: 0, build_turns: 25, health: 300.0 },
+        }
+    }
+}
+
+/// One way a match can be decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VictoryRule {
+    /// A team with no units and no structures left is out.
+    Elimination,
+    /// First team to `points_to_win` wins.
+    Points,
+    /// A team whose base is destroyed is out, whatever it has left.
+    Objective,
+}
+
+/// A measure used to pick a winner when the turn limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tiebreak {
+    /// Most units left alive.
+    Units,
+    /// Most total health across remaining units.
+    Health,
+    /// Most damage dealt over the match.
+    Damage,
+    /// Most points scored.
+    Points,
+}
+
+/// Who shares a win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VictoryMode {
+    /// Every team for itself.
+    #[default]
+    FreeForAll,
+    /// Teams in the same alliance don't fight each other and win or lose as one side.
+    Alliances,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VictorySettings {
+    #[serde(default)]
+    pub mode: VictoryMode,
+    /// Rules in force for the match.
+    pub rules: Vec<VictoryRule>,
+    pub points_to_win: u32,
+    pub points_per_kill: u32,
+    pub points_per_structure: u32,
+    /// Tried in order when `game.max_turns` is reached; the first one with a
+    /// sole leader decides the match. Empty, or level on all of them, is a draw.
+    #[serde(default = "default_tiebreaks")]
+    pub timeout_tiebreaks: Vec<Tiebreak>,
+}
+
+fn default_tiebreaks() -> Vec<Tiebreak> {
+    vec![Tiebreak::Units, Tiebreak::Health, Tiebreak::Damage, Tiebreak::Points]
+}
+
+impl VictorySettings {
+    pub fn has(&self, rule: VictoryRule) -> bool {
+        self.rules.contains(&rule)
+    }
+}
+
+/// Also reads the `most_health` rule of older configs, which picked the
+/// healthiest team at the turn limit and is now `timeout_tiebreaks = ["health"]`.
+impl<'de> Deserialize<'de> for VictorySettings {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum RuleRepr {
+            Elimination,
+            Points,
+            Objective,
+            MostHealth,
+        }
+
+        #[derive(Deserialize)]
+        struct Repr {
+            #[serde(default)]
+            mode: VictoryMode,
+            rules: Vec<RuleRepr>,
+            points_to_win: u32,
+            points_per_kill: u32,
+            points_per_structure: u32,
+            timeout_tiebreaks: Option<Vec<Tiebreak>>,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let most_health = repr.rules.iter().any(|rule| matches!(rule, RuleRepr::MostHealth));
+        let rules = repr.rules.into_iter()
+            .filter_map(|rule| match rule {
+                RuleRepr::Elimination => Some(VictoryRule::Elimination),
+                RuleRepr::Points => Some(VictoryRule::Points),
+                RuleRepr::Objective => Some(VictoryRule::Objective),
+                RuleRepr::MostHealth => None,
+            })
+            .collect();
+        let timeout_tiebreaks = match repr.timeout_tiebreaks {
+            Some(tiebreaks) => tiebreaks,
+            None if most_health => vec![Tiebreak::Health],
+            None => default_tiebreaks(),
+        };
+
+        Ok(Self {
+            mode: repr.mode,
+            rules,
+            points_to_win: repr.points_to_win,
+            points_per_kill: repr.points_per_kill,
+            points_per_structure: repr.points_per_structure,
+            timeout_tiebreaks,
+        })
+    }
+}
+
+impl Default for VictorySettings {
+    fn default() -> Self {
+        Self {
+            mode: VictoryMode::FreeForAll,
+            rules: vec![VictoryRule::Elimination, VictoryRule::Objective],
+            points_to_win: 50,
+            points_per_kill: 1,
+            points_per_structure: 5,
+            timeout_tiebreaks: default_tiebreaks(),
+        }
+    }
+}
+
+/// King-of-the-hill capture points. Points scored here count toward the `points` victory rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectiveSettings {
+    pub enabled: bool,
+    /// Axial (q, r) of every capture point. Always generated as plains.
+    pub positions: Vec<(i32, i32)>,
+    /// Turns a team must hold a point alone to take it over.
+    pub capture_turns: u32,
+    /// Awarded to the owner of each point at the end of every turn.
+    pub points_per_turn: u32,
+}
+
+impl ObjectiveSettings {
+    /// Capture point hexes in play, none when the mode is off.
+    pub fn coords(&self) -> Vec<HexCoord> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        self.positions.iter().map(|(q, r)| HexCoord::new(*q, *r)).collect()
+    }
+}
+
+impl Default for ObjectiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            positions: vec![(0, 0)],
+            capture_turns: 3,
+            points_per_turn: 1,
+        }
+    }
+}
+
+/// Team message passing between AI agents. Messages sent on one turn
+/// arrive in the next turn's observation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationSettings {
+    pub enabled: bool,
+    /// Floats per message; longer vectors are truncated, shorter ones zero-padded.
+    pub message_size: usize,
+    /// Messages a single unit may send each turn.
+    pub max_per_unit: usize,
+    /// Messages a whole team may send each turn, across all its units.
+    pub max_per_team: usize,
+}
+
+impl Default for CommunicationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message_size: 4,
+            max_per_unit: 1,
+            max_per_team: 8,
+        }
+    }
+}
+
+/// What goes into a saved replay, and how it is written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaySettings {
+    /// Turns between full-board keyframes; 0 keeps only the deltas.
+    pub keyframe_interval: u32,
+    /// Where headless matches save their replay.
+    #[serde(default = "default_replay_directory")]
+    pub directory: String,
+    #[serde(default)]
+    pub format: ReplayFormat,
+    /// Deflate the body of binary replays.
+    #[serde(default = "default_compress")]
+    pub compress: bool,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: 10,
+            directory: default_replay_directory(),
+            format: ReplayFormat::default(),
+            compress: true,
+        }
+    }
+}
+
+fn default_compress() -> bool {
+    true
+}
+
+fn default_replay_directory() -> String {
+    "data/replays".to_string()
+}
+
+/// How often `StateRecorder` snapshots the match, and where it streams them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotSettings {
+    /// Turns between snapshots; 0 turns them off.
+    pub interval: u32,
+    /// Directory for the per-match `.jsonl` files.
+    pub directory: String,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            interval: 1,
+            directory: "data/states".to_string(),
+        }
+    }
+}
+
+/// Where the per-match `MatchEvent` log goes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchLogSettings {
+    pub enabled: bool,
+    /// Directory for the per-match `.jsonl` files.
+    pub directory: String,
+}
+
+impl Default for MatchLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: "data/events".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayFormat {
+    /// Pretty-printed JSON, readable by hand and by older builds.
+    #[default]
+    Json,
+    /// The compact `engine::replay_format` encoding.
+    Binary,
+}
+
+impl ReplayFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReplayFormat::Json => "json",
+            ReplayFormat::Binary => "abr",
+        }
+    }
+
+    /// Picked from a file name: `.json` is JSON, anything else binary.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ReplayFormat::Json,
+            _ => ReplayFormat::Binary,
+        }
+    }
+}
+
 impl GameConfig {
+    /// Every team's spawn point, in team order.
+    pub fn spawn_points(&self) -> Vec<HexCoord> {
+        self.teams.iter().map(|t| HexCoord::new(t.spawn.0, t.spawn.1)).collect()
+    }
+
+    pub const DEFAULT_PATH: &'static str = "configs/game_config.toml";
+
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_str = fs::read_to_string("configs/game_config.toml")?;
+        Self::load_from(Path::new(Self::DEFAULT_PATH))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_str = fs::read_to_string(path)?;
         let config: GameConfig = toml::from_str(&config_str)?;
         Ok(config)
     }
@@ -69,6 +572,17 @@ impl Default for GameConfig {
                 enable_metrics: true,
                 metrics_interval: 5.0,
             },
+            terrain: TerrainSettings::default(),
+            vision: VisionSettings::default(),
+            economy: EconomySettings::default(),
+            production: ProductionSettings::default(),
+            victory: VictorySettings::default(),
+            objectives: ObjectiveSettings::default(),
+            communication: CommunicationSettings::default(),
+            replay: ReplaySettings::default(),
+            snapshots: SnapshotSettings::default(),
+            match_log: MatchLogSettings::default(),
+            teams: default_teams(),
         }
     }
 }
@@ -77,12 +591,61 @@ pub struct ConfigPlugin;
 
 impl Plugin for ConfigPlugin {
     fn build(&self, app: &mut App) {
-        let config = GameConfig::load().unwrap_or_else(|e| {
-            warn!("Failed to load config: {}. Using defaults.", e);
-            GameConfig::default()
-        });
+        // Configs inserted up front, e.g. built from command-line flags, win;
+        // without a SimulationConfig the app runs in visual mode
+        app.init_resource::<crate::config::SimulationConfig>();
+        if !app.world.contains_resource::<GameConfig>() {
+            let config = GameConfig::load().unwrap_or_else(|e| {
+                warn!("Failed to load config: {}. Using defaults.", e);
+                GameConfig::default()
+            });
+            app.insert_resource(config);
+        }
         
-        app.insert_resource(config);
+        app.add_systems(PreStartup, crate::units::teams::setup_team_roster);
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VICTORY: &str = "points_to_win = 50\npoints_per_kill = 1\npoints_per_structure = 5\n";
+
+    #[test]
+    fn most_health_reads_as_a_health_tiebreak() {
+        let victory: VictorySettings = toml::from_str(&format!("rules = [\"elimination\", \"most_health\"]\n{VICTORY}")).unwrap();
+        assert_eq!(victory.rules, vec![VictoryRule::Elimination]);
+        assert_eq!(victory.timeout_tiebreaks, vec![Tiebreak::Health]);
+    }
+
+    #[test]
+    fn explicit_tiebreaks_win_over_most_health() {
+        let victory: VictorySettings =
+            toml::from_str(&format!("rules = [\"most_health\"]\ntimeout_tiebreaks = [\"damage\"]\n{VICTORY}")).unwrap();
+        assert!(victory.rules.is_empty());
+        assert_eq!(victory.timeout_tiebreaks, vec![Tiebreak::Damage]);
+    }
+
+    #[test]
+    fn victory_settings_default_their_tiebreaks() {
+        let victory: VictorySettings = toml::from_str(&format!("rules = [\"points\"]\n{VICTORY}")).unwrap();
+        assert_eq!(victory.rules, vec![VictoryRule::Points]);
+        assert_eq!(victory.mode, VictoryMode::FreeForAll);
+        assert_eq!(victory.timeout_tiebreaks, default_tiebreaks());
+    }
+
+    #[test]
+    fn unknown_rules_are_rejected() {
+        assert!(toml::from_str::<VictorySettings>(&format!("rules = [\"most_gold\"]\n{VICTORY}")).is_err());
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        let config = GameConfig::load_from(Path::new(GameConfig::DEFAULT_PATH)).unwrap();
+        let round_trip: GameConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip.victory.rules, config.victory.rules);
+        assert_eq!(round_trip.victory.timeout_tiebreaks, config.victory.timeout_tiebreaks);
+    }
+}
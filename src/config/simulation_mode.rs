@@ -7,6 +7,8 @@ pub struct SimulationConfig {
     pub modes: ModeSettings,
     pub visual: VisualModeSettings,
     pub headless: HeadlessModeSettings,
+    #[serde(default)]
+    pub batch: BatchSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,9 +39,56 @@ pub struct HeadlessModeSettings {
     pub timestep_hz: u32,
 }
 
+/// Where batch runs leave their results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchSettings {
+    /// Each run writes `results.csv` and `results.json` to a
+    /// `batch_<timestamp>` directory in here.
+    pub output_dir: String,
+    /// Also save every game's replay alongside the results.
+    pub save_replays: bool,
+    /// Wall-clock seconds a single game may run for; 0 for no limit.
+    pub game_timeout_secs: f64,
+    /// What happens to a game that runs past `game_timeout_secs`.
+    pub on_timeout: TimeoutAction,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: "data/batches".to_string(),
+            save_replays: false,
+            game_timeout_secs: 0.0,
+            on_timeout: TimeoutAction::Adjudicate,
+        }
+    }
+}
+
+impl BatchSettings {
+    pub fn game_timeout(&self) -> Option<std::time::Duration> {
+        (self.game_timeout_secs > 0.0).then(|| std::time::Duration::from_secs_f64(self.game_timeout_secs))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutAction {
+    /// Decide the game on the turn-limit tiebreaks, as if `max_turns` had been reached.
+    Adjudicate,
+    /// Drop the game from the results.
+    Abort,
+}
+
 impl SimulationConfig {
+    pub const DEFAULT_PATH: &'static str = "configs/simulation_modes.toml";
+
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_str = fs::read_to_string("configs/simulation_modes.toml")?;
+        Self::load_from(std::path::Path::new(Self::DEFAULT_PATH))
+    }
+
+    pub fn load_from(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_str = fs::read_to_string(path)?;
         let config: SimulationConfig = toml::from_str(&config_str)?;
         Ok(config)
     }
@@ -63,6 +112,7 @@ impl Default for SimulationConfig {
                 fixed_timestep: true,
                 timestep_hz: 1000,
             },
+            batch: BatchSettings::default(),
         }
     }
 }
@@ -1,11 +1,25 @@
 ﻿use bevy::prelude::*;
-use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::app::{PluginsState, ScheduleRunnerPlugin};
+use bevy::ecs::schedule::ExecutorKind;
+use std::io::Write;
 use std::thread;
-use std::sync::Arc;
-use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use std::sync::mpsc::channel;
+use std::sync::{Arc, Once};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
 use crate::config::{GameConfig, SimulationConfig, SimulationMode};
+use crate::config::simulation_mode::TimeoutAction;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::engine::ReplayRecorder;
+use crate::engine::replay::GameOutcome;
+use crate::game::{AdjudicateMatch, MatchResult, ResetMatch, TurnState, WinReason};
+use crate::game::turn_manager::GameAI;
+use crate::game::victory::TeamScore;
+use crate::units::TeamRoster;
+use crate::world::terrain::{generate_map, MapSeed};
 
 #[derive(Clone)]
 pub struct BatchGameConfig {
@@ -13,21 +27,112 @@ pub struct BatchGameConfig {
     pub sim_config: SimulationConfig,
     pub num_games: usize,
     pub parallel_games: usize,
+    /// Where this run's results go; replays land in its `replays` folder.
+    pub output_dir: PathBuf,
 }
 
+#[derive(Serialize)]
 pub struct GameResult {
     pub game_id: usize,
+    /// Seed the map was generated from.
+    pub seed: Option<u64>,
+    pub map_width: i32,
+    pub map_height: i32,
+    /// `name@version` of each team's controller, in team order.
+    pub controllers: Vec<String>,
     pub winner: GameOutcome,
+    pub reason: WinReason,
+    /// Ran past the batch's time limit and was adjudicated.
+    pub timed_out: bool,
     pub total_turns: u32,
     pub duration_secs: f64,
     pub final_tps: f64,
+    /// Each team's standing at the end: units left, damage dealt and so on.
+    pub scores: Vec<TeamScore>,
+    pub replay: Option<PathBuf>,
 }
 
-#[derive(Clone, Debug)]
-pub enum GameOutcome {
-    RedWins,
-    BlueWins,
-    Draw,
+/// A game that didn't finish: it ran past the batch's time limit with
+/// `on_timeout = "abort"`, or was abandoned by a second Ctrl-C.
+#[derive(Serialize)]
+pub struct AbortedGame {
+    pub game_id: usize,
+    pub reason: AbortReason,
+    pub turns: u32,
+    pub duration_secs: f64,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AbortReason {
+    Timeout,
+    Interrupted,
+}
+
+/// What a batch got through. After a Ctrl-C this covers only the games that
+/// were played; the rest were never started.
+pub struct BatchOutcome {
+    pub results: Vec<GameResult>,
+    pub aborted: Vec<AbortedGame>,
+    pub interrupted: bool,
+}
+
+/// Ctrl-C presses seen during the current batch. The first stops new games
+/// being started and lets the ones in progress finish, the second abandons
+/// those too, and a third exits straight away.
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+/// Whether a batch is watching for Ctrl-C. Outside one, Ctrl-C quits as usual.
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+fn interrupts() -> usize {
+    INTERRUPTS.load(Ordering::Relaxed)
+}
+
+/// Counts Ctrl-C presses for one batch, from `start` until dropped.
+struct InterruptWatch;
+
+impl InterruptWatch {
+    fn start() -> Self {
+        install_interrupt_handler();
+        INTERRUPTS.store(0, Ordering::Relaxed);
+        WATCHING.store(true, Ordering::Relaxed);
+        InterruptWatch
+    }
+}
+
+impl Drop for InterruptWatch {
+    fn drop(&mut self) {
+        WATCHING.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Take over Ctrl-C, once. The handler stays for the rest of the process,
+/// so it does what the default would whenever no batch is watching.
+fn install_interrupt_handler() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        thread::spawn(|| {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    eprintln!("Ctrl-C handling unavailable: {}", e);
+                    return;
+                }
+            };
+            runtime.block_on(async {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if !WATCHING.load(Ordering::Relaxed) {
+                        std::process::exit(130);
+                    }
+                    match INTERRUPTS.fetch_add(1, Ordering::Relaxed) {
+                        0 => eprintln!("\nInterrupted: finishing the games in progress (Ctrl-C again to abandon them)"),
+                        1 => eprintln!("\nAbandoning the games in progress (Ctrl-C again to quit now)"),
+                        _ => std::process::exit(130),
+                    }
+                }
+            });
+        });
+    });
 }
 
 pub struct BatchRunner {
@@ -39,38 +144,35 @@ impl BatchRunner {
         Self { config }
     }
     
-    pub fn run_batch(&self) -> Vec<GameResult> {
-        let mut results = Vec::new();
+    /// Play the batch on `parallel_games` worker threads. Games are handed out
+    /// from a shared counter as workers free up, so a long game holds up only
+    /// its own thread.
+    pub fn run_batch(&self) -> BatchOutcome {
+        let _watch = InterruptWatch::start();
+        let num_games = self.config.num_games;
+        let num_threads = self.config.parallel_games.min(num_games).max(1);
+        let next_game = Arc::new(AtomicUsize::new(0));
         let (tx, rx) = channel();
         let mut handles = Vec::new();
         
-        // Create thread pool
-        let num_threads = self.config.parallel_games.min(self.config.num_games);
-        let games_per_thread = self.config.num_games / num_threads;
-        let remainder = self.config.num_games % num_threads;
-        
-        println!("Starting batch run: {} games across {} threads", 
-            self.config.num_games, num_threads);
+        println!("Starting batch run: {} games across {} threads", num_games, num_threads);
         
-        for thread_id in 0..num_threads {
+        for _ in 0..num_threads {
             let sender = tx.clone();
             let config = self.config.clone();
-            
-            let thread_games = if thread_id < remainder {
-                games_per_thread + 1
-            } else {
-                games_per_thread
-            };
-            
-            let game_start_id = thread_id * games_per_thread + thread_id.min(remainder);
+            let next_game = next_game.clone();
             
             let handle = thread::spawn(move || {
-                for i in 0..thread_games {
-                    let game_id = game_start_id + i;
-                    println!("Thread {}: Starting game {}", thread_id, game_id);
-                    let result = run_single_game(game_id, &config);
-                    println!("Thread {}: Completed game {} - {:?}", thread_id, game_id, result.winner);
-                    sender.send(result).ok();
+                // One app per thread, reset between games rather than rebuilt
+                let mut app = build_batch_app(&config);
+                while interrupts() == 0 {
+                    let game_id = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game_id >= config.num_games {
+                        break;
+                    }
+                    if sender.send(play_game(&mut app, game_id, &config)).is_err() {
+                        break;
+                    }
                 }
             });
             
@@ -80,129 +182,314 @@ impl BatchRunner {
         // Drop our sender so receiver knows when all threads are done
         drop(tx);
         
-        // Collect results as they come in
-        let mut completed = 0;
-        while let Ok(result) = rx.recv() {
-            completed += 1;
-            println!("Progress: {}/{} games completed", completed, self.config.num_games);
-            results.push(result);
+        let mut outcome = BatchOutcome { results: Vec::new(), aborted: Vec::new(), interrupted: false };
+        let mut progress = Progress::new(num_games);
+        loop {
+            match rx.recv_timeout(Duration::from_millis(250)) {
+                Ok(Ok(result)) => {
+                    progress.turns += result.total_turns as u64;
+                    outcome.results.push(result);
+                }
+                Ok(Err(aborted)) => {
+                    progress.turns += aborted.turns as u64;
+                    outcome.aborted.push(aborted);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            progress.print(outcome.results.len(), outcome.aborted.len());
         }
+        println!();
         
         // Wait for all threads
         for handle in handles {
             handle.join().ok();
         }
         
-        results
+        outcome.interrupted = interrupts() > 0;
+        outcome.results.sort_by_key(|r| r.game_id);
+        outcome.aborted.sort_by_key(|a| a.game_id);
+        outcome
     }
 }
 
-// Use Arc<Mutex<>> to share result between systems and main thread
-fn run_single_game(game_id: usize, config: &BatchGameConfig) -> GameResult {
-    let start_time = Instant::now();
+/// The live progress line under a running batch.
+struct Progress {
+    total: usize,
+    start: Instant,
+    turns: u64,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Self { total, start: Instant::now(), turns: 0 }
+    }
     
-    // Create shared result
-    let result = Arc::new(Mutex::new(BatchGameResult {
-        game_id,
-        outcome: None,
-        turns: 0,
-        completed: false,
-    }));
+    fn print(&self, finished: usize, aborted: usize) {
+        let line = self.line(finished, aborted, self.start.elapsed().as_secs_f64(), interrupts() > 0);
+        print!("\r{}    ", line);
+        std::io::stdout().flush().ok();
+    }
     
-    // Create a minimal Bevy app for headless simulation
+    /// The progress line after `elapsed` seconds. The ETA assumes the games
+    /// left take as long as the ones done so far.
+    fn line(&self, finished: usize, aborted: usize, elapsed: f64, stopping: bool) -> String {
+        let done = finished + aborted;
+        let eta = if stopping {
+            "stopping".to_string()
+        } else if done == 0 {
+            "ETA --".to_string()
+        } else {
+            format!("ETA {:.0}s", elapsed / done as f64 * self.total.saturating_sub(done) as f64)
+        };
+        let aborted = if aborted > 0 { format!(", {} aborted", aborted) } else { String::new() };
+        format!("[{}/{}] {:.0}%{} | {:.0} turns/s | {:.1}s elapsed | {}",
+            done, self.total, done as f64 / self.total.max(1) as f64 * 100.0, aborted,
+            self.turns as f64 / elapsed.max(1e-9), elapsed, eta)
+    }
+}
+
+/// A headless app for batch games. There is no schedule runner: the caller
+/// steps it with `app.update()`, and with the timestep unpaced every update
+/// plays a turn, so games run as fast as the CPU allows.
+fn build_batch_app(config: &BatchGameConfig) -> App {
+    let mut sim_config = config.sim_config.clone();
+    sim_config.headless.fixed_timestep = false;
+    
+    // The configs go in first so the plugins use them instead of reading the files again
     let mut app = App::new();
+    app
+        .insert_resource(sim_config)
+        .insert_resource(config.game_config.clone());
     
-    // Set up headless mode
-    let timestep = 1.0 / config.sim_config.headless.timestep_hz as f64;
-    app.add_plugins(
-        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
-            Duration::from_secs_f64(timestep)
-        ))
-    );
+    app.add_plugins(MinimalPlugins.build().disable::<ScheduleRunnerPlugin>());
     
     // Add game plugins
     app.add_plugins((
         crate::config::ConfigPlugin,
         crate::engine::HeadlessPlugin,
-        crate::world::hex_grid::HexGridPlugin,
-        crate::units::SpawningPlugin,
-        crate::units::MovementPlugin,
-        crate::units::HealthPlugin,
-        crate::game::TurnManagerPlugin,
-        crate::game::CombatPlugin,
-        crate::game::VictoryPlugin,
-        crate::ai::AiIntegrationPlugin,
+        (
+            crate::world::hex_grid::HexGridPlugin,
+            crate::world::SpatialIndexPlugin,
+            crate::game::MatchEventsPlugin,
+            crate::units::SpawningPlugin,
+            crate::units::MovementPlugin,
+            crate::units::HealthPlugin,
+            crate::game::TurnManagerPlugin,
+            crate::game::CombatPlugin,
+            crate::game::VictoryPlugin,
+            crate::game::EconomyPlugin,
+            crate::game::ProductionPlugin,
+            crate::game::ObjectivesPlugin,
+            crate::game::SessionPlugin,
+            crate::ai::AiIntegrationPlugin,
+        ),
         crate::performance::MetricsPlugin,
     ));
+    if config.sim_config.batch.save_replays {
+        app.add_plugins(crate::engine::ReplayPlugin);
+    }
     
-    // Insert configs
-    app.insert_resource(config.sim_config.clone());
-    app.insert_resource(config.game_config.clone());
+    while app.plugins_state() == PluginsState::Adding {
+        thread::yield_now();
+    }
+    app.finish();
+    app.cleanup();
     
-    // Insert shared result
-    app.insert_resource(BatchGameResultHandle(result.clone()));
+    // Games already run side by side, so each one keeps to its own thread
+    // rather than fanning its systems out over the shared task pool
+    for (_, schedule) in app.world.resource_mut::<Schedules>().iter_mut() {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    }
+    app
+}
+
+/// Play one game on `app` and read its result straight out of the world.
+/// The first game is set up by `Startup`; every later one gets a fresh map
+/// and a `ResetMatch`, the same reset the visual client does on R.
+fn play_game(app: &mut App, game_id: usize, config: &BatchGameConfig) -> Result<GameResult, AbortedGame> {
+    let start_time = Instant::now();
     
-    app.add_systems(Update, check_game_end);
+    if app.world.resource::<TurnState>().turn > 0 {
+        let seed = config.game_config.terrain.seed.unwrap_or_else(rand::random);
+        let (terrain, resources) = generate_map(&config.game_config, seed);
+        app.world.insert_resource(terrain);
+        app.world.insert_resource(resources);
+        app.world.insert_resource(MapSeed(seed));
+        app.world.send_event(ResetMatch);
+        app.update();
+    }
     
-    // Run until game ends
-    app.run();
+    // Victory always ends the match by `max_turns`, unless the clock runs out first
+    let timeout = config.sim_config.batch.game_timeout();
+    let mut timed_out = false;
+    while !app.world.contains_resource::<MatchResult>() {
+        let abort = if interrupts() > 1 {
+            Some(AbortReason::Interrupted)
+        } else if !timed_out && timeout.is_some_and(|t| start_time.elapsed() >= t) {
+            timed_out = true;
+            let abort = timeout_abort(config.sim_config.batch.on_timeout);
+            if abort.is_none() {
+                app.world.send_event(AdjudicateMatch);
+            }
+            abort
+        } else {
+            None
+        };
+        if let Some(reason) = abort {
+            return Err(AbortedGame {
+                game_id,
+                reason,
+                turns: app.world.resource::<TurnState>().turn,
+                duration_secs: start_time.elapsed().as_secs_f64(),
+            });
+        }
+        app.update();
+    }
     
-    // Extract results from shared state
-    let result_lock = result.lock().unwrap();
     let duration = start_time.elapsed().as_secs_f64();
-    let final_tps = result_lock.turns as f64 / duration;
+    let world = &app.world;
+    let match_result = world.resource::<MatchResult>().clone();
+    let turns = world.resource::<TurnState>().turn;
+    let ai = world.get_resource::<GameAI>();
+    let controllers = world.resource::<TeamRoster>().iter()
+        .map(|info| {
+            let version = ai.and_then(|ai| ai.0.get(&info.team)).map_or("unknown", |c| c.version());
+            format!("{}@{}", info.controller, version)
+        })
+        .collect();
     
-    GameResult {
+    let mut replay = None;
+    // A reset puts a recorder in either way; it only records with the replay plugin
+    if let Some(recorder) = world.get_resource::<ReplayRecorder>().filter(|_| config.sim_config.batch.save_replays) {
+        let path = config.output_dir
+            .join("replays")
+            .join(format!("game_{:05}.{}", game_id, config.game_config.replay.format.extension()));
+        match recorder.save_replay(&path, &config.game_config, Some(&match_result)) {
+            Ok(()) => replay = Some(path),
+            Err(e) => eprintln!("Game {}: failed to save replay: {}", game_id, e),
+        }
+    }
+    
+    Ok(GameResult {
         game_id,
-        winner: result_lock.outcome.clone().unwrap_or(GameOutcome::Draw),
-        total_turns: result_lock.turns,
+        seed: world.get_resource::<MapSeed>().map(|s| s.0),
+        map_width: config.game_config.game.map_width,
+        map_height: config.game_config.game.map_height,
+        controllers,
+        winner: GameOutcome::from(&match_result),
+        reason: match_result.reason,
+        timed_out,
+        total_turns: turns,
         duration_secs: duration,
-        final_tps,
-    }
+        final_tps: turns as f64 / duration,
+        scores: match_result.scores,
+        replay,
+    })
 }
 
-#[derive(Resource)]
-struct BatchGameResultHandle(Arc<Mutex<BatchGameResult>>);
+/// Why a game that ran out of time is aborted, or `None` when it is
+/// adjudicated instead.
+fn timeout_abort(action: TimeoutAction) -> Option<AbortReason> {
+    match action {
+        TimeoutAction::Adjudicate => None,
+        TimeoutAction::Abort => Some(AbortReason::Timeout),
+    }
+}
 
-struct BatchGameResult {
-    game_id: usize,
-    outcome: Option<GameOutcome>,
-    turns: u32,
-    completed: bool,
+/// Everything about a batch run, as written to `results.json`.
+#[derive(Serialize)]
+struct BatchSummary<'a> {
+    num_games: usize,
+    parallel_games: usize,
+    duration_secs: f64,
+    /// Stopped with Ctrl-C, so only some of `num_games` were played.
+    interrupted: bool,
+    game_config: &'a GameConfig,
+    results: &'a [GameResult],
+    aborted: &'a [AbortedGame],
 }
 
-fn check_game_end(
-    game_over: Res<crate::game::GameOver>,
-    turn_state: Res<crate::game::TurnState>,
-    units: Query<&crate::units::Unit>,
-    result_handle: Res<BatchGameResultHandle>,
-    mut exit: EventWriter<AppExit>,
-) {
-    let mut result = result_handle.0.lock().unwrap();
+/// Write `results.csv`, one row per finished game with a column group per
+/// team, and `results.json` with the config the games were played under and
+/// any games that were aborted.
+fn write_results(
+    config: &BatchGameConfig,
+    roster: &TeamRoster,
+    outcome: &BatchOutcome,
+    duration_secs: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = &config.output_dir;
+    fs::create_dir_all(dir)?;
     
-    if !result.completed {
-        result.turns = turn_state.turn;
-        
-        if game_over.0 {
-            result.completed = true;
-            
-            // Count units to determine winner
-            let (red, blue) = units.iter().fold((0, 0), |(r, b), unit| {
-                match unit.team {
-                    crate::units::Team::Red => (r + 1, b),
-                    crate::units::Team::Blue => (r, b + 1),
-                }
-            });
-            
-            result.outcome = Some(match (red, blue) {
-                (0, 0) => GameOutcome::Draw,
-                (0, _) => GameOutcome::BlueWins,
-                (_, 0) => GameOutcome::RedWins,
-                _ => GameOutcome::Draw,
-            });
-            
-            exit.send(AppExit);
+    let summary = BatchSummary {
+        num_games: config.num_games,
+        parallel_games: config.parallel_games,
+        duration_secs,
+        interrupted: outcome.interrupted,
+        game_config: &config.game_config,
+        results: &outcome.results,
+        aborted: &outcome.aborted,
+    };
+    fs::write(dir.join("results.json"), serde_json::to_string_pretty(&summary)?)?;
+    
+    let mut header = vec![
+        "game_id", "seed", "map", "winner", "reason", "timed_out", "turns", "duration_secs", "tps",
+    ].into_iter().map(String::from).collect::<Vec<_>>();
+    for info in roster.iter() {
+        let team = info.name.to_lowercase();
+        for column in ["controller", "units", "health", "damage", "kills", "points"] {
+            header.push(format!("{}_{}", team, column));
+        }
+    }
+    header.push("replay".to_string());
+    
+    // Team names come from the config and may need quoting too
+    let mut csv = header.iter().map(|column| csv_field(column)).collect::<Vec<_>>().join(",") + "\n";
+    for result in &outcome.results {
+        let winner = match &result.winner {
+            GameOutcome::Win(teams) => teams.iter().map(|t| roster.name(*t)).collect::<Vec<_>>().join("+"),
+            GameOutcome::Draw => "draw".to_string(),
+        };
+        let mut row = vec![
+            result.game_id.to_string(),
+            result.seed.map_or(String::new(), |s| s.to_string()),
+            format!("{}x{}", result.map_width, result.map_height),
+            winner,
+            format!("{:?}", result.reason).to_lowercase(),
+            result.timed_out.to_string(),
+            result.total_turns.to_string(),
+            format!("{:.3}", result.duration_secs),
+            format!("{:.1}", result.final_tps),
+        ];
+        for info in roster.iter() {
+            let index = info.team.0 as usize;
+            row.push(result.controllers.get(index).cloned().unwrap_or_default());
+            match result.scores.iter().find(|s| s.team == info.team) {
+                Some(score) => row.extend([
+                    score.units.to_string(),
+                    format!("{:.0}", score.total_health),
+                    format!("{:.0}", score.damage_dealt),
+                    score.kills.to_string(),
+                    score.points.to_string(),
+                ]),
+                None => row.extend(std::iter::repeat_n(String::new(), 5)),
+            }
         }
+        row.push(result.replay.as_deref().map_or(String::new(), |p| p.display().to_string()));
+        csv += &row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",");
+        csv += "\n";
+    }
+    fs::write(dir.join("results.csv"), csv)?;
+    Ok(())
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
 }
 
@@ -215,55 +502,346 @@ impl Plugin for BatchRunnerPlugin {
 }
 
 // Command line interface for batch running
-pub fn run_batch_games(num_games: usize, parallel: usize) {
+pub fn run_batch_games(game_config: GameConfig, mut sim_config: SimulationConfig, num_games: usize, parallel: usize) {
     println!("AI Battle Arena - Batch Mode");
     println!("===========================");
     
-    // Load configs
-    let game_config = GameConfig::load().unwrap_or_default();
-    let mut sim_config = SimulationConfig::load().unwrap_or_default();
     sim_config.modes.default = SimulationMode::Headless; // Force headless
     
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let output_dir = Path::new(&sim_config.batch.output_dir).join(format!("batch_{}", timestamp));
+    
     let config = BatchGameConfig {
         game_config,
         sim_config,
         num_games,
         parallel_games: parallel,
+        output_dir,
     };
+    create_replay_dir(&config);
     
     let runner = BatchRunner::new(config);
     let start = Instant::now();
-    let results = runner.run_batch();
+    let outcome = runner.run_batch();
+    let results = &outcome.results;
     let total_duration = start.elapsed();
     
     // Print summary
-    let mut red_wins = 0;
-    let mut blue_wins = 0;
+    let roster = TeamRoster::from_config(&runner.config.game_config);
+    let mut wins = HashMap::new();
     let mut draws = 0;
+    let mut timeouts = 0;
     let mut total_tps = 0.0;
     let mut total_turns = 0;
     
-    for result in &results {
-        match result.winner {
-            GameOutcome::RedWins => red_wins += 1,
-            GameOutcome::BlueWins => blue_wins += 1,
+    for result in results {
+        match &result.winner {
+            GameOutcome::Win(teams) => {
+                for team in teams {
+                    *wins.entry(*team).or_insert(0) += 1;
+                }
+            }
             GameOutcome::Draw => draws += 1,
         }
+        if result.reason == WinReason::Timeout {
+            timeouts += 1;
+        }
         total_tps += result.final_tps;
         total_turns += result.total_turns;
     }
     
+    let played = results.len().max(1) as f64;
+    
     println!("\n========== BATCH RESULTS ==========");
-    println!("Total Games: {}", num_games);
+    if outcome.interrupted {
+        println!("Interrupted: {} of {} games finished", results.len(), num_games);
+    } else {
+        println!("Total Games: {}", num_games);
+    }
+    if !outcome.aborted.is_empty() {
+        println!("Aborted: {}", outcome.aborted.len());
+    }
     println!("Total Duration: {:.2}s", total_duration.as_secs_f64());
-    println!("Games/Second: {:.2}", num_games as f64 / total_duration.as_secs_f64());
+    println!("Games/Second: {:.2}", results.len() as f64 / total_duration.as_secs_f64());
     println!();
-    println!("Red Wins: {} ({:.1}%)", red_wins, red_wins as f64 / num_games as f64 * 100.0);
-    println!("Blue Wins: {} ({:.1}%)", blue_wins, blue_wins as f64 / num_games as f64 * 100.0);
-    println!("Draws: {} ({:.1}%)", draws, draws as f64 / num_games as f64 * 100.0);
+    for info in roster.iter() {
+        let team_wins = wins.get(&info.team).copied().unwrap_or(0);
+        println!("{} Wins: {} ({:.1}%)", info.name, team_wins, team_wins as f64 / played * 100.0);
+    }
+    println!("Draws: {} ({:.1}%)", draws, draws as f64 / played * 100.0);
+    println!("Decided at turn limit: {} ({:.1}%)", timeouts, timeouts as f64 / played * 100.0);
     println!();
-    println!("Average TPS: {:.2}", total_tps / results.len() as f64);
-    println!("Average Turns/Game: {:.1}", total_turns as f64 / results.len() as f64);
+    println!("Average TPS: {:.2} per game", total_tps / played);
+    println!("Throughput: {:.0} turns/second across all threads", total_turns as f64 / total_duration.as_secs_f64());
+    println!("Average Turns/Game: {:.1}", total_turns as f64 / played);
     println!("Total Turns Simulated: {}", total_turns);
     println!("===================================");
+    
+    match write_results(&runner.config, &roster, &outcome, total_duration.as_secs_f64()) {
+        Ok(()) => println!("Results saved to: {}", runner.config.output_dir.display()),
+        Err(e) => eprintln!("Failed to save batch results: {}", e),
+    }
+}
+
+fn create_replay_dir(config: &BatchGameConfig) {
+    if config.sim_config.batch.save_replays {
+        if let Err(e) = fs::create_dir_all(config.output_dir.join("replays")) {
+            eprintln!("Failed to create replay directory: {}", e);
+        }
+    }
+}
+
+/// Wins, draws and losses of one tournament entry.
+#[derive(Default)]
+struct Standing {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+/// Play every ordered pairing of `controllers` on a two-team config, so each
+/// entry plays both seats, `games` games per pairing. Each pairing's results
+/// are exported like a batch; the standings go to `standings.csv`.
+pub fn run_tournament(
+    game_config: GameConfig,
+    mut sim_config: SimulationConfig,
+    controllers: &[String],
+    games: usize,
+    parallel: usize,
+) {
+    if controllers.len() < 2 {
+        eprintln!("A tournament needs at least two --controllers");
+        return;
+    }
+    if game_config.teams.len() != 2 {
+        eprintln!("A tournament needs a two-team config, this one has {} teams", game_config.teams.len());
+        return;
+    }
+    if let Some(unknown) = controllers.iter().find(|c| crate::ai::integration::controller_by_name(c, &game_config.communication).is_none()) {
+        eprintln!("Unknown controller '{}'", unknown);
+        return;
+    }
+    sim_config.modes.default = SimulationMode::Headless;
+    
+    // The same controller can be entered twice; tell the entries apart
+    let labels: Vec<String> = controllers.iter().enumerate()
+        .map(|(i, name)| match controllers[..i].iter().filter(|c| *c == name).count() {
+            0 => name.clone(),
+            n => format!("{}#{}", name, n + 1),
+        })
+        .collect();
+    
+    println!("AI Battle Arena - Tournament");
+    println!("============================");
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let output_dir = Path::new(&sim_config.batch.output_dir).join(format!("tournament_{}", timestamp));
+    let mut standings: Vec<Standing> = labels.iter().map(|_| Standing::default()).collect();
+    
+    for (a, b) in (0..labels.len()).flat_map(|a| (0..labels.len()).map(move |b| (a, b))).filter(|(a, b)| a != b) {
+        println!("\n{} vs {}", labels[a], labels[b]);
+        let mut pairing_config = game_config.clone();
+        pairing_config.teams[0].controller = controllers[a].clone();
+        pairing_config.teams[1].controller = controllers[b].clone();
+        
+        let config = BatchGameConfig {
+            game_config: pairing_config,
+            sim_config: sim_config.clone(),
+            num_games: games,
+            parallel_games: parallel,
+            output_dir: output_dir.join(format!("{}_vs_{}", labels[a], labels[b])),
+        };
+        create_replay_dir(&config);
+        
+        let start = Instant::now();
+        let outcome = BatchRunner::new(config.clone()).run_batch();
+        let roster = TeamRoster::from_config(&config.game_config);
+        if let Err(e) = write_results(&config, &roster, &outcome, start.elapsed().as_secs_f64()) {
+            eprintln!("Failed to save results for {} vs {}: {}", labels[a], labels[b], e);
+        }
+        
+        for result in &outcome.results {
+            match &result.winner {
+                GameOutcome::Win(teams) if teams.contains(&crate::units::Team(0)) => {
+                    standings[a].wins += 1;
+                    standings[b].losses += 1;
+                }
+                GameOutcome::Win(_) => {
+                    standings[b].wins += 1;
+                    standings[a].losses += 1;
+                }
+                GameOutcome::Draw => {
+                    standings[a].draws += 1;
+                    standings[b].draws += 1;
+                }
+            }
+        }
+        if outcome.interrupted {
+            println!("Tournament interrupted; standings cover the games played so far");
+            break;
+        }
+    }
+    
+    let mut order: Vec<usize> = (0..labels.len()).collect();
+    order.sort_by_key(|i| (std::cmp::Reverse(standings[*i].wins), standings[*i].losses));
+    
+    println!("\n========== STANDINGS ==========");
+    let mut csv = "controller,wins,draws,losses\n".to_string();
+    for i in order {
+        let s = &standings[i];
+        println!("{:<16} W {:>4}  D {:>4}  L {:>4}", labels[i], s.wins, s.draws, s.losses);
+        csv += &format!("{},{},{},{}\n", csv_field(&labels[i]), s.wins, s.draws, s.losses);
+    }
+    println!("===============================");
+    
+    match fs::create_dir_all(&output_dir).and_then(|_| fs::write(output_dir.join("standings.csv"), csv)) {
+        Ok(()) => println!("Results saved to: {}", output_dir.display()),
+        Err(e) => eprintln!("Failed to save standings: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Team;
+
+    /// Split one CSV line, undoing `csv_field`'s quoting.
+    fn parse_line(line: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    fn batch_config(dir: PathBuf) -> BatchGameConfig {
+        let mut game_config = GameConfig::default();
+        game_config.teams[0].name = "Red, \"the\" first".to_string();
+        BatchGameConfig {
+            game_config,
+            sim_config: SimulationConfig::default(),
+            num_games: 1,
+            parallel_games: 1,
+            output_dir: dir,
+        }
+    }
+
+    fn result(game_id: usize) -> GameResult {
+        GameResult {
+            game_id,
+            seed: Some(42),
+            map_width: 20,
+            map_height: 16,
+            controllers: vec!["random@1".to_string(), "random@1".to_string()],
+            winner: GameOutcome::Win(vec![Team(1)]),
+            reason: WinReason::Points,
+            timed_out: false,
+            total_turns: 57,
+            duration_secs: 1.25,
+            final_tps: 45.6,
+            scores: vec![
+                TeamScore { team: Team(0), units: 2, total_health: 150.0, damage_dealt: 300.0, kills: 3, points: 12 },
+                TeamScore { team: Team(1), units: 5, total_health: 420.0, damage_dealt: 410.0, kills: 6, points: 50 },
+            ],
+            replay: Some(PathBuf::from("replays/game_0001.abr")),
+        }
+    }
+
+    #[test]
+    fn results_csv_reads_back() {
+        let dir = std::env::temp_dir().join(format!("arena_results_csv_{}", std::process::id()));
+        let config = batch_config(dir.clone());
+        let roster = TeamRoster::from_config(&config.game_config);
+        let outcome = BatchOutcome { results: vec![result(1)], aborted: Vec::new(), interrupted: false };
+        write_results(&config, &roster, &outcome, 2.0).unwrap();
+        let csv = fs::read_to_string(dir.join("results.csv")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let lines: Vec<_> = csv.lines().map(parse_line).collect();
+        assert_eq!(lines.len(), 2);
+        let (header, row) = (&lines[0], &lines[1]);
+        assert_eq!(header.len(), row.len());
+        let column = |name: &str| &row[header.iter().position(|c| c == name).unwrap()];
+
+        assert_eq!(column("game_id"), "1");
+        assert_eq!(column("seed"), "42");
+        assert_eq!(column("map"), "20x16");
+        assert_eq!(column("winner"), "Blue");
+        assert_eq!(column("reason"), "points");
+        assert_eq!(column("turns"), "57");
+        assert_eq!(column("red, \"the\" first_controller"), "random@1");
+        assert_eq!(column("red, \"the\" first_kills"), "3");
+        assert_eq!(column("blue_points"), "50");
+        assert_eq!(column("blue_health"), "420");
+        assert_eq!(column("replay"), "replays/game_0001.abr");
+    }
+
+    #[test]
+    fn progress_shows_counts_rate_and_eta() {
+        let mut progress = Progress::new(10);
+        assert_eq!(progress.line(0, 0, 0.5, false), "[0/10] 0% | 0 turns/s | 0.5s elapsed | ETA --");
+
+        progress.turns = 800;
+        assert_eq!(progress.line(3, 1, 4.0, false), "[4/10] 40%, 1 aborted | 200 turns/s | 4.0s elapsed | ETA 6s");
+        assert_eq!(progress.line(4, 0, 4.0, true), "[4/10] 40% | 200 turns/s | 4.0s elapsed | stopping");
+        assert!(progress.line(10, 0, 8.0, false).ends_with("ETA 0s"));
+    }
+
+    #[test]
+    fn timeouts_abort_or_adjudicate_as_configured() {
+        assert!(timeout_abort(TimeoutAction::Adjudicate).is_none());
+        assert!(matches!(timeout_abort(TimeoutAction::Abort), Some(AbortReason::Timeout)));
+    }
+
+    /// A batch whose games run out of time as soon as they start.
+    fn timing_out_batch(on_timeout: TimeoutAction) -> BatchGameConfig {
+        let mut config = batch_config(std::env::temp_dir().join("arena_unused"));
+        config.game_config.terrain.seed = Some(7);
+        config.sim_config.batch.game_timeout_secs = 1e-9;
+        config.sim_config.batch.on_timeout = on_timeout;
+        config.num_games = 2;
+        config
+    }
+
+    #[test]
+    fn a_timed_out_game_is_adjudicated() {
+        let outcome = BatchRunner::new(timing_out_batch(TimeoutAction::Adjudicate)).run_batch();
+        assert!(outcome.aborted.is_empty());
+        assert_eq!(outcome.results.iter().map(|r| r.game_id).collect::<Vec<_>>(), vec![0, 1]);
+        for result in &outcome.results {
+            assert!(result.timed_out);
+            assert_eq!(result.reason, WinReason::Timeout);
+            assert_eq!(result.seed, Some(7));
+        }
+    }
+
+    #[test]
+    fn aborted_games_are_left_out_of_the_results() {
+        let outcome = BatchRunner::new(timing_out_batch(TimeoutAction::Abort)).run_batch();
+        assert!(outcome.results.is_empty());
+        assert!(!outcome.interrupted);
+        assert_eq!(outcome.aborted.iter().map(|a| a.game_id).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(outcome.aborted.iter().all(|a| matches!(a.reason, AbortReason::Timeout)));
+
+        // results.csv gets the header only; the aborted games are listed in results.json
+        let dir = std::env::temp_dir().join(format!("arena_aborted_{}", std::process::id()));
+        let config = batch_config(dir.clone());
+        let roster = TeamRoster::from_config(&config.game_config);
+        write_results(&config, &roster, &outcome, 1.0).unwrap();
+        let csv = fs::read_to_string(dir.join("results.csv")).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("results.json")).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(csv.lines().count(), 1);
+        assert_eq!(json["results"].as_array().unwrap().len(), 0);
+        assert_eq!(json["aborted"][1]["reason"], "timeout");
+    }
 }
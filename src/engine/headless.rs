@@ -1,21 +1,22 @@
 ﻿use bevy::prelude::*;
-use crate::config::SimulationConfig;
+use crate::config::{SimulationConfig, SimulationMode};
 
+/// Puts the app in headless mode. Adding it is what selects the mode: the
+/// `SimulationConfig` already in the app (or the default one) is switched to
+/// headless and its timestep used, so insert any config before this plugin.
 pub struct HeadlessPlugin;
 
 impl Plugin for HeadlessPlugin {
     fn build(&self, app: &mut App) {
-        let sim_config = SimulationConfig::load().unwrap_or_default();
+        let mut sim_config = app.world.get_resource::<SimulationConfig>().cloned().unwrap_or_default();
+        sim_config.modes.default = SimulationMode::Headless;
         
-        if sim_config.modes.default == crate::config::SimulationMode::Headless {
-            // Configure fixed timestep for headless mode
-            let timestep_hz = sim_config.headless.timestep_hz as f64;
-            
-            // Set fixed timestep
-            app.insert_resource(Time::<Fixed>::from_hz(timestep_hz));
-            
-            info!("Configured HEADLESS mode at {} Hz", sim_config.headless.timestep_hz);
-        }
+        // Configure fixed timestep for headless mode
+        let timestep_hz = sim_config.headless.timestep_hz as f64;
+        app.insert_resource(Time::<Fixed>::from_hz(timestep_hz));
+        
+        info!("Configured HEADLESS mode at {} Hz", sim_config.headless.timestep_hz);
+        app.insert_resource(sim_config);
     }
 }
 
@@ -0,0 +1,95 @@
+﻿use bevy::prelude::*;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::config::GameConfig;
+use crate::game::MatchEvent;
+
+/// Writes every `MatchEvent` of the current match to a JSON-lines file, as
+/// the canonical record for analytics, debugging and the replay viewer.
+#[derive(Resource)]
+pub struct MatchLog {
+    recording: bool,
+    path: Option<PathBuf>,
+    writer: Option<BufWriter<File>>,
+    written: usize,
+}
+
+impl MatchLog {
+    pub fn new() -> Self {
+        Self {
+            recording: true,
+            path: None,
+            writer: None,
+            written: 0,
+        }
+    }
+    
+    /// Open `match_events_<timestamp>.jsonl` in `directory` on first use.
+    fn open(&mut self, directory: &Path) -> Result<&mut BufWriter<File>, Box<dyn std::error::Error>> {
+        if self.writer.is_none() {
+            fs::create_dir_all(directory)?;
+            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S_%3f");
+            let path = directory.join(format!("match_events_{}.jsonl", timestamp));
+            self.writer = Some(BufWriter::new(File::create(&path)?));
+            self.path = Some(path);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+    
+    /// Append `events` and flush, so the log survives a crash up to this frame.
+    pub fn write<'a>(&mut self, directory: &Path, events: impl IntoIterator<Item = &'a MatchEvent>) {
+        if !self.recording {
+            return;
+        }
+        let mut count = 0;
+        let written = self.open(directory).and_then(|writer| {
+            for event in events {
+                serde_json::to_writer(&mut *writer, event)?;
+                writer.write_all(b"\n")?;
+                count += 1;
+            }
+            writer.flush()?;
+            Ok(())
+        });
+        match written {
+            Ok(()) => self.written += count,
+            Err(e) => {
+                eprintln!("Failed to write match events, no longer logging: {}", e);
+                self.recording = false;
+            }
+        }
+    }
+    
+    /// The file events go to, once the first one is written.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    
+    pub fn event_count(&self) -> usize {
+        self.written
+    }
+}
+
+pub struct MatchLogPlugin;
+
+impl Plugin for MatchLogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(MatchLog::new())
+            // Last, so everything sent this frame is already in
+            .add_systems(Last, write_match_events);
+    }
+}
+
+fn write_match_events(
+    mut events: EventReader<MatchEvent>,
+    config: Res<GameConfig>,
+    mut log: ResMut<MatchLog>,
+) {
+    if !config.match_log.enabled || events.is_empty() {
+        events.clear();
+        return;
+    }
+    log.write(Path::new(&config.match_log.directory), events.read());
+}
@@ -3,11 +3,14 @@ pub mod state;
 pub mod physics;
 pub mod headless;
 pub mod state_serialization;
+pub mod match_log;
 pub mod replay;
+pub mod replay_format;
 pub mod batch_runner;
 
 pub use headless::HeadlessPlugin;
 pub use state_serialization::{StateSerializationPlugin, StateRecorder};
+pub use match_log::{MatchLogPlugin, MatchLog};
 pub use replay::{ReplayPlugin, ReplayRecorder};
 pub use batch_runner::run_batch_games;
 
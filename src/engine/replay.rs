@@ -1,30 +1,385 @@
 ﻿use bevy::prelude::*;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use crate::world::HexCoord;
 use crate::world::actions::Action;
-use crate::units::Team;
+use crate::world::resource::{ResourceField, ResourceNode};
+use crate::world::terrain::{MapSeed, TerrainMap, TerrainType};
+use crate::units::{Unit, UnitId, Structure, HexPosition, Dead, Team, UnitType, StructureKind, TeamRoster};
+use crate::game::{MatchResult, MatchEvent, TurnState, CapturePoints};
+use crate::game::events::{MatchEventKind, Subject};
+use crate::game::objectives::CapturePoint;
+use crate::config::GameConfig;
+use crate::config::game_config::ReplayFormat;
+use crate::engine::replay_format;
+use crate::ai::NeuralMessage;
+use crate::game::turn_manager::GameAI;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Clone)]
 pub struct ReplayFrame {
     pub turn: u32,
     pub actions: Vec<ReplayAction>,
     pub rng_seed: u64,
+    /// Team messages sent this turn, delivered the next.
+    #[serde(default)]
+    pub messages: Vec<ReplayMessage>,
+    /// How the board changed over the turn, applied in order.
+    #[serde(default)]
+    pub changes: Vec<ReplayChange>,
+    /// The turn's `MatchEvent`s, the same ones the match log gets.
+    pub events: Vec<MatchEvent>,
+}
+
+/// Also reads frames from before they carried `MatchEvent`s, whose
+/// `Attack` events become match events. Their `Kill` events only echoed
+/// the frame's `Removed` changes and are dropped.
+impl<'de> Deserialize<'de> for ReplayFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum EventRepr {
+            Event(MatchEvent),
+            Legacy(LegacyEvent),
+        }
+
+        #[derive(Deserialize)]
+        enum LegacyEvent {
+            Attack { attacker: u32, defender: u32 },
+            Kill {},
+        }
+
+        #[derive(Deserialize)]
+        struct Repr {
+            turn: u32,
+            actions: Vec<ReplayAction>,
+            rng_seed: u64,
+            #[serde(default)]
+            messages: Vec<ReplayMessage>,
+            #[serde(default)]
+            changes: Vec<ReplayChange>,
+            #[serde(default)]
+            events: Vec<EventRepr>,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let events = repr.events.into_iter()
+            .filter_map(|event| match event {
+                EventRepr::Event(event) => Some(event),
+                EventRepr::Legacy(LegacyEvent::Attack { attacker, defender }) => Some(legacy_attack(repr.turn, attacker, defender)),
+                EventRepr::Legacy(LegacyEvent::Kill {}) => None,
+            })
+            .collect();
+        Ok(Self {
+            turn: repr.turn,
+            actions: repr.actions,
+            rng_seed: repr.rng_seed,
+            messages: repr.messages,
+            changes: repr.changes,
+            events,
+        })
+    }
+}
+
+/// An attack as replays recorded it before frames carried `MatchEvent`s.
+pub(crate) fn legacy_attack(turn: u32, attacker: u32, defender: u32) -> MatchEvent {
+    MatchEvent::new(turn, MatchEventKind::Attack {
+        attacker: Some(attacker),
+        target: Subject::Unit(defender),
+    })
+}
+
+impl ReplayFrame {
+    fn empty(turn: u32) -> Self {
+        Self {
+            turn,
+            actions: Vec::new(),
+            rng_seed: 0,
+            messages: Vec::new(),
+            changes: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PieceKind {
+    Unit(UnitType),
+    Structure(StructureKind),
+}
+
+/// A unit or structure on the board, keyed by its `UnitId`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReplayPiece {
+    pub id: u32,
+    pub team: Team,
+    pub kind: PieceKind,
+    pub coord: HexCoord,
+    pub health: f32,
+    pub max_health: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ReplayChange {
+    Spawned(ReplayPiece),
+    Moved { id: u32, to: HexCoord },
+    Health { id: u32, health: f32 },
+    Removed { id: u32 },
+    Point(CapturePoint),
+}
+
+/// The whole board as it stood at the end of `turn`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Keyframe {
+    pub turn: u32,
+    pub pieces: Vec<ReplayPiece>,
+    pub capture_points: Vec<CapturePoint>,
+}
+
+/// Board state a replay is rebuilt into, by the recorder to diff against
+/// and by the player to seek.
+#[derive(Clone, Default)]
+pub struct BoardState {
+    pub pieces: HashMap<u32, ReplayPiece>,
+    pub capture_points: Vec<CapturePoint>,
+}
+
+impl BoardState {
+    fn from_keyframe(keyframe: &Keyframe) -> Self {
+        Self {
+            pieces: keyframe.pieces.iter().map(|p| (p.id, *p)).collect(),
+            capture_points: keyframe.capture_points.clone(),
+        }
+    }
+
+    fn to_keyframe(&self, turn: u32) -> Keyframe {
+        let mut pieces: Vec<_> = self.pieces.values().copied().collect();
+        pieces.sort_by_key(|p| p.id);
+        Keyframe {
+            turn,
+            pieces,
+            capture_points: self.capture_points.clone(),
+        }
+    }
+
+    pub fn apply(&mut self, change: &ReplayChange) {
+        match change {
+            ReplayChange::Spawned(piece) => {
+                self.pieces.insert(piece.id, *piece);
+            }
+            ReplayChange::Moved { id, to } => {
+                if let Some(piece) = self.pieces.get_mut(id) {
+                    piece.coord = *to;
+                }
+            }
+            ReplayChange::Health { id, health } => {
+                if let Some(piece) = self.pieces.get_mut(id) {
+                    piece.health = *health;
+                }
+            }
+            ReplayChange::Removed { id } => {
+                self.pieces.remove(id);
+            }
+            ReplayChange::Point(point) => {
+                match self.capture_points.iter_mut().find(|p| p.coord == point.coord) {
+                    Some(existing) => *existing = *point,
+                    None => self.capture_points.push(*point),
+                }
+            }
+        }
+    }
+
+    /// Changes that turn `self` into `next`.
+    fn diff(&self, next: &BoardState) -> Vec<ReplayChange> {
+        let mut changes = Vec::new();
+        let mut ids: Vec<_> = self.pieces.keys().chain(next.pieces.keys()).copied().collect();
+        ids.sort();
+        ids.dedup();
+        for id in ids {
+            match (self.pieces.get(&id), next.pieces.get(&id)) {
+                (None, Some(piece)) => changes.push(ReplayChange::Spawned(*piece)),
+                (Some(_), None) => changes.push(ReplayChange::Removed { id }),
+                (Some(old), Some(new)) => {
+                    if old.coord != new.coord {
+                        changes.push(ReplayChange::Moved { id, to: new.coord });
+                    }
+                    if old.health != new.health {
+                        changes.push(ReplayChange::Health { id, health: new.health });
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        for point in &next.capture_points {
+            let unchanged = self.capture_points.iter().any(|p| {
+                p.coord == point.coord && p.owner == point.owner
+                    && p.capturing == point.capturing && p.progress == point.progress
+            });
+            if !unchanged {
+                changes.push(ReplayChange::Point(*point));
+            }
+        }
+        changes
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ReplayAction {
-    pub entity_id: u32,  // We'll use a stable ID instead of Entity
+    /// The acting unit's or structure's `UnitId`.
+    pub entity_id: u32,
     pub team: Team,
     pub action: Action,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplayMessage {
+    pub team: Team,
+    pub from: u32,
+    /// `None` for a broadcast to the whole team.
+    pub to: Option<u32>,
+    pub data: Vec<f32>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ReplayFile {
     pub initial_seed: u64,
     pub map_config: ReplayMapConfig,
     pub frames: Vec<ReplayFrame>,
     pub final_outcome: Option<GameOutcome>,
+    /// Full result, for replays recorded after match results existed.
+    #[serde(default)]
+    pub match_result: Option<MatchResult>,
+    /// Full board every `replay.keyframe_interval` turns, so a player can
+    /// seek without rebuilding from turn 0.
+    #[serde(default)]
+    pub keyframes: Vec<Keyframe>,
+    /// `replay_format::config_hash` of the settings the match ran under.
+    #[serde(default)]
+    pub config_hash: Option<u64>,
+    /// Everything needed to set the match up again, for replays recorded
+    /// since it was added.
+    #[serde(default)]
+    pub setup: Option<ReplaySetup>,
+}
+
+/// The rules, map and players a replay was recorded with, so it can be
+/// played back without the `game_config.toml` of the time.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplaySetup {
+    /// `CARGO_PKG_VERSION` of the build that recorded it.
+    pub crate_version: String,
+    /// Effective config, with `terrain.seed` pinned to the seed the map came from.
+    pub config: GameConfig,
+    pub map: ReplayMap,
+    pub controllers: Vec<ControllerInfo>,
+}
+
+/// The generated map: every non-plains hex and the resource nodes at full size.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplayMap {
+    pub terrain: Vec<(HexCoord, TerrainType)>,
+    pub resource_nodes: Vec<ResourceNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ControllerInfo {
+    pub team: Team,
+    pub name: String,
+    pub version: String,
+}
+
+impl ReplayMap {
+    pub fn capture(terrain: &TerrainMap, resources: &ResourceField) -> Self {
+        let mut tiles: Vec<_> = terrain.tiles().collect();
+        tiles.sort_by_key(|(c, _)| (c.q, c.r));
+        let mut resource_nodes = resources.nodes.clone();
+        for node in &mut resource_nodes {
+            node.amount = node.max_amount;
+        }
+        Self { terrain: tiles, resource_nodes }
+    }
+
+    pub fn terrain_map(&self, config: &GameConfig) -> TerrainMap {
+        TerrainMap::from_tiles(crate::world::HexGrid::from_config(config), self.terrain.iter().copied())
+    }
+
+    pub fn resource_field(&self) -> ResourceField {
+        ResourceField { nodes: self.resource_nodes.clone() }
+    }
+}
+
+impl ReplayFile {
+    /// Read a replay in either format, telling them apart by the binary magic.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = fs::read(path)?;
+        if replay_format::is_binary(&bytes) {
+            replay_format::decode(&bytes)
+        } else {
+            Ok(serde_json::from_slice(&bytes)?)
+        }
+    }
+
+    /// What about `running` differs from the rules this replay was recorded
+    /// under, one line per config section or version. Empty when they match
+    /// or when the replay is too old to say.
+    pub fn rule_differences(&self, running: &GameConfig) -> Vec<String> {
+        let Some(setup) = &self.setup else {
+            return match self.config_hash {
+                Some(hash) if hash != replay_format::config_hash(running) => {
+                    vec!["config differs (replay predates embedded configs)".to_string()]
+                }
+                _ => Vec::new(),
+            };
+        };
+
+        let mut differences = Vec::new();
+        if setup.crate_version != env!("CARGO_PKG_VERSION") {
+            differences.push(format!("recorded with version {}, running {}",
+                setup.crate_version, env!("CARGO_PKG_VERSION")));
+        }
+        let mut recorded = setup.config.clone();
+        // The seed is pinned on record; a random seed here isn't a rule change
+        recorded.terrain.seed = running.terrain.seed;
+        if let (Ok(serde_json::Value::Object(recorded)), Ok(serde_json::Value::Object(running))) =
+            (serde_json::to_value(&recorded), serde_json::to_value(running))
+        {
+            for (section, value) in recorded.iter().filter(|(s, _)| !replay_format::OUTPUT_SECTIONS.contains(&s.as_str())) {
+                let now = running.get(section).unwrap_or(&serde_json::Value::Null);
+                match (value, now) {
+                    (serde_json::Value::Object(fields), serde_json::Value::Object(now_fields)) => {
+                        for (field, value) in fields {
+                            let now = now_fields.get(field).unwrap_or(&serde_json::Value::Null);
+                            if now != value {
+                                differences.push(format!("{}.{}: recorded {}, running {}", section, field, value, now));
+                            }
+                        }
+                    }
+                    _ if now != value => differences.push(format!("[{}] differs", section)),
+                    _ => {}
+                }
+            }
+        }
+        differences
+    }
+
+    /// Whether the recording build is newer than this one, in which case
+    /// the replay may use rules this build doesn't know.
+    pub fn is_from_newer_version(&self) -> bool {
+        let parse = |v: &str| v.split('.').map(|p| p.parse::<u32>().unwrap_or(0)).collect::<Vec<_>>();
+        self.setup.as_ref()
+            .is_some_and(|s| parse(&s.crate_version) > parse(env!("CARGO_PKG_VERSION")))
+    }
+    
+    /// Write as JSON when `path` ends in `.json`, in the binary format otherwise.
+    pub fn save(&self, path: &Path, compress: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if ReplayFormat::from_path(path) == ReplayFormat::Json {
+            fs::write(path, serde_json::to_string_pretty(self)?)?;
+        } else {
+            fs::write(path, replay_format::encode(self, compress)?)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -34,21 +389,56 @@ pub struct ReplayMapConfig {
     pub units_per_team: usize,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Clone, Debug, PartialEq)]
 pub enum GameOutcome {
-    RedWins,
-    BlueWins,
+    /// The winning team and its allies.
+    Win(Vec<Team>),
     Draw,
 }
 
+/// Also reads the `RedWins`/`BlueWins` outcomes of two-team replays.
+impl<'de> Deserialize<'de> for GameOutcome {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        enum Repr {
+            Win(Vec<Team>),
+            Draw,
+            RedWins,
+            BlueWins,
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Win(teams) => GameOutcome::Win(teams),
+            Repr::Draw => GameOutcome::Draw,
+            Repr::RedWins => GameOutcome::Win(vec![Team(0)]),
+            Repr::BlueWins => GameOutcome::Win(vec![Team(1)]),
+        })
+    }
+}
+
+impl From<&MatchResult> for GameOutcome {
+    fn from(result: &MatchResult) -> Self {
+        if result.is_draw() {
+            GameOutcome::Draw
+        } else {
+            GameOutcome::Win(result.winners.clone())
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct ReplayRecorder {
     initial_seed: u64,
     frames: Vec<ReplayFrame>,
     current_frame: Vec<ReplayAction>,
+    current_messages: Vec<ReplayMessage>,
     recording: bool,
-    entity_id_map: std::collections::HashMap<Entity, u32>,
-    next_id: u32,
+    keyframes: Vec<Keyframe>,
+    setup: Option<ReplaySetup>,
+    /// Board as of the last `record_board`.
+    board: BoardState,
+    /// Turn the last `record_board` belonged to.
+    board_turn: Option<u32>,
 }
 
 impl ReplayRecorder {
@@ -57,104 +447,537 @@ impl ReplayRecorder {
             initial_seed: seed,
             frames: Vec::new(),
             current_frame: Vec::new(),
+            current_messages: Vec::new(),
             recording: true,
-            entity_id_map: std::collections::HashMap::new(),
-            next_id: 0,
+            keyframes: Vec::new(),
+            setup: None,
+            board: BoardState::default(),
+            board_turn: None,
         }
     }
     
-    pub fn get_or_assign_id(&mut self, entity: Entity) -> u32 {
-        *self.entity_id_map.entry(entity).or_insert_with(|| {
-            let id = self.next_id;
-            self.next_id += 1;
-            id
-        })
+    /// Remember how the match was set up. The map seed becomes the replay's seed.
+    pub fn record_setup(&mut self, seed: u64, setup: ReplaySetup) {
+        self.initial_seed = seed;
+        self.setup = Some(setup);
     }
-    
-    pub fn record_action(&mut self, entity: Entity, team: Team, action: Action) {
+
+    pub fn has_setup(&self) -> bool {
+        self.setup.is_some()
+    }
+
+    pub fn record_action(&mut self, actor: UnitId, team: Team, action: Action) {
         if !self.recording {
             return;
         }
         
-        let entity_id = self.get_or_assign_id(entity);
         self.current_frame.push(ReplayAction {
-            entity_id,
+            entity_id: actor.0,
             team,
             action,
         });
     }
     
-    pub fn end_turn(&mut self, turn: u32, rng_seed: u64) {
-        if !self.recording || self.current_frame.is_empty() {
+    /// Record `message` with its sender and recipient as `UnitId`s, looked
+    /// up through `ids`. Messages from units without one are skipped.
+    pub fn record_message(&mut self, team: Team, message: &NeuralMessage, ids: &HashMap<Entity, UnitId>) {
+        if !self.recording {
             return;
         }
         
-        self.frames.push(ReplayFrame {
-            turn,
-            actions: std::mem::take(&mut self.current_frame),
-            rng_seed,
+        let Some(from) = ids.get(&message.from) else {
+            return;
+        };
+        self.current_messages.push(ReplayMessage {
+            team,
+            from: from.0,
+            to: message.to.and_then(|to| ids.get(&to)).map(|id| id.0),
+            data: message.data.clone(),
         });
     }
     
+    pub fn end_turn(&mut self, turn: u32, rng_seed: u64) {
+        if !self.recording || (self.current_frame.is_empty() && self.current_messages.is_empty()) {
+            return;
+        }
+        
+        let actions = std::mem::take(&mut self.current_frame);
+        let messages = std::mem::take(&mut self.current_messages);
+        let frame = self.frame_mut(turn);
+        frame.actions = actions;
+        frame.rng_seed = rng_seed;
+        frame.messages = messages;
+    }
+    
+    /// The frame for `turn`, started if this is the first thing recorded on
+    /// it. Not always the last one: combat for a turn can resolve on the
+    /// frame the next turn runs.
+    fn frame_mut(&mut self, turn: u32) -> &mut ReplayFrame {
+        let index = self.frames.partition_point(|f| f.turn < turn);
+        if self.frames.get(index).is_none_or(|f| f.turn != turn) {
+            self.frames.insert(index, ReplayFrame::empty(turn));
+        }
+        &mut self.frames[index]
+    }
+    
+    /// Diff the board against the last call and record the changes under
+    /// `turn`. Whatever settles after a turn resolves (damage, deaths) lands
+    /// on the turn that was current when it was seen. The board is saved as
+    /// a keyframe once a turn that is a multiple of `keyframe_interval` is over.
+    pub fn record_board(&mut self, turn: u32, board: BoardState, keyframe_interval: u32) {
+        if !self.recording {
+            return;
+        }
+        
+        if let Some(last) = self.board_turn.filter(|last| *last < turn) {
+            if keyframe_interval > 0 && last % keyframe_interval == 0 {
+                self.keyframes.push(self.board.to_keyframe(last));
+            }
+        }
+        self.board_turn = Some(turn);
+        
+        let changes = self.board.diff(&board);
+        if changes.is_empty() {
+            return;
+        }
+        self.frame_mut(turn).changes.extend(changes);
+        self.board = board;
+    }
+    
+    pub fn record_event(&mut self, event: &MatchEvent) {
+        if !self.recording {
+            return;
+        }
+        
+        self.frame_mut(event.turn).events.push(event.clone());
+    }
+    
+    /// Save in the format `path`'s extension asks for; see `ReplayFile::save`.
     pub fn save_replay(
         &self,
         path: &Path,
-        map_config: ReplayMapConfig,
-        outcome: Option<GameOutcome>,
+        config: &GameConfig,
+        result: Option<&MatchResult>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let replay = ReplayFile {
+        self.to_replay(config, result).save(path, config.replay.compress)
+    }
+
+    /// Everything recorded so far, as the file `save_replay` writes.
+    fn to_replay(&self, config: &GameConfig, result: Option<&MatchResult>) -> ReplayFile {
+        ReplayFile {
             initial_seed: self.initial_seed,
-            map_config,
+            map_config: ReplayMapConfig {
+                width: config.game.map_width,
+                height: config.game.map_height,
+                units_per_team: config.game.units_per_team,
+            },
             frames: self.frames.clone(),
-            final_outcome: outcome,
-        };
-        
-        let json = serde_json::to_string_pretty(&replay)?;
-        fs::write(path, json)?;
-        Ok(())
+            final_outcome: result.map(GameOutcome::from),
+            match_result: result.cloned(),
+            keyframes: self.keyframes.clone(),
+            config_hash: Some(replay_format::config_hash(config)),
+            setup: self.setup.clone(),
+        }
     }
 }
 
+/// Plays a recorded match back by rebuilding the board from its frames.
 #[derive(Resource)]
 pub struct ReplayPlayer {
     replay: ReplayFile,
-    current_frame_index: usize,
-    playing: bool,
+    board: BoardState,
+    /// Turn `board` is at, `None` before anything was applied.
+    turn: Option<u32>,
 }
 
 impl ReplayPlayer {
     pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let json = fs::read_to_string(path)?;
-        let replay: ReplayFile = serde_json::from_str(&json)?;
-        
-        Ok(Self {
+        Ok(Self::new(ReplayFile::load(path)?))
+    }
+
+    pub fn new(replay: ReplayFile) -> Self {
+        let mut player = Self {
             replay,
-            current_frame_index: 0,
-            playing: false,
-        })
+            board: BoardState::default(),
+            turn: None,
+        };
+        player.seek(0);
+        player
     }
     
-    pub fn get_next_frame(&mut self) -> Option<&ReplayFrame> {
-        if self.current_frame_index < self.replay.frames.len() {
-            let frame = &self.replay.frames[self.current_frame_index];
-            self.current_frame_index += 1;
-            Some(frame)
-        } else {
-            None
+    pub fn replay(&self) -> &ReplayFile {
+        &self.replay
+    }
+    
+    pub fn board(&self) -> &BoardState {
+        &self.board
+    }
+    
+    pub fn turn(&self) -> u32 {
+        self.turn.unwrap_or(0)
+    }
+    
+    pub fn last_turn(&self) -> u32 {
+        self.replay.frames.last().map_or(0, |f| f.turn)
+    }
+    
+    pub fn frame(&self, turn: u32) -> Option<&ReplayFrame> {
+        let index = self.replay.frames.partition_point(|f| f.turn < turn);
+        self.replay.frames.get(index).filter(|f| f.turn == turn)
+    }
+    
+    /// Move the board to the end of `turn`. Going forward applies frames
+    /// from where the board is; going back restarts from the closest
+    /// keyframe at or before `turn`. What runs forward from there are the
+    /// recorded board changes, not the game systems: a replay plays back the
+    /// same even when the simulation it came from isn't deterministic.
+    pub fn seek(&mut self, turn: u32) {
+        let turn = turn.min(self.last_turn());
+        if self.turn.is_none_or(|current| turn < current) {
+            match self.replay.keyframes.iter().rev().find(|k| k.turn <= turn) {
+                Some(keyframe) => {
+                    self.board = BoardState::from_keyframe(keyframe);
+                    self.turn = Some(keyframe.turn);
+                }
+                None => {
+                    self.board = BoardState::default();
+                    self.turn = None;
+                }
+            }
+        }
+        
+        let start = self.replay.frames.partition_point(|f| Some(f.turn) <= self.turn);
+        for frame in self.replay.frames[start..].iter().take_while(|f| f.turn <= turn) {
+            for change in &frame.changes {
+                self.board.apply(change);
+            }
+        }
+        self.turn = Some(turn);
+    }
+    
+    /// Everything inconsistent about the replay: frames out of order,
+    /// keyframes that don't match replaying the deltas from the start, or
+    /// an outcome that disagrees with the recorded result.
+    pub fn problems(&self) -> Vec<String> {
+        let replay = &self.replay;
+        let mut problems = Vec::new();
+        if let Some(pair) = replay.frames.windows(2).find(|w| w[0].turn >= w[1].turn) {
+            problems.push(format!("frame for turn {} follows turn {}", pair[1].turn, pair[0].turn));
+        }
+        
+        let mut board = BoardState::default();
+        let mut frames = replay.frames.iter().peekable();
+        for keyframe in &replay.keyframes {
+            while let Some(frame) = frames.next_if(|f| f.turn <= keyframe.turn) {
+                for change in &frame.changes {
+                    board.apply(change);
+                }
+            }
+            let expected = BoardState::from_keyframe(keyframe);
+            if board.pieces != expected.pieces || board.capture_points != expected.capture_points {
+                problems.push(format!("keyframe at turn {} doesn't match the deltas before it", keyframe.turn));
+            }
         }
+        
+        if let (Some(outcome), Some(result)) = (&replay.final_outcome, &replay.match_result) {
+            if *outcome != GameOutcome::from(result) {
+                problems.push("final outcome disagrees with the match result".to_string());
+            }
+        }
+        problems
     }
     
-    pub fn reset(&mut self) {
-        self.current_frame_index = 0;
+    /// Attacks and deaths per turn, for a timeline.
+    pub fn combat_turns(&self) -> impl Iterator<Item = (u32, usize, usize)> + '_ {
+        self.replay.frames.iter()
+            .map(|f| {
+                let count = |matches: fn(&MatchEventKind) -> bool| f.events.iter().filter(|e| matches(&e.kind)).count();
+                let attacks = count(|kind| matches!(kind, MatchEventKind::Attack { .. }));
+                let kills = count(|kind| matches!(kind, MatchEventKind::Died { .. }));
+                (f.turn, attacks, kills)
+            })
+            .filter(|(_, attacks, kills)| attacks + kills > 0)
     }
 }
 
+/// Check the replay at `path` and print what was found, returning whether
+/// it is sound. Rule differences from `running` are reported but don't fail it.
+pub fn verify(path: &Path, running: &GameConfig) -> bool {
+    let player = match ReplayPlayer::load(path) {
+        Ok(player) => player,
+        Err(e) => {
+            println!("{}: unreadable: {}", path.display(), e);
+            return false;
+        }
+    };
+    let replay = player.replay();
+    println!("{}: {} frames, {} keyframes, last turn {}",
+        path.display(), replay.frames.len(), replay.keyframes.len(), player.last_turn());
+    match &replay.setup {
+        Some(setup) => {
+            let controllers: Vec<String> = setup.controllers.iter()
+                .map(|c| format!("{}@{}", c.name, c.version))
+                .collect();
+            println!("  recorded by version {}, seed {}, controllers {}",
+                setup.crate_version, replay.initial_seed, controllers.join(" vs "));
+        }
+        None => println!("  no embedded setup (recorded before replays carried their config)"),
+    }
+    for difference in replay.rule_differences(running) {
+        println!("  warning: {}", difference);
+    }
+    
+    let problems = player.problems();
+    for problem in &problems {
+        println!("  error: {}", problem);
+    }
+    if problems.is_empty() {
+        println!("  OK");
+    }
+    problems.is_empty()
+}
+
 pub struct ReplayPlugin;
 
 impl Plugin for ReplayPlugin {
     fn build(&self, app: &mut App) {
         // Initialize with a random seed for now
         let seed = chrono::Local::now().timestamp() as u64;
-        app.insert_resource(ReplayRecorder::new(seed));
+        app
+            .insert_resource(ReplayRecorder::new(seed))
+            .add_systems(PostUpdate, (
+                record_setup,
+                record_match_events,
+                record_board.run_if(board_touched),
+            ).chain());
+    }
+}
+
+/// Capture the match setup once per recorder, i.e. once per match.
+fn record_setup(
+    (config, seed): (Res<GameConfig>, Option<Res<MapSeed>>),
+    (terrain, resources): (Option<Res<TerrainMap>>, Option<Res<ResourceField>>),
+    (roster, ai): (Res<TeamRoster>, Option<Res<GameAI>>),
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if recorder.has_setup() {
+        return;
+    }
+    let (Some(seed), Some(terrain), Some(resources), Some(ai)) = (seed, terrain, resources, ai) else {
+        return;
+    };
+
+    let mut config = config.clone();
+    config.terrain.seed = Some(seed.0);
+    let controllers = roster.iter()
+        .map(|info| ControllerInfo {
+            team: info.team,
+            name: info.controller.clone(),
+            version: ai.0.get(&info.team).map_or("unknown", |c| c.version()).to_string(),
+        })
+        .collect();
+    let setup = ReplaySetup {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        config,
+        map: ReplayMap::capture(&terrain, &resources),
+        controllers,
+    };
+    recorder.record_setup(seed.0, setup);
+}
+
+fn record_match_events(
+    mut match_events: EventReader<MatchEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    for event in match_events.read() {
+        recorder.record_event(event);
+    }
+}
+
+/// Units and structures that moved, took damage or died since the last frame.
+type BoardTouched = Or<(Changed<Unit>, Changed<HexPosition>, Changed<Structure>, Added<Dead>)>;
+
+/// Whether a turn ran or anything on the board changed since the last
+/// frame. Between turns nothing does, and the board isn't diffed.
+fn board_touched(
+    (turn_state, capture_points): (Res<TurnState>, Res<CapturePoints>),
+    touched: Query<(), BoardTouched>,
+) -> bool {
+    turn_state.is_changed() || capture_points.is_changed() || !touched.is_empty()
+}
+
+fn record_board(
+    units: Query<(&UnitId, &Unit, &HexPosition), Without<Dead>>,
+    structures: Query<(&UnitId, &Structure, &HexPosition), Without<Dead>>,
+    capture_points: Res<CapturePoints>,
+    (turn_state, config): (Res<TurnState>, Res<GameConfig>),
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let unit_pieces: Vec<_> = units.iter()
+        .map(|(id, u, p)| (id.0, u.team, PieceKind::Unit(u.unit_type), p.coord, u.health, u.max_health))
+        .collect();
+    let structure_pieces: Vec<_> = structures.iter()
+        .map(|(id, s, p)| (id.0, s.team, PieceKind::Structure(s.kind), p.coord, s.health, s.max_health))
+        .collect();
+    
+    let pieces = unit_pieces.into_iter()
+        .chain(structure_pieces)
+        .map(|(id, team, kind, coord, health, max_health)| (id, ReplayPiece { id, team, kind, coord, health, max_health }))
+        .collect();
+    let board = BoardState {
+        pieces,
+        capture_points: capture_points.points.clone(),
+    };
+    recorder.record_board(turn_state.turn, board, config.replay.keyframe_interval);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn frames_read_the_attacks_and_kills_of_older_replays() {
+        let frame: ReplayFrame = serde_json::from_value(json!({
+            "turn": 12,
+            "actions": [],
+            "rng_seed": 0,
+            "events": [
+                { "Attack": { "attacker": 3, "defender": 5, "damage": 35.0 } },
+                { "Kill": { "id": 5 } },
+            ],
+        })).unwrap();
+
+        assert_eq!(frame.events.len(), 1);
+        assert_eq!(frame.events[0].turn, 12);
+        assert!(matches!(frame.events[0].kind, MatchEventKind::Attack { attacker: Some(3), target: Subject::Unit(5) }));
+    }
+
+    #[test]
+    fn frames_keep_their_match_events_as_json() {
+        let mut frame = ReplayFrame::empty(4);
+        frame.events.push(MatchEvent::new(4, MatchEventKind::Died {
+            target: Subject::Structure(HexCoord::new(2, -1)),
+            team: Team(1),
+            killer: Some(Team(0)),
+        }));
+
+        let value = serde_json::to_value(&frame).unwrap();
+        let read: ReplayFrame = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), value);
+        assert_eq!(value["events"][0]["event"], "died");
+    }
+
+    fn piece(id: u32, q: i32, health: f32) -> ReplayPiece {
+        ReplayPiece {
+            id,
+            team: Team((id % 2) as u8),
+            kind: PieceKind::Unit(UnitType::Fighter),
+            coord: HexCoord::new(q, 0),
+            health,
+            max_health: 100.0,
+        }
+    }
+
+    fn board(pieces: &[ReplayPiece], points: &[CapturePoint]) -> BoardState {
+        BoardState {
+            pieces: pieces.iter().map(|p| (p.id, *p)).collect(),
+            capture_points: points.to_vec(),
+        }
+    }
+
+    fn assert_same_board(left: &BoardState, right: &BoardState) {
+        assert_eq!(left.pieces, right.pieces);
+        assert_eq!(left.capture_points, right.capture_points);
+    }
+
+    #[test]
+    fn applying_a_diff_gives_the_next_board() {
+        let mut point = CapturePoint::new(HexCoord::new(3, 3));
+        let before = board(&[piece(0, 0, 100.0), piece(1, 1, 100.0), piece(2, 2, 100.0)], &[point]);
+        point.capturing = Some(Team(1));
+        point.progress = 1;
+        let after = board(&[piece(0, 1, 100.0), piece(1, 1, 40.0), piece(3, 5, 100.0)], &[point]);
+
+        let changes = before.diff(&after);
+        assert_eq!(changes.len(), 5);
+        let mut board = before.clone();
+        for change in &changes {
+            board.apply(change);
+        }
+        assert_same_board(&board, &after);
+        assert!(after.diff(&after).is_empty());
+    }
+
+    /// Records a unit walking and taking damage over `turns` turns, with
+    /// one fighter dying and another joining, and returns the board after each.
+    fn record(recorder: &mut ReplayRecorder, turns: u32, keyframe_interval: u32) -> Vec<BoardState> {
+        let mut boards = Vec::new();
+        for turn in 0..=turns {
+            let t = turn as i32;
+            let mut pieces = vec![piece(0, t, 100.0 - 10.0 * turn as f32)];
+            if turn < 4 {
+                pieces.push(piece(1, -t, 100.0));
+            }
+            if turn >= 3 {
+                pieces.push(piece(2, 10, 50.0));
+            }
+            let board = board(&pieces, &[]);
+            recorder.record_board(turn, board.clone(), keyframe_interval);
+            boards.push(board);
+        }
+        boards
+    }
+
+    #[test]
+    fn seeking_back_and_forth_rebuilds_each_recorded_board() {
+        let mut recorder = ReplayRecorder::new(0);
+        let boards = record(&mut recorder, 7, 2);
+        let mut player = ReplayPlayer::new(recorder.to_replay(&GameConfig::default(), None));
+        assert_eq!(player.replay().keyframes.iter().map(|k| k.turn).collect::<Vec<_>>(), vec![0, 2, 4, 6]);
+        assert!(player.problems().is_empty());
+
+        for turn in (0..=7).rev().chain(0..=7) {
+            player.seek(turn);
+            assert_eq!(player.turn(), turn);
+            assert_same_board(player.board(), &boards[turn as usize]);
+        }
+
+        // Jumping straight back to a turn between keyframes
+        player.seek(7);
+        player.seek(3);
+        assert_same_board(player.board(), &boards[3]);
+    }
+
+    #[test]
+    fn keyframes_match_the_deltas_before_them() {
+        let mut recorder = ReplayRecorder::new(0);
+        let boards = record(&mut recorder, 9, 3);
+        let replay = recorder.to_replay(&GameConfig::default(), None);
+        for keyframe in &replay.keyframes {
+            assert_same_board(&BoardState::from_keyframe(keyframe), &boards[keyframe.turn as usize]);
+        }
+
+        // Without keyframes the deltas alone still get there
+        let mut recorder = ReplayRecorder::new(0);
+        let boards = record(&mut recorder, 9, 0);
+        let mut player = ReplayPlayer::new(recorder.to_replay(&GameConfig::default(), None));
+        assert!(player.replay().keyframes.is_empty());
+        player.seek(9);
+        player.seek(5);
+        assert_same_board(player.board(), &boards[5]);
+    }
+
+    #[test]
+    fn outcomes_read_the_two_team_names_of_older_replays() {
+        let read = |json: &str| serde_json::from_str::<GameOutcome>(json).unwrap();
+        assert_eq!(read("\"RedWins\""), GameOutcome::Win(vec![Team(0)]));
+        assert_eq!(read("\"BlueWins\""), GameOutcome::Win(vec![Team(1)]));
+        assert_eq!(read("\"Draw\""), GameOutcome::Draw);
+        assert_eq!(read(r#"{"Win":[0,2]}"#), GameOutcome::Win(vec![Team(0), Team(2)]));
+
+        let outcome = GameOutcome::Win(vec![Team(3)]);
+        assert_eq!(read(&serde_json::to_string(&outcome).unwrap()), outcome);
+        assert!(serde_json::from_str::<GameOutcome>("\"GreenWins\"").is_err());
     }
 }
@@ -0,0 +1,911 @@
+﻿// Compact binary encoding for replay files
+//
+// Layout, all integers little-endian:
+//
+//   magic    b"ABRP"
+//   version  u16
+//   flags    u8     bit 0: body is deflate-compressed
+//   config   u64    hash of the GameConfig the match was played with (0 if unknown)
+//   seed     u64
+//   body
+//
+// The body holds a JSON metadata blob (map size, outcome, full result and,
+// from version 2, the embedded setup: config, map and controllers), then
+// the keyframes and the frames. Integers in the body are LEB128 varints,
+// signed ones zigzagged, and each frame stores its turn as the gap from the
+// one before, so a frame is little more than its board changes and, from
+// version 3, its match events.
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use crate::config::GameConfig;
+use crate::engine::replay::{
+    legacy_attack, GameOutcome, Keyframe, PieceKind, ReplayAction, ReplayChange, ReplayFile,
+    ReplayFrame, ReplayMapConfig, ReplayMessage, ReplayPiece, ReplaySetup,
+};
+use crate::game::{MatchEvent, MatchResult, WinReason};
+use crate::game::events::{MatchEventKind, Subject};
+use crate::game::objectives::CapturePoint;
+use crate::units::{StructureKind, Team, UnitType};
+use crate::world::HexCoord;
+use crate::world::actions::Action;
+use crate::world::resource::ResourceKind;
+
+pub const MAGIC: &[u8; 4] = b"ABRP";
+/// Bump when the body layout changes, and keep a reader for every older one.
+pub const FORMAT_VERSION: u16 = 3;
+const FLAG_DEFLATE: u8 = 1;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Whether `bytes` start like a binary replay rather than JSON.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Config sections that only say where output goes, not how the game plays.
+pub const OUTPUT_SECTIONS: [&str; 4] = ["performance", "replay", "snapshots", "match_log"];
+
+/// Stable FNV-1a hash of the config's rules as JSON, to tell whether a
+/// replay was recorded under the same settings. Covers what
+/// `ReplayFile::rule_differences` compares: everything but the output
+/// sections and the map seed.
+pub fn config_hash(config: &GameConfig) -> u64 {
+    let mut rules = config.clone();
+    rules.terrain.seed = None;
+    let mut value = serde_json::to_value(&rules).unwrap_or_default();
+    if let Some(sections) = value.as_object_mut() {
+        sections.retain(|section, _| !OUTPUT_SECTIONS.contains(&section.as_str()));
+    }
+    let json = serde_json::to_vec(&value).unwrap_or_default();
+    json.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Rewrite a replay in the format `output`'s extension names, printing the
+/// size change.
+pub fn convert(input: &Path, output: &Path) {
+    let result = ReplayFile::load(input).and_then(|replay| replay.save(output, true));
+    match result {
+        Ok(()) => {
+            let size = |p: &Path| std::fs::metadata(p).map_or(0, |m| m.len());
+            println!("Converted {} ({} bytes) -> {} ({} bytes)",
+                input.display(), size(input), output.display(), size(output));
+        }
+        Err(e) => eprintln!("Failed to convert {}: {}", input.display(), e),
+    }
+}
+
+pub fn encode(replay: &ReplayFile, compress: bool) -> Result<Vec<u8>> {
+    let mut body = Writer::default();
+    let metadata = serde_json::to_vec(&(
+        &replay.map_config,
+        &replay.final_outcome,
+        &replay.match_result,
+        &replay.setup,
+    ))?;
+    body.bytes(&metadata);
+    body.list(&replay.keyframes, Writer::keyframe);
+    body.varint(replay.frames.len() as u64);
+    let mut previous = 0;
+    for frame in &replay.frames {
+        let gap = frame.turn.checked_sub(previous)
+            .ok_or_else(|| format!("frame for turn {} comes after turn {}", frame.turn, previous))?;
+        body.varint(gap as u64);
+        previous = frame.turn;
+        body.frame(frame);
+    }
+
+    let mut out = Vec::with_capacity(body.0.len() / 2);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.push(if compress { FLAG_DEFLATE } else { 0 });
+    out.extend_from_slice(&replay.config_hash.unwrap_or(0).to_le_bytes());
+    out.extend_from_slice(&replay.initial_seed.to_le_bytes());
+    if compress {
+        let mut encoder = DeflateEncoder::new(out, Compression::default());
+        encoder.write_all(&body.0)?;
+        out = encoder.finish()?;
+    } else {
+        out.extend_from_slice(&body.0);
+    }
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<ReplayFile> {
+    let mut header = Reader(bytes);
+    if header.take(4)? != MAGIC {
+        return Err("not a binary replay".into());
+    }
+    let version = u16::from_le_bytes(header.array()?);
+    let flags = header.u8()?;
+    let config_hash = u64::from_le_bytes(header.array()?);
+    let initial_seed = u64::from_le_bytes(header.array()?);
+
+    let inflated;
+    let body = if flags & FLAG_DEFLATE != 0 {
+        let mut buffer = Vec::new();
+        DeflateDecoder::new(header.0).read_to_end(&mut buffer)?;
+        inflated = buffer;
+        inflated.as_slice()
+    } else {
+        header.0
+    };
+
+    let mut replay = match version {
+        1 => decode_v1(&mut Reader(body))?,
+        2 => decode_v2(&mut Reader(body))?,
+        3 => decode_v3(&mut Reader(body))?,
+        _ => return Err(format!("replay format version {} is newer than this build reads ({})", version, FORMAT_VERSION).into()),
+    };
+    replay.initial_seed = initial_seed;
+    replay.config_hash = Some(config_hash).filter(|h| *h != 0);
+    Ok(replay)
+}
+
+type MetadataV1 = (ReplayMapConfig, Option<GameOutcome>, Option<MatchResult>);
+type MetadataV2 = (ReplayMapConfig, Option<GameOutcome>, Option<MatchResult>, Option<ReplaySetup>);
+
+fn decode_v1(body: &mut Reader) -> Result<ReplayFile> {
+    let (map_config, final_outcome, match_result): MetadataV1 = serde_json::from_slice(body.bytes()?)?;
+    decode_frames(body, (map_config, final_outcome, match_result, None), |r, turn| r.legacy_events(turn))
+}
+
+/// Version 2 only adds the setup to the metadata.
+fn decode_v2(body: &mut Reader) -> Result<ReplayFile> {
+    let metadata: MetadataV2 = serde_json::from_slice(body.bytes()?)?;
+    decode_frames(body, metadata, |r, turn| r.legacy_events(turn))
+}
+
+/// Version 3 stores each frame's match events in place of its attacks and kills.
+fn decode_v3(body: &mut Reader) -> Result<ReplayFile> {
+    let metadata: MetadataV2 = serde_json::from_slice(body.bytes()?)?;
+    decode_frames(body, metadata, |r, turn| r.events(turn))
+}
+
+/// How a format version stores a frame's events.
+type ReadEvents = fn(&mut Reader, u32) -> Result<Vec<MatchEvent>>;
+
+fn decode_frames(body: &mut Reader, metadata: MetadataV2, events: ReadEvents) -> Result<ReplayFile> {
+    let (map_config, final_outcome, match_result, setup) = metadata;
+    let keyframes = body.list(Reader::keyframe)?;
+    let count = body.varint()? as usize;
+    let mut frames = Vec::with_capacity(count.min(1 << 16));
+    let mut turn = 0u32;
+    for _ in 0..count {
+        turn = turn.checked_add(body.u32()?).ok_or("frame turn out of range")?;
+        frames.push(body.frame(turn, events)?);
+    }
+
+    Ok(ReplayFile {
+        initial_seed: 0,
+        map_config,
+        frames,
+        final_outcome,
+        match_result,
+        keyframes,
+        config_hash: None,
+        setup,
+    })
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn signed(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn list<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        self.varint(items.len() as u64);
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    fn team(&mut self, team: Team) {
+        self.u8(team.0);
+    }
+
+    fn option_team(&mut self, team: Option<Team>) {
+        // 0 for none, otherwise the index shifted up by one
+        self.u8(team.map_or(0, |t| t.0 + 1));
+    }
+
+    fn coord(&mut self, coord: HexCoord) {
+        self.signed(coord.q as i64);
+        self.signed(coord.r as i64);
+    }
+
+    fn unit_type(&mut self, unit_type: UnitType) {
+        self.u8(match unit_type {
+            UnitType::Worker => 0,
+            UnitType::Fighter => 1,
+            UnitType::Scout => 2,
+        });
+    }
+
+    fn structure_kind(&mut self, kind: StructureKind) {
+        self.u8(match kind {
+            StructureKind::Base => 0,
+            StructureKind::Barracks => 1,
+        });
+    }
+
+    fn resource_kind(&mut self, kind: ResourceKind) {
+        self.u8(match kind {
+            ResourceKind::Energy => 0,
+            ResourceKind::Metal => 1,
+            ResourceKind::Knowledge => 2,
+        });
+    }
+
+    fn win_reason(&mut self, reason: WinReason) {
+        self.u8(match reason {
+            WinReason::Elimination => 0,
+            WinReason::Timeout => 1,
+            WinReason::Objective => 2,
+            WinReason::Forfeit => 3,
+            WinReason::Points => 4,
+        });
+    }
+
+    fn subject(&mut self, subject: Subject) {
+        match subject {
+            Subject::Unit(id) => {
+                self.u8(0);
+                self.varint(id as u64);
+            }
+            Subject::Structure(coord) => {
+                self.u8(1);
+                self.coord(coord);
+            }
+        }
+    }
+
+    fn piece(&mut self, piece: &ReplayPiece) {
+        self.varint(piece.id as u64);
+        self.team(piece.team);
+        match piece.kind {
+            PieceKind::Unit(unit_type) => {
+                self.u8(0);
+                self.unit_type(unit_type);
+            }
+            PieceKind::Structure(kind) => {
+                self.u8(1);
+                self.structure_kind(kind);
+            }
+        }
+        self.coord(piece.coord);
+        self.f32(piece.health);
+        self.f32(piece.max_health);
+    }
+
+    fn capture_point(&mut self, point: &CapturePoint) {
+        self.coord(point.coord);
+        self.option_team(point.owner);
+        self.option_team(point.capturing);
+        self.varint(point.progress as u64);
+    }
+
+    fn keyframe(&mut self, keyframe: &Keyframe) {
+        self.varint(keyframe.turn as u64);
+        self.list(&keyframe.pieces, Self::piece);
+        self.list(&keyframe.capture_points, Self::capture_point);
+    }
+
+    fn action(&mut self, action: &Action) {
+        match action {
+            Action::Stay => self.u8(0),
+            Action::Move(dq, dr) => {
+                self.u8(1);
+                self.signed(*dq as i64);
+                self.signed(*dr as i64);
+            }
+            Action::Gather => self.u8(2),
+            Action::Build(kind, at) => {
+                self.u8(3);
+                self.structure_kind(*kind);
+                self.coord(*at);
+            }
+            Action::Produce(unit_type) => {
+                self.u8(4);
+                self.unit_type(*unit_type);
+            }
+        }
+    }
+
+    /// The event's turn is the frame's, so it isn't written again.
+    fn event(&mut self, event: &MatchEvent) {
+        match &event.kind {
+            MatchEventKind::Spawned { unit, team, unit_type, at } => {
+                self.u8(0);
+                self.varint(*unit as u64);
+                self.team(*team);
+                self.unit_type(*unit_type);
+                self.coord(*at);
+            }
+            MatchEventKind::Moved { unit, from, to } => {
+                self.u8(1);
+                self.varint(*unit as u64);
+                self.coord(*from);
+                self.coord(*to);
+            }
+            MatchEventKind::Blocked { unit, at, toward } => {
+                self.u8(2);
+                self.varint(*unit as u64);
+                self.coord(*at);
+                self.coord(*toward);
+            }
+            MatchEventKind::Gathered { unit, kind, amount } => {
+                self.u8(3);
+                self.varint(*unit as u64);
+                self.resource_kind(*kind);
+                self.signed(*amount as i64);
+            }
+            MatchEventKind::BuildStarted { unit, team, kind, at } => {
+                self.u8(4);
+                self.varint(*unit as u64);
+                self.team(*team);
+                self.structure_kind(*kind);
+                self.coord(*at);
+            }
+            MatchEventKind::ProductionStarted { team, at, unit_type } => {
+                self.u8(5);
+                self.team(*team);
+                self.coord(*at);
+                self.unit_type(*unit_type);
+            }
+            MatchEventKind::Captured { team, at } => {
+                self.u8(6);
+                self.team(*team);
+                self.coord(*at);
+            }
+            MatchEventKind::Attack { attacker, target } => {
+                self.u8(7);
+                // Attacker shifted up by one so 0 can mean unknown
+                self.varint(attacker.map_or(0, |id| id as u64 + 1));
+                self.subject(*target);
+            }
+            MatchEventKind::Damaged { target, amount, health } => {
+                self.u8(8);
+                self.subject(*target);
+                self.f32(*amount);
+                self.f32(*health);
+            }
+            MatchEventKind::Died { target, team, killer } => {
+                self.u8(9);
+                self.subject(*target);
+                self.team(*team);
+                self.option_team(*killer);
+            }
+            MatchEventKind::MatchEnded { winners, reason } => {
+                self.u8(10);
+                self.list(winners, |w, team| w.team(*team));
+                self.win_reason(*reason);
+            }
+        }
+    }
+
+    fn frame(&mut self, frame: &ReplayFrame) {
+        self.varint(frame.rng_seed);
+        self.list(&frame.actions, |w, a: &ReplayAction| {
+            w.varint(a.entity_id as u64);
+            w.team(a.team);
+            w.action(&a.action);
+        });
+        self.list(&frame.messages, |w, m: &ReplayMessage| {
+            w.team(m.team);
+            w.varint(m.from as u64);
+            // Recipient shifted up by one so 0 can mean broadcast
+            w.varint(m.to.map_or(0, |to| to as u64 + 1));
+            w.list(&m.data, |w, v| w.f32(*v));
+        });
+        self.list(&frame.changes, |w, change| match change {
+            ReplayChange::Spawned(piece) => {
+                w.u8(0);
+                w.piece(piece);
+            }
+            ReplayChange::Moved { id, to } => {
+                w.u8(1);
+                w.varint(*id as u64);
+                w.coord(*to);
+            }
+            ReplayChange::Health { id, health } => {
+                w.u8(2);
+                w.varint(*id as u64);
+                w.f32(*health);
+            }
+            ReplayChange::Removed { id } => {
+                w.u8(3);
+                w.varint(*id as u64);
+            }
+            ReplayChange::Point(point) => {
+                w.u8(4);
+                w.capture_point(point);
+            }
+        });
+        self.list(&frame.events, Self::event);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err("replay is truncated".into());
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint too long".into())
+    }
+
+    fn signed(&mut self) -> Result<i64> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::try_from(self.varint()?)?)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::try_from(self.signed()?)?)
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let len = self.varint()? as usize;
+        // Every item takes at least a byte, which bounds a corrupt length
+        let mut items = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            items.push(read(self)?);
+        }
+        Ok(items)
+    }
+
+    fn team(&mut self) -> Result<Team> {
+        Ok(Team(self.u8()?))
+    }
+
+    fn option_team(&mut self) -> Result<Option<Team>> {
+        Ok(self.u8()?.checked_sub(1).map(Team))
+    }
+
+    fn coord(&mut self) -> Result<HexCoord> {
+        Ok(HexCoord::new(self.i32()?, self.i32()?))
+    }
+
+    fn unit_type(&mut self) -> Result<UnitType> {
+        match self.u8()? {
+            0 => Ok(UnitType::Worker),
+            1 => Ok(UnitType::Fighter),
+            2 => Ok(UnitType::Scout),
+            tag => Err(format!("unknown unit type {}", tag).into()),
+        }
+    }
+
+    fn structure_kind(&mut self) -> Result<StructureKind> {
+        match self.u8()? {
+            0 => Ok(StructureKind::Base),
+            1 => Ok(StructureKind::Barracks),
+            tag => Err(format!("unknown structure kind {}", tag).into()),
+        }
+    }
+
+    fn resource_kind(&mut self) -> Result<ResourceKind> {
+        match self.u8()? {
+            0 => Ok(ResourceKind::Energy),
+            1 => Ok(ResourceKind::Metal),
+            2 => Ok(ResourceKind::Knowledge),
+            tag => Err(format!("unknown resource kind {}", tag).into()),
+        }
+    }
+
+    fn win_reason(&mut self) -> Result<WinReason> {
+        match self.u8()? {
+            0 => Ok(WinReason::Elimination),
+            1 => Ok(WinReason::Timeout),
+            2 => Ok(WinReason::Objective),
+            3 => Ok(WinReason::Forfeit),
+            4 => Ok(WinReason::Points),
+            tag => Err(format!("unknown win reason {}", tag).into()),
+        }
+    }
+
+    fn subject(&mut self) -> Result<Subject> {
+        match self.u8()? {
+            0 => Ok(Subject::Unit(self.u32()?)),
+            1 => Ok(Subject::Structure(self.coord()?)),
+            tag => Err(format!("unknown event subject {}", tag).into()),
+        }
+    }
+
+    fn piece(&mut self) -> Result<ReplayPiece> {
+        let id = self.u32()?;
+        let team = self.team()?;
+        let kind = match self.u8()? {
+            0 => PieceKind::Unit(self.unit_type()?),
+            1 => PieceKind::Structure(self.structure_kind()?),
+            tag => return Err(format!("unknown piece kind {}", tag).into()),
+        };
+        Ok(ReplayPiece {
+            id,
+            team,
+            kind,
+            coord: self.coord()?,
+            health: self.f32()?,
+            max_health: self.f32()?,
+        })
+    }
+
+    fn capture_point(&mut self) -> Result<CapturePoint> {
+        Ok(CapturePoint {
+            coord: self.coord()?,
+            owner: self.option_team()?,
+            capturing: self.option_team()?,
+            progress: self.u32()?,
+        })
+    }
+
+    fn keyframe(&mut self) -> Result<Keyframe> {
+        Ok(Keyframe {
+            turn: self.u32()?,
+            pieces: self.list(Self::piece)?,
+            capture_points: self.list(Self::capture_point)?,
+        })
+    }
+
+    fn action(&mut self) -> Result<Action> {
+        match self.u8()? {
+            0 => Ok(Action::Stay),
+            1 => Ok(Action::Move(self.i32()?, self.i32()?)),
+            2 => Ok(Action::Gather),
+            3 => Ok(Action::Build(self.structure_kind()?, self.coord()?)),
+            4 => Ok(Action::Produce(self.unit_type()?)),
+            tag => Err(format!("unknown action {}", tag).into()),
+        }
+    }
+
+    fn event(&mut self, turn: u32) -> Result<MatchEvent> {
+        let kind = match self.u8()? {
+            0 => MatchEventKind::Spawned { unit: self.u32()?, team: self.team()?, unit_type: self.unit_type()?, at: self.coord()? },
+            1 => MatchEventKind::Moved { unit: self.u32()?, from: self.coord()?, to: self.coord()? },
+            2 => MatchEventKind::Blocked { unit: self.u32()?, at: self.coord()?, toward: self.coord()? },
+            3 => MatchEventKind::Gathered { unit: self.u32()?, kind: self.resource_kind()?, amount: self.i32()? },
+            4 => MatchEventKind::BuildStarted { unit: self.u32()?, team: self.team()?, kind: self.structure_kind()?, at: self.coord()? },
+            5 => MatchEventKind::ProductionStarted { team: self.team()?, at: self.coord()?, unit_type: self.unit_type()? },
+            6 => MatchEventKind::Captured { team: self.team()?, at: self.coord()? },
+            7 => MatchEventKind::Attack { attacker: self.u32()?.checked_sub(1), target: self.subject()? },
+            8 => MatchEventKind::Damaged { target: self.subject()?, amount: self.f32()?, health: self.f32()? },
+            9 => MatchEventKind::Died { target: self.subject()?, team: self.team()?, killer: self.option_team()? },
+            10 => MatchEventKind::MatchEnded { winners: self.list(Self::team)?, reason: self.win_reason()? },
+            tag => return Err(format!("unknown match event {}", tag).into()),
+        };
+        Ok(MatchEvent::new(turn, kind))
+    }
+
+    fn events(&mut self, turn: u32) -> Result<Vec<MatchEvent>> {
+        self.list(|r| r.event(turn))
+    }
+
+    /// The attacks and kills frames held before version 3. Attacks become
+    /// match events; kills only echoed the frame's `Removed` changes.
+    fn legacy_events(&mut self, turn: u32) -> Result<Vec<MatchEvent>> {
+        let events = self.list(|r| match r.u8()? {
+            0 => {
+                let (attacker, defender, _damage) = (r.u32()?, r.u32()?, r.f32()?);
+                Ok(Some(legacy_attack(turn, attacker, defender)))
+            }
+            1 => r.u32().map(|_| None),
+            tag => Err(format!("unknown replay event {}", tag).into()),
+        })?;
+        Ok(events.into_iter().flatten().collect())
+    }
+
+    fn frame(&mut self, turn: u32, events: ReadEvents) -> Result<ReplayFrame> {
+        let rng_seed = self.varint()?;
+        let actions = self.list(|r| Ok(ReplayAction {
+            entity_id: r.u32()?,
+            team: r.team()?,
+            action: r.action()?,
+        }))?;
+        let messages = self.list(|r| Ok(ReplayMessage {
+            team: r.team()?,
+            from: r.u32()?,
+            to: r.u32()?.checked_sub(1),
+            data: r.list(Self::f32)?,
+        }))?;
+        let changes = self.list(|r| match r.u8()? {
+            0 => Ok(ReplayChange::Spawned(r.piece()?)),
+            1 => Ok(ReplayChange::Moved { id: r.u32()?, to: r.coord()? }),
+            2 => Ok(ReplayChange::Health { id: r.u32()?, health: r.f32()? }),
+            3 => Ok(ReplayChange::Removed { id: r.u32()? }),
+            4 => Ok(ReplayChange::Point(r.capture_point()?)),
+            tag => Err(format!("unknown board change {}", tag).into()),
+        })?;
+        let events = events(self, turn)?;
+
+        Ok(ReplayFrame {
+            turn,
+            actions,
+            rng_seed,
+            messages,
+            changes,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::objectives::CapturePoint;
+
+    fn piece(id: u32, coord: HexCoord) -> ReplayPiece {
+        ReplayPiece {
+            id,
+            team: Team(1),
+            kind: PieceKind::Unit(UnitType::Fighter),
+            coord,
+            health: 100.0,
+            max_health: 100.0,
+        }
+    }
+
+    fn frame(turn: u32) -> ReplayFrame {
+        ReplayFrame {
+            turn,
+            actions: vec![ReplayAction { entity_id: 1, team: Team(1), action: Action::Move(-1, 1) }],
+            rng_seed: u64::MAX,
+            messages: vec![ReplayMessage { team: Team(1), from: 1, to: None, data: vec![0.5, -2.0] }],
+            changes: vec![
+                ReplayChange::Moved { id: 1, to: HexCoord::new(-4, 3) },
+                ReplayChange::Health { id: 1, health: 62.5 },
+                ReplayChange::Point(CapturePoint { coord: HexCoord::new(0, 0), owner: None, capturing: Some(Team(1)), progress: 2 }),
+            ],
+            events: events(turn),
+        }
+    }
+
+    /// One of every kind of event.
+    fn events(turn: u32) -> Vec<MatchEvent> {
+        let base = Subject::Structure(HexCoord::new(6, -1));
+        [
+            MatchEventKind::Spawned { unit: 1, team: Team(1), unit_type: UnitType::Scout, at: HexCoord::new(-3, 2) },
+            MatchEventKind::Moved { unit: 1, from: HexCoord::new(-3, 2), to: HexCoord::new(-4, 3) },
+            MatchEventKind::Blocked { unit: 2, at: HexCoord::new(0, 0), toward: HexCoord::new(1, 0) },
+            MatchEventKind::Gathered { unit: 2, kind: ResourceKind::Knowledge, amount: 5 },
+            MatchEventKind::BuildStarted { unit: 2, team: Team(0), kind: StructureKind::Barracks, at: HexCoord::new(1, -1) },
+            MatchEventKind::ProductionStarted { team: Team(0), at: HexCoord::new(6, -1), unit_type: UnitType::Worker },
+            MatchEventKind::Captured { team: Team(1), at: HexCoord::new(0, 0) },
+            MatchEventKind::Attack { attacker: Some(2), target: Subject::Unit(1) },
+            MatchEventKind::Attack { attacker: None, target: base },
+            MatchEventKind::Damaged { target: base, amount: 37.5, health: 0.0 },
+            MatchEventKind::Died { target: base, team: Team(0), killer: Some(Team(1)) },
+            MatchEventKind::Died { target: Subject::Unit(1), team: Team(1), killer: None },
+            MatchEventKind::MatchEnded { winners: vec![Team(1), Team(2)], reason: WinReason::Points },
+        ]
+        .into_iter()
+        .map(|kind| MatchEvent::new(turn, kind))
+        .collect()
+    }
+
+    fn sample() -> ReplayFile {
+        ReplayFile {
+            initial_seed: 0xdead_beef,
+            map_config: ReplayMapConfig { width: 20, height: 15, units_per_team: 3 },
+            // Turns skip, as they do when frames without changes are dropped
+            frames: vec![frame(1), frame(2), frame(7), frame(300)],
+            final_outcome: Some(GameOutcome::Win(vec![Team(1)])),
+            match_result: None,
+            keyframes: vec![Keyframe { turn: 0, pieces: vec![piece(1, HexCoord::new(-3, 2))], capture_points: Vec::new() }],
+            config_hash: Some(42),
+            setup: None,
+        }
+    }
+
+    fn json(replay: &ReplayFile) -> serde_json::Value {
+        serde_json::to_value(replay).unwrap()
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        let unsigned = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        let signed = [0, -1, 1, -64, 64, i32::MIN as i64, i64::MIN, i64::MAX];
+        let mut writer = Writer::default();
+        unsigned.iter().for_each(|v| writer.varint(*v));
+        signed.iter().for_each(|v| writer.signed(*v));
+
+        let mut reader = Reader(&writer.0);
+        for value in unsigned {
+            assert_eq!(reader.varint().unwrap(), value);
+        }
+        for value in signed {
+            assert_eq!(reader.signed().unwrap(), value);
+        }
+        assert!(reader.0.is_empty());
+    }
+
+    #[test]
+    fn small_values_take_one_byte() {
+        let mut writer = Writer::default();
+        writer.varint(127);
+        writer.signed(-64);
+        writer.signed(63);
+        assert_eq!(writer.0, [0x7f, 0x7f, 0x7e]);
+    }
+
+    #[test]
+    fn round_trips_uncompressed() {
+        let replay = sample();
+        let bytes = encode(&replay, false).unwrap();
+        assert_eq!(bytes[6] & FLAG_DEFLATE, 0);
+        assert_eq!(json(&decode(&bytes).unwrap()), json(&replay));
+    }
+
+    #[test]
+    fn round_trips_compressed() {
+        let replay = sample();
+        let bytes = encode(&replay, true).unwrap();
+        assert_eq!(bytes[6] & FLAG_DEFLATE, FLAG_DEFLATE);
+        assert_eq!(json(&decode(&bytes).unwrap()), json(&replay));
+    }
+
+    #[test]
+    fn turns_are_stored_as_gaps() {
+        let replay = sample();
+        let decoded = decode(&encode(&replay, false).unwrap()).unwrap();
+        let turns: Vec<u32> = decoded.frames.iter().map(|f| f.turn).collect();
+        assert_eq!(turns, [1, 2, 7, 300]);
+    }
+
+    #[test]
+    fn frames_out_of_order_are_rejected() {
+        let mut replay = sample();
+        replay.frames = vec![frame(5), frame(3)];
+        assert!(encode(&replay, false).is_err());
+    }
+
+    #[test]
+    fn turn_overflow_is_a_decode_error() {
+        let mut body = Writer::default();
+        body.bytes(&serde_json::to_vec(&(sample().map_config, None::<GameOutcome>, None::<MatchResult>, None::<ReplaySetup>)).unwrap());
+        body.varint(0);
+        body.varint(2);
+        for gap in [u32::MAX as u64, 1] {
+            body.varint(gap);
+            body.varint(0);
+            (0..4).for_each(|_| body.varint(0));
+        }
+        assert!(decode_v2(&mut Reader(&body.0)).is_err());
+    }
+
+    #[test]
+    fn truncated_replays_are_errors() {
+        let bytes = encode(&sample(), false).unwrap();
+        assert!(decode(&bytes[..bytes.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn config_hash_ignores_output_settings() {
+        let config = GameConfig::default();
+        let mut output = config.clone();
+        output.replay.directory = "elsewhere".to_string();
+        output.snapshots.interval = 10;
+        output.match_log.enabled = !output.match_log.enabled;
+        output.terrain.seed = Some(7);
+        assert_eq!(config_hash(&output), config_hash(&config));
+
+        let mut rules = config.clone();
+        rules.game.max_turns += 1;
+        assert_ne!(config_hash(&rules), config_hash(&config));
+    }
+
+    /// A version 1 replay byte for byte: no setup in the metadata, and the
+    /// old two-team outcome.
+    #[test]
+    fn reads_version_1() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"ABRP");
+        bytes.extend_from_slice(&[1, 0]); // version 1
+        bytes.push(0); // uncompressed
+        bytes.extend_from_slice(&[0x34, 0x12, 0, 0, 0, 0, 0, 0]); // config hash
+        bytes.extend_from_slice(&[42, 0, 0, 0, 0, 0, 0, 0]); // seed
+        let metadata = br#"[{"width":20,"height":15,"units_per_team":3},"RedWins",null]"#;
+        bytes.push(metadata.len() as u8);
+        bytes.extend_from_slice(metadata);
+        bytes.extend_from_slice(&[
+            1, // one keyframe
+            0, // turn 0
+            1, 1, 0, 0, 1, 3, 2, 0x00, 0x00, 0xc8, 0x42, 0x00, 0x00, 0xc8, 0x42, // red fighter 1 at (-2, 1), 100/100
+            0, // no capture points
+            2, // two frames
+            1, 7, // turn 1, rng seed 7
+            1, 1, 0, 1, 2, 0, // unit 1 moves (1, 0)
+            0, // no messages
+            1, 1, 1, 1, 2, // unit 1 now at (-1, 1)
+            0, // no events
+            2, 9, // turn 3, rng seed 9
+            0, 0,
+            1, 2, 1, 0x00, 0x00, 0x48, 0x42, // unit 1 down to 50
+            2, 0, 2, 1, 0x00, 0x00, 0x48, 0x42, 1, 1, // unit 2 hits unit 1 for 50, unit 1 killed
+        ]);
+
+        let replay = decode(&bytes).unwrap();
+        assert_eq!(replay.initial_seed, 42);
+        assert_eq!(replay.config_hash, Some(0x1234));
+        assert_eq!(replay.map_config.width, 20);
+        assert_eq!(replay.final_outcome, Some(GameOutcome::Win(vec![Team(0)])));
+        assert!(replay.setup.is_none());
+
+        let start = &replay.keyframes[0].pieces[0];
+        assert_eq!(start.kind, PieceKind::Unit(UnitType::Fighter));
+        assert_eq!(start.coord, HexCoord::new(-2, 1));
+        assert_eq!(start.health, 100.0);
+
+        assert_eq!(replay.frames.iter().map(|f| f.turn).collect::<Vec<_>>(), [1, 3]);
+        assert!(matches!(replay.frames[0].actions[0].action, Action::Move(1, 0)));
+        assert!(matches!(replay.frames[0].changes[0], ReplayChange::Moved { id: 1, to } if to == HexCoord::new(-1, 1)));
+        assert!(matches!(replay.frames[1].changes[0], ReplayChange::Health { id: 1, health } if health == 50.0));
+        // The attack carries over as a match event; the kill is already the board's to tell
+        assert_eq!(replay.frames[1].events.len(), 1);
+        assert_eq!(replay.frames[1].events[0].turn, 3);
+        assert!(matches!(replay.frames[1].events[0].kind, MatchEventKind::Attack { attacker: Some(2), target: Subject::Unit(1) }));
+    }
+}
@@ -1,33 +1,43 @@
 ﻿use bevy::prelude::*;
 use serde::{Serialize, Deserialize};
-use crate::units::{Unit, HexPosition, Team};
-use crate::game::TurnState;
-use std::fs;
-use std::path::Path;
-
-// Make Team serializable
-impl Serialize for Team {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        match self {
-            Team::Red => serializer.serialize_str("red"),
-            Team::Blue => serializer.serialize_str("blue"),
-        }
-    }
-}
+use crate::units::{Unit, HexPosition, Team, UnitType, Structure, StructureKind, ProductionOrder};
+use crate::game::{TeamStockpiles, CapturePoints};
+use crate::game::objectives::CapturePoint;
+use crate::world::resource::{Resource as Stockpile, ResourceField, ResourceNode};
+use crate::game::{TurnState, MatchResult};
+use crate::config::GameConfig;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+/// Snapshot files opened by this process, so matches started in the same
+/// millisecond, e.g. a parallel batch, still get a file each.
+static FILES_OPENED: AtomicUsize = AtomicUsize::new(0);
+
+/// Snapshots buffered before a flush; the last turn of a match always flushes.
+const FLUSH_EVERY: usize = 16;
+
+// Teams are written as their roster index; files from the two-team days used names
 impl<'de> Deserialize<'de> for Team {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        match s.as_str() {
-            "red" => Ok(Team::Red),
-            "blue" => Ok(Team::Blue),
-            _ => Err(serde::de::Error::custom("invalid team")),
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Index(u8),
+            Name(String),
+        }
+        
+        match Repr::deserialize(deserializer)? {
+            Repr::Index(index) => Ok(Team(index)),
+            Repr::Name(name) => match name.as_str() {
+                "red" => Ok(Team(0)),
+                "blue" => Ok(Team(1)),
+                _ => Err(serde::de::Error::custom("invalid team")),
+            },
         }
     }
 }
@@ -37,57 +47,159 @@ pub struct GameSnapshot {
     pub turn: u32,
     pub time: f32,
     pub units: Vec<UnitSnapshot>,
+    #[serde(default)]
+    pub stockpiles: Vec<(Team, Stockpile)>,
+    #[serde(default)]
+    pub resource_nodes: Vec<ResourceNode>,
+    #[serde(default)]
+    pub structures: Vec<StructureSnapshot>,
+    #[serde(default)]
+    pub capture_points: Vec<CapturePoint>,
+}
+
+impl GameSnapshot {
+    pub fn capture(
+        turn_state: &TurnState,
+        units: Vec<(&Unit, &HexPosition)>,
+        stockpiles: &TeamStockpiles,
+        field: &ResourceField,
+        structures: Vec<(&Structure, &HexPosition)>,
+        capture_points: &CapturePoints,
+    ) -> Self {
+        GameSnapshot {
+            turn: turn_state.turn,
+            time: turn_state.time,
+            units: units.into_iter()
+                .map(|(unit, pos)| UnitSnapshot {
+                    team: unit.team,
+                    unit_type: unit.unit_type,
+                    health: unit.health,
+                    position: (pos.coord.q, pos.coord.r),
+                })
+                .collect(),
+            stockpiles: {
+                let mut by_team: Vec<_> = stockpiles.0.iter()
+                    .map(|(team, stockpile)| (*team, stockpile.clone()))
+                    .collect();
+                by_team.sort_by_key(|(team, _)| *team);
+                by_team
+            },
+            resource_nodes: field.nodes.clone(),
+            structures: structures.into_iter()
+                .map(|(structure, pos)| StructureSnapshot {
+                    team: structure.team,
+                    kind: structure.kind,
+                    health: structure.health,
+                    position: (pos.coord.q, pos.coord.r),
+                    build_turns_left: structure.build_turns_left,
+                    production: structure.production,
+                })
+                .collect(),
+            capture_points: capture_points.points.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct UnitSnapshot {
     pub team: Team,
+    #[serde(default)]
+    pub unit_type: UnitType,
+    pub health: f32,
+    pub position: (i32, i32),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StructureSnapshot {
+    pub team: Team,
+    pub kind: StructureKind,
     pub health: f32,
     pub position: (i32, i32),
+    pub build_turns_left: u32,
+    pub production: Option<ProductionOrder>,
 }
 
+/// Streams a `GameSnapshot` per recorded turn to a JSON-lines file, so a
+/// long or crashed match still leaves everything up to its last flush.
 #[derive(Resource)]
 pub struct StateRecorder {
-    snapshots: Vec<GameSnapshot>,
     recording: bool,
+    /// Turn of the last snapshot; frames run faster than turns, and a turn is
+    /// only written once.
+    last_turn: Option<u32>,
+    path: Option<PathBuf>,
+    writer: Option<BufWriter<File>>,
+    written: usize,
 }
 
 impl StateRecorder {
     pub fn new() -> Self {
         Self {
-            snapshots: Vec::new(),
             recording: true,
+            last_turn: None,
+            path: None,
+            writer: None,
+            written: 0,
         }
     }
     
-    pub fn record_snapshot(
-        &mut self,
-        turn_state: &TurnState,
-        units: Vec<(&Unit, &HexPosition)>,
-    ) {
+    /// Whether `turn` is due for a snapshot every `interval` turns. The
+    /// final turn of a match is always due.
+    pub fn wants(&self, turn: u32, interval: u32, match_over: bool) -> bool {
+        if !self.recording || interval == 0 {
+            return false;
+        }
+        let due = turn % interval == 0 || match_over;
+        due && self.last_turn.is_none_or(|last| turn > last)
+    }
+    
+    /// Open `game_state_<timestamp>_<n>.jsonl` in `directory` on first use,
+    /// `n` counting the files this process has opened.
+    fn open(&mut self, directory: &Path) -> Result<&mut BufWriter<File>, Box<dyn std::error::Error>> {
+        if self.writer.is_none() {
+            fs::create_dir_all(directory)?;
+            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S_%3f");
+            let n = FILES_OPENED.fetch_add(1, Ordering::Relaxed);
+            let path = directory.join(format!("game_state_{}_{}.jsonl", timestamp, n));
+            self.writer = Some(BufWriter::new(File::create(&path)?));
+            self.path = Some(path);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+    
+    /// Append `snapshot`. Lines are flushed every `FLUSH_EVERY` snapshots and
+    /// at the end of the match, when `match_over`.
+    pub fn record_snapshot(&mut self, directory: &Path, snapshot: &GameSnapshot, match_over: bool) {
         if !self.recording {
             return;
         }
+        self.last_turn = Some(snapshot.turn);
         
-        let snapshot = GameSnapshot {
-            turn: turn_state.turn,
-            time: turn_state.time,
-            units: units.into_iter()
-                .map(|(unit, pos)| UnitSnapshot {
-                    team: unit.team,
-                    health: unit.health,
-                    position: (pos.coord.q, pos.coord.r),
-                })
-                .collect(),
-        };
-        
-        self.snapshots.push(snapshot);
+        let flush = match_over || (self.written + 1).is_multiple_of(FLUSH_EVERY);
+        let written = self.open(directory).and_then(|writer| {
+            serde_json::to_writer(&mut *writer, snapshot)?;
+            writer.write_all(b"\n")?;
+            if flush {
+                writer.flush()?;
+            }
+            Ok(())
+        });
+        match written {
+            Ok(()) => self.written += 1,
+            Err(e) => {
+                eprintln!("Failed to write game state, no longer recording: {}", e);
+                self.recording = false;
+            }
+        }
     }
     
-    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(&self.snapshots)?;
-        fs::write(path, json)?;
-        Ok(())
+    /// The file snapshots are streamed to, once the first one is written.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    
+    pub fn snapshot_count(&self) -> usize {
+        self.written
     }
 }
 
@@ -102,13 +214,137 @@ impl Plugin for StateSerializationPlugin {
 }
 
 fn record_game_state(
-    turn_state: Res<TurnState>,
+    (turn_state, config, result): (Res<TurnState>, Res<GameConfig>, Option<Res<MatchResult>>),
     mut recorder: ResMut<StateRecorder>,
     units: Query<(&Unit, &HexPosition), Without<crate::units::Dead>>,
+    stockpiles: Res<TeamStockpiles>,
+    field: Res<ResourceField>,
+    structures: Query<(&Structure, &HexPosition), Without<crate::units::Dead>>,
+    capture_points: Res<CapturePoints>,
 ) {
-    // Record every 10 turns to avoid huge files
-    if turn_state.turn % 10 == 0 {
-        let unit_data: Vec<_> = units.iter().collect();
-        recorder.record_snapshot(&turn_state, unit_data);
+    let settings = &config.snapshots;
+    if !recorder.wants(turn_state.turn, settings.interval, result.is_some()) {
+        return;
+    }
+    let snapshot = GameSnapshot::capture(
+        &turn_state,
+        units.iter().collect(),
+        &stockpiles,
+        &field,
+        structures.iter().collect(),
+        &capture_points,
+    );
+    recorder.record_snapshot(Path::new(&settings.directory), &snapshot, result.is_some());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_zero_never_records() {
+        let recorder = StateRecorder::new();
+        assert!(!recorder.wants(0, 0, false));
+        assert!(!recorder.wants(7, 0, false));
+        assert!(!recorder.wants(7, 0, true));
+    }
+
+    #[test]
+    fn records_every_interval_turns() {
+        let recorder = StateRecorder::new();
+        let due: Vec<u32> = (1..=12).filter(|turn| recorder.wants(*turn, 4, false)).collect();
+        assert_eq!(due, vec![4, 8, 12]);
+    }
+
+    #[test]
+    fn final_turn_is_always_recorded() {
+        let recorder = StateRecorder::new();
+        assert!(!recorder.wants(7, 4, false));
+        assert!(recorder.wants(7, 4, true));
+    }
+
+    #[test]
+    fn a_turn_is_recorded_once() {
+        let mut recorder = StateRecorder::new();
+        assert!(recorder.wants(8, 4, false));
+        recorder.last_turn = Some(8);
+        assert!(!recorder.wants(8, 4, false));
+        assert!(!recorder.wants(8, 4, true));
+        assert!(recorder.wants(12, 4, false));
+    }
+
+    #[test]
+    fn teams_read_as_an_index_or_an_old_name() {
+        let read = |json: &str| serde_json::from_str::<Team>(json);
+        assert_eq!(read("\"red\"").unwrap(), Team(0));
+        assert_eq!(read("\"blue\"").unwrap(), Team(1));
+        assert_eq!(read("3").unwrap(), Team(3));
+        assert!(read("\"green\"").is_err());
+        assert_eq!(serde_json::to_string(&Team(1)).unwrap(), "1");
+    }
+
+    #[test]
+    fn reads_two_team_snapshots() {
+        let snapshot: GameSnapshot = serde_json::from_str(r#"{
+            "turn": 12,
+            "time": 6.0,
+            "units": [
+                { "team": "red", "health": 80.0, "position": [0, -4] },
+                { "team": "blue", "health": 100.0, "position": [1, 4] }
+            ]
+        }"#).unwrap();
+        let teams: Vec<_> = snapshot.units.iter().map(|u| u.team).collect();
+        assert_eq!(teams, vec![Team(0), Team(1)]);
+        assert_eq!(snapshot.units[0].unit_type, UnitType::default());
+        assert!(snapshot.structures.is_empty() && snapshot.stockpiles.is_empty());
+    }
+
+    fn snapshot(turn: u32) -> GameSnapshot {
+        GameSnapshot {
+            turn,
+            time: turn as f32 * 0.5,
+            units: Vec::new(),
+            stockpiles: Vec::new(),
+            resource_nodes: Vec::new(),
+            structures: Vec::new(),
+            capture_points: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn snapshots_are_flushed_at_match_end() {
+        let dir = std::env::temp_dir().join(format!("arena_snapshots_{}", std::process::id()));
+        let mut recorder = StateRecorder::new();
+        for turn in 1..=3 {
+            recorder.record_snapshot(&dir, &snapshot(turn), false);
+        }
+        let path = recorder.path().unwrap().to_path_buf();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 0);
+
+        recorder.record_snapshot(&dir, &snapshot(4), true);
+        let lines: Vec<GameSnapshot> = fs::read_to_string(&path).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.iter().map(|s| s.turn).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(recorder.snapshot_count(), 4);
+
+        // A second match started straight away writes a file of its own
+        let mut next = StateRecorder::new();
+        next.record_snapshot(&dir, &snapshot(1), true);
+        assert_ne!(next.path(), Some(path.as_path()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn long_matches_flush_as_they_go() {
+        let dir = std::env::temp_dir().join(format!("arena_snapshots_long_{}", std::process::id()));
+        let mut recorder = StateRecorder::new();
+        for turn in 1..=FLUSH_EVERY as u32 + 2 {
+            recorder.record_snapshot(&dir, &snapshot(turn), false);
+        }
+        let written = fs::read_to_string(recorder.path().unwrap()).unwrap();
+        assert_eq!(written.lines().count(), FLUSH_EVERY);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -1,7 +1,11 @@
 ﻿use bevy::prelude::*;
-use crate::units::{Unit, HexPosition, Dead};
-use crate::units::movement::hex_distance;
-use crate::config::{SimulationConfig, SimulationMode};
+use crate::units::{Unit, UnitId, Dead, Structure, Team, TeamRoster, HexPosition};
+use crate::world::HexCoord;
+use crate::world::SpatialIndex;
+use crate::config::{GameConfig, SimulationConfig, SimulationMode};
+use crate::game::victory::MatchScores;
+use crate::game::TurnState;
+use crate::game::events::{MatchEvent, MatchEventKind, Subject};
 
 #[derive(Event)]
 pub struct CombatEvent {
@@ -20,42 +24,121 @@ impl Plugin for CombatPlugin {
     }
 }
 
-pub fn check_combat(
-    units: &Query<(Entity, &Unit, &HexPosition), Without<Dead>>,
-) -> Vec<(Entity, Entity)> {
+/// Every pair of hostile units within `range` of each other, each pair listed once.
+pub fn check_combat(index: &SpatialIndex, roster: &TeamRoster, range: i32) -> Vec<(Entity, Entity)> {
     let mut combat_pairs = Vec::new();
-    let snapshot: Vec<_> = units.iter().collect();
     
-    for i in 0..snapshot.len() {
-        for j in (i + 1)..snapshot.len() {
-            let (e1, u1, p1) = snapshot[i];
-            let (e2, u2, p2) = snapshot[j];
-            
-            if u1.team != u2.team && hex_distance(p1.coord, p2.coord) <= 1 {
+    for (e1, t1, coord) in index.iter() {
+        for (e2, t2, _) in index.get_neighbors(coord, range) {
+            if e1 < e2 && !roster.allied(t1, t2) {
                 combat_pairs.push((e1, e2));
             }
         }
     }
     
+    // Index iteration order is arbitrary; keep damage order reproducible
+    combat_pairs.sort();
     combat_pairs
 }
 
+/// (attacker, structure) for every unit within `range` of an enemy structure.
+/// Structures don't shoot back.
+pub fn check_structure_attacks(
+    index: &SpatialIndex,
+    roster: &TeamRoster,
+    structures: &[(Entity, Team, HexCoord)],
+    range: i32,
+) -> Vec<(Entity, Entity)> {
+    let mut attacks: Vec<_> = structures.iter()
+        .flat_map(|(structure, team, coord)| {
+            index.get_neighbors(*coord, range)
+                .filter(move |(_, t, _)| !roster.allied(*t, *team))
+                .map(move |(attacker, _, _)| (attacker, *structure))
+        })
+        .collect();
+    
+    attacks.sort();
+    attacks
+}
+
 fn resolve_combat(
     mut commands: Commands,
     mut combat_events: EventReader<CombatEvent>,
-    mut units: Query<&mut Unit>,
-    sim_config: Res<SimulationConfig>,
+    mut units: Query<(&mut Unit, &UnitId)>,
+    mut structures: Query<(&mut Structure, &HexPosition), Without<Dead>>,
+    (mut scores, roster): (ResMut<MatchScores>, Res<TeamRoster>),
+    (config, sim_config): (Res<GameConfig>, Res<SimulationConfig>),
+    (turn_state, mut match_events): (Res<TurnState>, EventWriter<MatchEvent>),
 ) {
+    let turn = turn_state.turn;
     for event in combat_events.read() {
+        let attacker = units.get(event.attacker).ok();
+        let attacker_team = attacker.as_ref().map(|(u, _)| u.team);
+        let attacker_id = attacker.map(|(_, id)| id.0);
+        
+        if let Ok((mut structure, pos)) = structures.get_mut(event.defender) {
+            if structure.health <= 0.0 {
+                continue;
+            }
+            let target = Subject::Structure(pos.coord);
+            let dealt = event.damage.min(structure.health);
+            structure.health -= event.damage;
+            if let Some(team) = attacker_team {
+                scores.record_damage(team, dealt);
+            }
+            match_events.send_batch([
+                MatchEvent::new(turn, MatchEventKind::Attack { attacker: attacker_id, target }),
+                MatchEvent::new(turn, MatchEventKind::Damaged { target, amount: dealt, health: structure.health.max(0.0) }),
+            ]);
+            
+            if structure.health <= 0.0 {
+                commands.entity(event.defender).insert(Dead);
+                match_events.send(MatchEvent::new(turn, MatchEventKind::Died {
+                    target,
+                    team: structure.team,
+                    killer: attacker_team,
+                }));
+                if let Some(team) = attacker_team {
+                    scores.record_kill(team, config.victory.points_per_structure);
+                }
+                if sim_config.modes.default == SimulationMode::Visual {
+                    println!("     {} {:?} destroyed!", roster.tag(structure.team), structure.kind);
+                }
+            }
+            continue;
+        }
+        
         // Apply damage to defender
-        if let Ok(mut defender_unit) = units.get_mut(event.defender) {
+        if let Ok((mut defender_unit, defender_id)) = units.get_mut(event.defender) {
+            if defender_unit.health <= 0.0 {
+                continue;
+            }
+            let dealt = event.damage.min(defender_unit.health);
             defender_unit.health -= event.damage;
+            if let Some(team) = attacker_team {
+                scores.record_damage(team, dealt);
+            }
+            let target = Subject::Unit(defender_id.0);
+            match_events.send_batch([
+                MatchEvent::new(turn, MatchEventKind::Attack { attacker: attacker_id, target }),
+                MatchEvent::new(turn, MatchEventKind::Damaged { target, amount: dealt, health: defender_unit.health.max(0.0) }),
+            ]);
+            if defender_unit.health <= 0.0 {
+                match_events.send(MatchEvent::new(turn, MatchEventKind::Died {
+                    target,
+                    team: defender_unit.team,
+                    killer: attacker_team,
+                }));
+            }
             
             if defender_unit.health <= 0.0 {
                 commands.entity(event.defender).insert(Dead);
+                if let Some(team) = attacker_team {
+                    scores.record_kill(team, config.victory.points_per_kill);
+                }
                 if sim_config.modes.default == SimulationMode::Visual {
-                    println!("     {} {:?} Fighter defeated!", 
-                        defender_unit.team.tag(), defender_unit.team);
+                    println!("     {} {:?} defeated!", 
+                        roster.tag(defender_unit.team), defender_unit.unit_type);
                 }
             }
         }
@@ -0,0 +1,108 @@
+﻿use bevy::prelude::*;
+use bevy::sprite::{Sprite, SpriteBundle};
+use std::collections::HashMap;
+use crate::config::{SimulationConfig, SimulationMode};
+use crate::units::Team;
+use crate::units::movement::{hex_distance, hex_to_world_pos};
+use crate::world::HexCoord;
+use crate::world::resource::{Resource, ResourceField, ResourceKind};
+
+/// What each team has gathered and not yet spent. Teams that have never
+/// gathered anything have no entry.
+#[derive(Resource, Default)]
+pub struct TeamStockpiles(pub HashMap<Team, Resource>);
+
+impl TeamStockpiles {
+    pub fn get(&self, team: Team) -> Resource {
+        self.0.get(&team).cloned().unwrap_or_else(Resource::new)
+    }
+}
+
+#[derive(Component)]
+pub struct ResourceMarker {
+    pub node: usize,
+}
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TeamStockpiles>()
+            .add_systems(Startup, spawn_resource_markers.after(crate::world::terrain::generate_terrain))
+            .add_systems(Update, update_resource_markers);
+    }
+}
+
+/// Harvest for a worker of `team` standing on `coord`: the first non-empty node
+/// on its own hex or an adjacent one. Returns what was gathered, if anything.
+pub fn resolve_gather(
+    field: &mut ResourceField,
+    stockpiles: &mut TeamStockpiles,
+    team: Team,
+    coord: HexCoord,
+    rate: i32,
+) -> Option<(ResourceKind, i32)> {
+    let node = field.nodes.iter_mut()
+        .filter(|n| !n.is_depleted() && hex_distance(n.coord, coord) <= 1)
+        .min_by_key(|n| hex_distance(n.coord, coord))?;
+    
+    let taken = rate.min(node.amount);
+    node.amount -= taken;
+    stockpiles.0.entry(team).or_insert_with(Resource::new).add(node.kind, taken);
+    Some((node.kind, taken))
+}
+
+fn kind_color(kind: ResourceKind) -> Color {
+    match kind {
+        ResourceKind::Energy => Color::rgb(1.0, 0.85, 0.2),
+        ResourceKind::Metal => Color::rgb(0.75, 0.75, 0.8),
+        ResourceKind::Knowledge => Color::rgb(0.7, 0.4, 0.9),
+    }
+}
+
+fn spawn_resource_markers(
+    mut commands: Commands,
+    field: Res<ResourceField>,
+    sim_config: Res<SimulationConfig>,
+) {
+    if sim_config.modes.default != SimulationMode::Visual {
+        return;
+    }
+    
+    for (i, node) in field.nodes.iter().enumerate() {
+        let pos = hex_to_world_pos(node.coord.q, node.coord.r);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: kind_color(node.kind),
+                    custom_size: Some(Vec2::new(14.0, 14.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 0.5))
+                    .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+                ..default()
+            },
+            ResourceMarker { node: i },
+        ));
+    }
+}
+
+/// Shrink and fade markers as their node is harvested.
+fn update_resource_markers(
+    field: Res<ResourceField>,
+    mut markers: Query<(&ResourceMarker, &mut Sprite)>,
+) {
+    if !field.is_changed() {
+        return;
+    }
+    
+    for (marker, mut sprite) in &mut markers {
+        if let Some(node) = field.nodes.get(marker.node) {
+            let ratio = (node.amount as f32 / node.max_amount.max(1) as f32).clamp(0.0, 1.0);
+            let size = 6.0 + 8.0 * ratio;
+            sprite.custom_size = Some(Vec2::new(size, size));
+            sprite.color = kind_color(node.kind).with_a(if node.is_depleted() { 0.25 } else { 1.0 });
+        }
+    }
+}
@@ -0,0 +1,120 @@
+﻿use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::units::{Team, UnitType, StructureKind};
+use crate::world::HexCoord;
+use crate::world::resource::ResourceKind;
+use crate::game::victory::WinReason;
+
+/// Registers `MatchEvent` for every plugin that sends or reads it.
+pub struct MatchEventsPlugin;
+
+impl Plugin for MatchEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MatchEvent>();
+    }
+}
+
+/// Something that happened in the match, stamped with the turn it happened
+/// on. The turn manager, combat, victory and spawning systems send these;
+/// `engine::MatchLog` writes them out one JSON object per line.
+#[derive(Event, Clone, Debug, Serialize, Deserialize)]
+pub struct MatchEvent {
+    pub turn: u32,
+    #[serde(flatten)]
+    pub kind: MatchEventKind,
+}
+
+impl MatchEvent {
+    pub fn new(turn: u32, kind: MatchEventKind) -> Self {
+        Self { turn, kind }
+    }
+}
+
+/// A unit by its `UnitId`, or a structure by the hex it stands on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subject {
+    Unit(u32),
+    Structure(HexCoord),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MatchEventKind {
+    Spawned { unit: u32, team: Team, unit_type: UnitType, at: HexCoord },
+    Moved { unit: u32, from: HexCoord, to: HexCoord },
+    /// The hex `toward` was impassable or taken.
+    Blocked { unit: u32, at: HexCoord, toward: HexCoord },
+    Gathered { unit: u32, kind: ResourceKind, amount: i32 },
+    BuildStarted { unit: u32, team: Team, kind: StructureKind, at: HexCoord },
+    ProductionStarted { team: Team, at: HexCoord, unit_type: UnitType },
+    Captured { team: Team, at: HexCoord },
+    Attack { attacker: Option<u32>, target: Subject },
+    /// `amount` is what was actually taken off, never more than was left.
+    Damaged { target: Subject, amount: f32, health: f32 },
+    Died { target: Subject, team: Team, killer: Option<Team> },
+    MatchEnded { winners: Vec<Team>, reason: WinReason },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(event: &MatchEvent) -> serde_json::Value {
+        let value = serde_json::to_value(event).unwrap();
+        let read: MatchEvent = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), value);
+        value
+    }
+
+    #[test]
+    fn events_are_flat_objects_tagged_by_kind() {
+        let spawned = MatchEvent::new(0, MatchEventKind::Spawned {
+            unit: 3,
+            team: Team(1),
+            unit_type: UnitType::Worker,
+            at: HexCoord::new(-2, 1),
+        });
+        assert_eq!(round_trip(&spawned), json!({
+            "turn": 0,
+            "event": "spawned",
+            "unit": 3,
+            "team": 1,
+            "unit_type": "worker",
+            "at": { "q": -2, "r": 1 },
+        }));
+
+        let ended = MatchEvent::new(90, MatchEventKind::MatchEnded { winners: vec![Team(0), Team(2)], reason: WinReason::Elimination });
+        assert_eq!(round_trip(&ended), json!({
+            "turn": 90,
+            "event": "match_ended",
+            "winners": [0, 2],
+            "reason": "elimination",
+        }));
+    }
+
+    #[test]
+    fn subjects_name_a_unit_or_a_structure_hex() {
+        let on_unit = MatchEvent::new(7, MatchEventKind::Attack { attacker: Some(4), target: Subject::Unit(9) });
+        assert_eq!(round_trip(&on_unit), json!({
+            "turn": 7,
+            "event": "attack",
+            "attacker": 4,
+            "target": { "unit": 9 },
+        }));
+
+        let on_structure = MatchEvent::new(8, MatchEventKind::Died {
+            target: Subject::Structure(HexCoord::new(5, 0)),
+            team: Team(0),
+            killer: None,
+        });
+        assert_eq!(round_trip(&on_structure), json!({
+            "turn": 8,
+            "event": "died",
+            "target": { "structure": { "q": 5, "r": 0 } },
+            "team": 0,
+            "killer": null,
+        }));
+    }
+}
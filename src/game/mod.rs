@@ -1,7 +1,17 @@
 ﻿pub mod turn_manager;
 pub mod combat_system;
 pub mod victory;
+pub mod economy;
+pub mod production;
+pub mod objectives;
+pub mod session;
+pub mod events;
 
 pub use turn_manager::{TurnState, TurnManagerPlugin};
 pub use combat_system::CombatPlugin;
-pub use victory::{VictoryPlugin, GameOver};
+pub use victory::{VictoryPlugin, MatchResult, WinReason, AdjudicateMatch};
+pub use economy::{EconomyPlugin, TeamStockpiles};
+pub use production::ProductionPlugin;
+pub use objectives::{ObjectivesPlugin, CapturePoints};
+pub use session::{SessionPlugin, SimControl, ResetMatch};
+pub use events::{MatchEventsPlugin, MatchEvent};
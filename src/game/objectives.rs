@@ -0,0 +1,214 @@
+﻿use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use serde::{Serialize, Deserialize};
+use crate::config::{GameConfig, SimulationConfig, SimulationMode};
+use crate::units::{Team, TeamRoster};
+use crate::units::movement::hex_to_world_pos;
+use crate::world::HexCoord;
+use crate::world::hex_grid::{create_hex_mesh, HEX_SIZE};
+
+/// One king-of-the-hill hex and who holds it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapturePoint {
+    pub coord: HexCoord,
+    pub owner: Option<Team>,
+    /// Team currently holding the point alone, if it isn't already the owner.
+    pub capturing: Option<Team>,
+    /// Consecutive turns `capturing` has held the point.
+    pub progress: u32,
+}
+
+impl CapturePoint {
+    pub fn new(coord: HexCoord) -> Self {
+        Self {
+            coord,
+            owner: None,
+            capturing: None,
+            progress: 0,
+        }
+    }
+
+    /// Advance one turn given the teams with a unit on the point.
+    /// Returns the new owner when the point changes hands.
+    pub fn update(&mut self, occupants: &[Team], capture_turns: u32) -> Option<Team> {
+        let sole = occupants.first().copied()
+            .filter(|team| occupants.iter().all(|t| t == team));
+
+        match sole {
+            Some(team) if Some(team) != self.owner => {
+                if self.capturing == Some(team) {
+                    self.progress += 1;
+                } else {
+                    self.capturing = Some(team);
+                    self.progress = 1;
+                }
+                if self.progress >= capture_turns {
+                    self.owner = Some(team);
+                    self.capturing = None;
+                    self.progress = 0;
+                    return Some(team);
+                }
+            }
+            // Empty, contested, or held by the owner: any capture in progress is lost
+            _ => {
+                self.capturing = None;
+                self.progress = 0;
+            }
+        }
+        None
+    }
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct CapturePoints {
+    pub points: Vec<CapturePoint>,
+}
+
+impl CapturePoints {
+    pub fn from_config(config: &GameConfig) -> Self {
+        Self {
+            points: config.objectives.coords().into_iter().map(CapturePoint::new).collect(),
+        }
+    }
+
+    pub fn owned_by(&self, team: Team) -> usize {
+        self.points.iter().filter(|p| p.owner == Some(team)).count()
+    }
+
+    /// Run one turn of capturing for every point given live unit positions.
+    /// Only a unit standing on the point's own hex counts towards it.
+    /// Returns the points that changed hands.
+    pub fn update(&mut self, units: &[(Team, HexCoord)], capture_turns: u32) -> Vec<(Team, HexCoord)> {
+        let mut captured = Vec::new();
+        for point in &mut self.points {
+            let occupants: Vec<Team> = units.iter()
+                .filter(|(_, coord)| *coord == point.coord)
+                .map(|(team, _)| *team)
+                .collect();
+            if let Some(team) = point.update(&occupants, capture_turns) {
+                captured.push((team, point.coord));
+            }
+        }
+        captured
+    }
+}
+
+#[derive(Component)]
+pub struct CapturePointMarker {
+    pub index: usize,
+}
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CapturePoints>()
+            .add_systems(Startup, setup_capture_points)
+            .add_systems(Update, update_capture_markers);
+    }
+}
+
+fn setup_capture_points(
+    mut commands: Commands,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+    config: Res<GameConfig>,
+    sim_config: Res<SimulationConfig>,
+) {
+    let capture_points = CapturePoints::from_config(&config);
+
+    if sim_config.modes.default == SimulationMode::Visual {
+        if let (Some(meshes), Some(materials)) = (meshes.as_mut(), materials.as_mut()) {
+            let mesh = meshes.add(create_hex_mesh(HEX_SIZE * 0.8));
+            for (index, point) in capture_points.points.iter().enumerate() {
+                let pos = hex_to_world_pos(point.coord.q, point.coord.r);
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: mesh.clone().into(),
+                        material: materials.add(ColorMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.35))),
+                        transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 0.3)),
+                        ..default()
+                    },
+                    CapturePointMarker { index },
+                ));
+            }
+        }
+    }
+
+    commands.insert_resource(capture_points);
+}
+
+/// Tint each point by its owner, blending toward the capturing team while a capture is underway.
+fn update_capture_markers(
+    capture_points: Res<CapturePoints>,
+    config: Res<GameConfig>,
+    roster: Res<TeamRoster>,
+    markers: Query<(&CapturePointMarker, &Handle<ColorMaterial>)>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+) {
+    if !capture_points.is_changed() {
+        return;
+    }
+    let Some(materials) = materials.as_mut() else {
+        return;
+    };
+
+    for (marker, handle) in &markers {
+        let (Some(point), Some(material)) = (capture_points.points.get(marker.index), materials.get_mut(handle)) else {
+            continue;
+        };
+        let base = point.owner.map_or(Color::WHITE, |team| roster.color(team));
+        material.color = match point.capturing {
+            Some(team) => {
+                let t = point.progress as f32 / config.objectives.capture_turns.max(1) as f32;
+                let target = roster.color(team);
+                Color::rgb(
+                    base.r() + (target.r() - base.r()) * t,
+                    base.g() + (target.g() - base.g()) * t,
+                    base.b() + (target.b() - base.b()) * t,
+                )
+            }
+            None => base,
+        }
+        .with_a(0.5);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> CapturePoints {
+        CapturePoints { points: vec![CapturePoint::new(HexCoord::new(0, 0))] }
+    }
+
+    #[test]
+    fn a_point_is_captured_from_its_own_hex() {
+        let mut points = points();
+        let on = [(Team(1), HexCoord::new(0, 0))];
+        assert!(points.update(&on, 2).is_empty());
+        assert_eq!(points.points[0].capturing, Some(Team(1)));
+        assert_eq!(points.update(&on, 2), vec![(Team(1), HexCoord::new(0, 0))]);
+        assert_eq!(points.owned_by(Team(1)), 1);
+    }
+
+    #[test]
+    fn neighbours_do_not_hold_a_point() {
+        let mut points = points();
+        let next_to = [(Team(1), HexCoord::new(1, 0)), (Team(1), HexCoord::new(0, -1))];
+        for _ in 0..3 {
+            assert!(points.update(&next_to, 2).is_empty());
+        }
+        assert_eq!(points.points[0].capturing, None);
+    }
+
+    #[test]
+    fn a_contested_point_loses_its_progress() {
+        let mut points = points();
+        points.update(&[(Team(0), HexCoord::new(0, 0))], 3);
+        points.update(&[(Team(0), HexCoord::new(0, 0)), (Team(1), HexCoord::new(0, 0))], 3);
+        assert_eq!(points.points[0].capturing, None);
+        assert_eq!(points.points[0].progress, 0);
+    }
+}
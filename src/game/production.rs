@@ -0,0 +1,343 @@
+﻿use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Sprite, SpriteBundle};
+use std::collections::HashSet;
+use crate::config::GameConfig;
+use crate::config::game_config::ProductionSettings;
+use crate::units::{Team, TeamRoster, UnitType, UnitId, Structure, StructureKind, ProductionOrder, HexPosition};
+use crate::units::health::HealthBar;
+use crate::units::movement::hex_to_world_pos;
+use crate::units::spawning::{UnitSpawner, spawn_initial_units};
+use crate::world::{HexCoord, TerrainMap};
+use crate::world::resource::{Resource, ResourceField};
+use crate::world::hex_grid::create_hex_mesh;
+
+/// Entities the turn system wants created. Spawning happens in its own system
+/// so the turn system doesn't need render assets.
+#[derive(Event, Clone, Copy, Debug)]
+pub enum SpawnRequest {
+    Unit {
+        team: Team,
+        unit_type: UnitType,
+        coord: HexCoord,
+    },
+    Structure {
+        team: Team,
+        kind: StructureKind,
+        coord: HexCoord,
+        build_turns: u32,
+    },
+}
+
+pub struct ProductionPlugin;
+
+impl Plugin for ProductionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SpawnRequest>()
+            // After the starting units, so ids come out in the same order every match
+            .add_systems(Startup, place_starting_bases.after(spawn_initial_units))
+            .add_systems(PreUpdate, place_starting_bases
+                .in_set(crate::game::session::MatchSetup)
+                .after(spawn_initial_units))
+            .add_systems(Update, (
+                handle_spawn_requests.after(crate::game::turn_manager::game_turn_system),
+                update_structure_visuals,
+            ));
+    }
+}
+
+/// Spawned directly rather than through `SpawnRequest` so the bases exist
+/// before victory is first checked.
+fn place_starting_bases(
+    mut spawner: UnitSpawner,
+    config: Res<GameConfig>,
+    roster: Res<TeamRoster>,
+) {
+    if !config.production.enabled || !config.production.start_with_base {
+        return;
+    }
+
+    // Each base sits on its team's spawn point, which spawning leaves free
+    let is_visual = spawner.is_visual();
+    for info in roster.iter() {
+        let id = spawner.next_id();
+        spawn_structure(
+            &mut spawner.commands,
+            spawner.meshes.as_deref_mut().zip(spawner.materials.as_deref_mut()).filter(|_| is_visual),
+            id,
+            new_structure(&config.production, info.team, StructureKind::Base, 0),
+            info.color,
+            info.spawn,
+        );
+    }
+}
+
+fn new_structure(settings: &ProductionSettings, team: Team, kind: StructureKind, build_turns: u32) -> Structure {
+    let health = settings.structure_cost(kind).health;
+    Structure {
+        team,
+        kind,
+        health,
+        max_health: health,
+        build_turns_left: build_turns,
+        production: None,
+    }
+}
+
+/// Queue `unit_type` on an idle, finished structure that can train it,
+/// charging the team up front. Returns whether the order was accepted.
+pub fn try_produce(
+    structure: &mut Structure,
+    unit_type: UnitType,
+    stockpile: &mut Resource,
+    settings: &ProductionSettings,
+) -> bool {
+    if !structure.is_complete() || structure.production.is_some() || !structure.kind.can_produce(unit_type) {
+        return false;
+    }
+
+    let cost = settings.unit_cost(unit_type);
+    if !stockpile.spend(&cost.resources()) {
+        return false;
+    }
+
+    structure.production = Some(ProductionOrder {
+        unit_type,
+        turns_left: cost.build_turns,
+    });
+    true
+}
+
+/// Passable and nobody standing or built on it.
+fn is_free(terrain: &TerrainMap, occupied: &HashSet<(i32, i32)>, coord: HexCoord) -> bool {
+    terrain.is_passable(coord) && !occupied.contains(&(coord.q, coord.r))
+}
+
+/// Whether a structure may be placed on `coord` this turn. Resource nodes
+/// stay open for harvesting, so nothing is built on top of one.
+pub fn can_build_at(
+    terrain: &TerrainMap,
+    resources: &ResourceField,
+    occupied: &HashSet<(i32, i32)>,
+    coord: HexCoord,
+) -> bool {
+    is_free(terrain, occupied, coord) && resources.node_at(coord).is_none()
+}
+
+/// Tick construction and training by one turn. Returns the unit type that
+/// finished training this turn, if any; the caller decides where it appears.
+pub fn advance_production(structure: &mut Structure) -> Option<UnitType> {
+    if !structure.is_complete() {
+        structure.build_turns_left -= 1;
+        return None;
+    }
+
+    let order = structure.production.as_mut()?;
+    order.turns_left = order.turns_left.saturating_sub(1);
+    (order.turns_left == 0).then_some(order.unit_type)
+}
+
+/// First free, passable hex next to `coord`, for a freshly trained unit.
+pub fn free_neighbor(terrain: &TerrainMap, occupied: &HashSet<(i32, i32)>, coord: HexCoord) -> Option<HexCoord> {
+    coord.neighbors().find(|n| is_free(terrain, occupied, *n))
+}
+
+fn handle_spawn_requests(
+    mut spawner: UnitSpawner,
+    mut requests: EventReader<SpawnRequest>,
+    config: Res<GameConfig>,
+    roster: Res<TeamRoster>,
+) {
+    let is_visual = spawner.is_visual();
+
+    for request in requests.read() {
+        match *request {
+            SpawnRequest::Unit { team, unit_type, coord } => {
+                if is_visual {
+                    println!("   {} {:?} trained at ({}, {})", roster.tag(team), unit_type, coord.q, coord.r);
+                }
+                spawner.spawn(team, unit_type, coord);
+            }
+            SpawnRequest::Structure { team, kind, coord, build_turns } => {
+                if is_visual {
+                    println!("   {} {:?} placed at ({}, {})", roster.tag(team), kind, coord.q, coord.r);
+                }
+                let id = spawner.next_id();
+                spawn_structure(
+                    &mut spawner.commands,
+                    spawner.meshes.as_deref_mut().zip(spawner.materials.as_deref_mut()).filter(|_| is_visual),
+                    id,
+                    new_structure(&config.production, team, kind, build_turns),
+                    roster.color(team),
+                    coord,
+                );
+            }
+        }
+    }
+}
+
+/// Same split as `spawn_unit`: visuals when render assets are available.
+fn spawn_structure(
+    commands: &mut Commands,
+    assets: Option<(&mut Assets<Mesh>, &mut Assets<ColorMaterial>)>,
+    id: UnitId,
+    structure: Structure,
+    team_color: Color,
+    coord: HexCoord,
+) {
+    let Some((meshes, materials)) = assets else {
+        commands.spawn((structure, id, HexPosition { coord }, Transform::default()));
+        return;
+    };
+
+    let world_pos = hex_to_world_pos(coord.q, coord.r);
+    let size = match structure.kind {
+        StructureKind::Base => 24.0,
+        StructureKind::Barracks => 18.0,
+    };
+
+    let alpha = if structure.is_complete() { 1.0 } else { 0.4 };
+    let color = team_color.with_a(alpha);
+    let entity = commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(create_hex_mesh(size)).into(),
+            material: materials.add(ColorMaterial::from(color)),
+            transform: Transform::from_translation(Vec3::new(world_pos.x, world_pos.y, 0.8)),
+            ..default()
+        },
+        structure,
+        id,
+        HexPosition { coord },
+    )).id();
+
+    let health_bar = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::GREEN,
+                custom_size: Some(Vec2::new(60.0, 8.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(0.0, 32.0, 3.0)),
+            ..default()
+        },
+        HealthBar,
+    )).id();
+
+    commands.entity(entity).add_child(health_bar);
+}
+
+/// Structures under construction are drawn faded until they are finished.
+fn update_structure_visuals(
+    structures: Query<(&Structure, &Handle<ColorMaterial>), Changed<Structure>>,
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+    roster: Res<TeamRoster>,
+) {
+    let Some(materials) = materials.as_mut() else {
+        return;
+    };
+
+    for (structure, handle) in &structures {
+        if let Some(material) = materials.get_mut(handle) {
+            let alpha = if structure.is_complete() { 1.0 } else { 0.4 };
+            material.color = roster.color(structure.team).with_a(alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::HexGrid;
+    use crate::world::resource::{ResourceKind, ResourceNode};
+    use crate::world::terrain::TerrainType;
+
+    fn settings() -> ProductionSettings {
+        ProductionSettings { enabled: true, ..ProductionSettings::default() }
+    }
+
+    fn base() -> Structure {
+        new_structure(&settings(), Team(0), StructureKind::Base, 0)
+    }
+
+    fn rich() -> Resource {
+        Resource { energy: 1000, metal: 1000, knowledge: 1000 }
+    }
+
+    #[test]
+    fn construction_counts_down_before_training() {
+        let mut barracks = new_structure(&settings(), Team(0), StructureKind::Barracks, 2);
+        assert!(!try_produce(&mut barracks, UnitType::Fighter, &mut rich(), &settings()));
+        assert_eq!(advance_production(&mut barracks), None);
+        assert_eq!(advance_production(&mut barracks), None);
+        assert!(barracks.is_complete());
+        assert_eq!(barracks.health, settings().barracks.health);
+    }
+
+    #[test]
+    fn training_finishes_after_its_build_turns() {
+        let settings = settings();
+        let mut base = base();
+        assert!(try_produce(&mut base, UnitType::Worker, &mut rich(), &settings));
+        for _ in 1..settings.worker.build_turns {
+            assert_eq!(advance_production(&mut base), None);
+        }
+        assert_eq!(advance_production(&mut base), Some(UnitType::Worker));
+
+        // Done but not yet placed: it keeps coming out until the caller clears it
+        assert_eq!(advance_production(&mut base), Some(UnitType::Worker));
+        base.production = None;
+        assert_eq!(advance_production(&mut base), None);
+    }
+
+    #[test]
+    fn orders_are_paid_for_up_front() {
+        let settings = settings();
+        let mut stockpile = rich();
+        let mut barracks = new_structure(&settings, Team(0), StructureKind::Barracks, 0);
+        assert!(try_produce(&mut barracks, UnitType::Scout, &mut stockpile, &settings));
+        assert_eq!(stockpile.energy, 1000 - settings.scout.energy);
+        assert_eq!(stockpile.metal, 1000 - settings.scout.metal);
+        assert_eq!(stockpile.knowledge, 1000 - settings.scout.knowledge);
+        assert_eq!(barracks.production.map(|o| o.turns_left), Some(settings.scout.build_turns));
+
+        // Busy now, and nothing more is charged
+        assert!(!try_produce(&mut barracks, UnitType::Fighter, &mut stockpile, &settings));
+        assert_eq!(stockpile.energy, 1000 - settings.scout.energy);
+    }
+
+    #[test]
+    fn orders_are_refused_without_the_means() {
+        let settings = settings();
+        let mut base = base();
+        assert!(!try_produce(&mut base, UnitType::Fighter, &mut rich(), &settings));
+
+        let mut poor = Resource { energy: settings.worker.energy - 1, metal: 0, knowledge: 0 };
+        assert!(!try_produce(&mut base, UnitType::Worker, &mut poor, &settings));
+        assert_eq!(poor.energy, settings.worker.energy - 1);
+        assert!(base.production.is_none());
+    }
+
+    #[test]
+    fn structures_go_on_free_passable_hexes_without_a_node() {
+        let grid = HexGrid { width: 10, height: 10 };
+        let terrain = TerrainMap::from_tiles(grid, [(HexCoord::new(1, 0), TerrainType::Water)]);
+        let resources = ResourceField {
+            nodes: vec![ResourceNode { coord: HexCoord::new(0, 1), kind: ResourceKind::Metal, amount: 0, max_amount: 100 }],
+        };
+        let occupied: HashSet<(i32, i32)> = [(-1, 0)].into();
+
+        assert!(can_build_at(&terrain, &resources, &occupied, HexCoord::new(2, 2)));
+        assert!(!can_build_at(&terrain, &resources, &occupied, HexCoord::new(1, 0)));
+        assert!(!can_build_at(&terrain, &resources, &occupied, HexCoord::new(-1, 0)));
+        // Even a depleted node keeps its hex
+        assert!(!can_build_at(&terrain, &resources, &occupied, HexCoord::new(0, 1)));
+        assert!(!can_build_at(&terrain, &resources, &occupied, HexCoord::new(9, 0)));
+
+        // Trained units may still step out onto a node
+        let all_but_node: HashSet<(i32, i32)> = HexCoord::new(0, 0).neighbors()
+            .filter(|n| *n != HexCoord::new(0, 1))
+            .map(|n| (n.q, n.r))
+            .collect();
+        assert_eq!(free_neighbor(&terrain, &all_but_node, HexCoord::new(0, 0)), Some(HexCoord::new(0, 1)));
+    }
+}
@@ -0,0 +1,137 @@
+﻿use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use crate::config::{GameConfig, SimulationConfig, SimulationMode};
+use crate::engine::{MatchLog, ReplayRecorder, StateRecorder};
+use crate::game::{TurnState, TeamStockpiles, CapturePoints, MatchResult};
+use crate::game::combat_system::CombatEvent;
+use crate::game::production::SpawnRequest;
+use crate::game::victory::MatchScores;
+use crate::units::{Unit, Structure};
+use crate::units::spawning::UnitIds;
+use crate::world::{TeamVisibility, SpatialIndex};
+use crate::world::resource::ResourceField;
+
+/// Speed multipliers the visual client steps through with +/-.
+const SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Playback controls for the turn loop. Only the visual client changes these.
+#[derive(Resource)]
+pub struct SimControl {
+    pub paused: bool,
+    step: bool,
+    speed_index: usize,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            step: false,
+            speed_index: 2,
+        }
+    }
+}
+
+impl SimControl {
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Run exactly one turn while paused.
+    pub fn request_step(&mut self) {
+        if self.paused {
+            self.step = true;
+        }
+    }
+
+    /// Whether a single step is due, clearing the request.
+    pub fn take_step(&mut self) -> bool {
+        std::mem::take(&mut self.step)
+    }
+
+    /// Multiplier applied to `game.tick_rate`.
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed_index]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+}
+
+/// Start the match over on the same map. Each plugin respawns its own
+/// starting state on this event.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ResetMatch;
+
+/// `reset_match` and every system that sets a new match up after it. Runs
+/// as a whole in `PreUpdate` on the frame after a `ResetMatch` is sent, so
+/// nothing in `Update` sees the old match half torn down.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchSetup;
+
+/// Per-match resources cleared by a reset.
+#[derive(SystemParam)]
+pub struct MatchState<'w> {
+    pub turn_state: ResMut<'w, TurnState>,
+    pub scores: ResMut<'w, MatchScores>,
+    pub stockpiles: ResMut<'w, TeamStockpiles>,
+    pub visibility: ResMut<'w, TeamVisibility>,
+    pub spatial_index: ResMut<'w, SpatialIndex>,
+    pub resources: ResMut<'w, ResourceField>,
+    pub capture_points: ResMut<'w, CapturePoints>,
+    pub spawn_requests: ResMut<'w, Events<SpawnRequest>>,
+    pub combat_events: ResMut<'w, Events<CombatEvent>>,
+    pub unit_ids: ResMut<'w, UnitIds>,
+}
+
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimControl>()
+            .add_event::<ResetMatch>()
+            .configure_sets(PreUpdate, MatchSetup.run_if(on_event::<ResetMatch>()))
+            .add_systems(PreUpdate, reset_match.in_set(MatchSetup));
+    }
+}
+
+/// Clear everything the last match left behind. Units, bases and AI state
+/// are recreated by their own plugins right after this.
+pub fn reset_match(
+    mut commands: Commands,
+    units: Query<Entity, With<Unit>>,
+    structures: Query<Entity, With<Structure>>,
+    (config, sim_config): (Res<GameConfig>, Res<SimulationConfig>),
+    mut state: MatchState,
+) {
+    for entity in units.iter().chain(&structures) {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<MatchResult>();
+
+    let seed = chrono::Local::now().timestamp() as u64;
+    commands.insert_resource(ReplayRecorder::new(seed));
+    commands.insert_resource(StateRecorder::new());
+    commands.insert_resource(MatchLog::new());
+
+    *state.turn_state = TurnState { turn: 0, time: 0.0 };
+    *state.scores = MatchScores::default();
+    *state.stockpiles = TeamStockpiles::default();
+    *state.visibility = TeamVisibility::default();
+    state.spatial_index.clear();
+    state.resources.replenish();
+    *state.capture_points = CapturePoints::from_config(&config);
+    state.spawn_requests.clear();
+    state.combat_events.clear();
+    *state.unit_ids = UnitIds::default();
+
+    if sim_config.modes.default == SimulationMode::Visual {
+        println!("\n[RESET] Match restarted");
+    }
+}
@@ -1,9 +1,20 @@
 ﻿use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
 use std::collections::{HashSet, HashMap};
-use crate::units::{Unit, HexPosition, Dead, Team};
-use crate::ai::{AiController, WorldSnapshot};
-use crate::world::actions::Action;
-use crate::game::combat_system::{CombatEvent, check_combat};
+use crate::units::{Unit, UnitId, HexPosition, Dead, Team, TeamRoster, Vision, UnitType, Structure, LastAction};
+use crate::units::movement::hex_distance;
+use crate::ai::{AiController, WorldSnapshot, DecisionContext, CommChannel, AiDebug};
+use crate::ai::controller::StructureInfo;
+use crate::world::actions::{Action, ActionResult};
+use crate::world::{HexCoord, TerrainMap, Pathfinder, TeamVisibility, SpatialIndex};
+use crate::world::resource::{Resource, ResourceField};
+use crate::game::economy::{TeamStockpiles, resolve_gather};
+use crate::game::combat_system::{CombatEvent, check_combat, check_structure_attacks};
+use crate::game::objectives::CapturePoints;
+use crate::game::victory::MatchScores;
+use crate::game::events::{MatchEvent, MatchEventKind};
+use crate::game::session::SimControl;
+use crate::game::production::{SpawnRequest, try_produce, can_build_at, advance_production, free_neighbor};
 use crate::config::{GameConfig, SimulationConfig, SimulationMode};
 use crate::performance::PerformanceMetrics;
 use crate::engine::ReplayRecorder;
@@ -14,8 +25,26 @@ pub struct TurnState {
     pub time: f32,
 }
 
+/// The controller playing each team.
 #[derive(Resource)]
-pub struct GameAI(pub Box<dyn AiController>);
+pub struct GameAI(pub HashMap<Team, Box<dyn AiController>>);
+
+/// World-level state the turn system reads and refreshes every turn.
+#[derive(SystemParam)]
+pub struct TurnWorld<'w> {
+    pub terrain: Res<'w, TerrainMap>,
+    pub visibility: ResMut<'w, TeamVisibility>,
+    pub spatial_index: ResMut<'w, SpatialIndex>,
+    pub resources: ResMut<'w, ResourceField>,
+    pub stockpiles: ResMut<'w, TeamStockpiles>,
+    pub spawn_requests: EventWriter<'w, SpawnRequest>,
+    pub capture_points: ResMut<'w, CapturePoints>,
+    pub scores: ResMut<'w, MatchScores>,
+    pub roster: Res<'w, TeamRoster>,
+    pub comms: ResMut<'w, CommChannel>,
+    pub debug: ResMut<'w, AiDebug>,
+    pub match_events: EventWriter<'w, MatchEvent>,
+}
 
 pub struct TurnManagerPlugin;
 
@@ -23,19 +52,22 @@ impl Plugin for TurnManagerPlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(TurnState { turn: 0, time: 0.0 })
+            .init_resource::<TeamVisibility>()
             .add_systems(Update, game_turn_system.run_if(not(crate::game::victory::game_over)));
     }
 }
 
-fn game_turn_system(
+pub(crate) fn game_turn_system(
     time: Res<Time>,
     mut queries: ParamSet<(
-        Query<(Entity, &mut Unit, &mut HexPosition), Without<Dead>>,
+        Query<(Entity, &mut Unit, &mut HexPosition, &mut LastAction, &UnitId), Without<Dead>>,
         Query<(Entity, &Unit, &HexPosition), Without<Dead>>,
+        Query<(&Unit, &HexPosition, Option<&Vision>), Without<Dead>>,
+        Query<(Entity, &mut Structure, &HexPosition, &UnitId), Without<Dead>>,
     )>,
     mut turn_state: ResMut<TurnState>,
     mut last_turn: Local<f32>,
-    mut turn: Local<u32>,
+    mut control: ResMut<SimControl>,
     mut combat_events: EventWriter<CombatEvent>,
     mut ai: ResMut<GameAI>,
     config: Res<GameConfig>,
@@ -43,27 +75,37 @@ fn game_turn_system(
     mut metrics: ResMut<PerformanceMetrics>,
     mut profiler: Option<ResMut<crate::performance::profiler::Profiler>>,
     mut replay_recorder: Option<ResMut<ReplayRecorder>>,
+    mut turn_world: TurnWorld,
 ) {
     // Profile turn system
     if let Some(ref mut prof) = profiler {
         prof.start("turn_system");
     }
     
-    // Check if it's time for next turn
+    // Check if it's time for next turn; a single step while paused runs straight away
     let now = time.elapsed_seconds();
     let tick_rate = if sim_config.modes.default == SimulationMode::Headless {
-        1.0 / sim_config.headless.timestep_hz as f32
+        // Unpaced, every frame is a turn and the sim runs as fast as it can
+        if sim_config.headless.fixed_timestep {
+            1.0 / sim_config.headless.timestep_hz as f32
+        } else {
+            0.0
+        }
     } else {
-        config.game.tick_rate
+        config.game.tick_rate / control.speed()
     };
     
-    if now - *last_turn < tick_rate {
+    if control.paused {
+        if !control.take_step() {
+            return;
+        }
+    } else if now - *last_turn < tick_rate {
         return;
     }
     
     *last_turn = now;
-    *turn += 1;
-    turn_state.turn = *turn;
+    turn_state.turn += 1;
+    let turn = turn_state.turn;
     turn_state.time = now;
     metrics.record_tick();
     
@@ -72,16 +114,57 @@ fn game_turn_system(
     
     if should_log {
         println!("\n----------------------------------------");
-        println!("[TURN] TURN {} - Time: {:.1}s", *turn, now);
+        println!("[TURN] TURN {} - Time: {:.1}s", turn, now);
     }
     
-    // Create world snapshot for AI
-    let world_snapshot = {
-        let units_query = queries.p1();
-        WorldSnapshot {
-            units: units_query.iter().map(|(e, u, p)| (e, u.team, p.coord)).collect(),
-        }
-    };
+    // Vision Phase - work out what each team can see this turn
+    let all_units: Vec<(Entity, Team, HexCoord)> = queries.p1()
+        .iter()
+        .map(|(e, u, p)| (e, u.team, p.coord))
+        .collect();
+    let unit_types: HashMap<Entity, UnitType> = queries.p1()
+        .iter()
+        .map(|(e, u, _)| (e, u.unit_type))
+        .collect();
+    let structures: Vec<StructureInfo> = queries.p3()
+        .iter()
+        .map(|(_, s, p, _)| StructureInfo {
+            team: s.team,
+            kind: s.kind,
+            coord: p.coord,
+        })
+        .collect();
+    let visibility = &mut turn_world.visibility;
+    visibility.enabled = config.vision.fog_of_war;
+    if visibility.enabled {
+        // Structures see as far as a unit does
+        let viewers: Vec<_> = queries.p2()
+            .iter()
+            .map(|(u, p, v)| (u.team, p.coord, v.map_or(config.vision.unit_range, |v| v.range)))
+            .chain(structures.iter().map(|s| (s.team, s.coord, config.vision.unit_range)))
+            .collect();
+        visibility.update(turn, &turn_world.terrain, &viewers, &all_units);
+    }
+    
+    // Create each team's world snapshot for AI, including last turn's messages
+    let mut world_snapshots: HashMap<Team, WorldSnapshot> = HashMap::new();
+    for team in turn_world.roster.teams() {
+        let agents = all_units.iter().filter(|(_, t, _)| *t == team).count();
+        turn_world.comms.begin_turn(team, agents);
+        world_snapshots.insert(team, WorldSnapshot::for_team(
+            team,
+            &all_units,
+            &unit_types,
+            &turn_world.visibility,
+            &turn_world.resources,
+            turn_world.stockpiles.get(team),
+            &structures,
+        )
+        .with_capture_points(&turn_world.capture_points.points)
+        .with_allies(turn_world.roster.side_members(team))
+        .with_messages(turn_world.comms.inbox(team))
+        .with_build_costs(&config.production));
+    }
     
     // Movement Phase - AI Controlled
     if should_log {
@@ -90,65 +173,262 @@ fn game_turn_system(
     
     // Collect current positions and AI decisions
     let (mut occupied, ai_decisions) = {
-        let units_query = queries.p1();
-        let mut occupied: HashSet<(i32, i32)> = HashSet::new();
+        // Structures block their hex for the whole turn
+        let mut occupied: HashSet<(i32, i32)> = structures.iter()
+            .map(|s| (s.coord.q, s.coord.r))
+            .collect();
         let mut ai_decisions = HashMap::new();
+        turn_world.debug.begin_turn();
+        
+        // One pathfinder per team, only aware of the units and structures that team can see
+        let mut pathfinders: HashMap<Team, Pathfinder> = world_snapshots.iter()
+            .map(|(team, snapshot)| {
+                let seen: HashSet<HexCoord> = snapshot.units.iter().map(|(_, _, c)| *c)
+                    .chain(snapshot.structures.iter().map(|s| s.coord))
+                    .collect();
+                (*team, Pathfinder::new(&turn_world.terrain, seen))
+            })
+            .collect();
         
+        let units_query = queries.p1();
         for (entity, unit, pos) in &units_query {
             occupied.insert((pos.coord.q, pos.coord.r));
-            let decision = ai.0.decide(entity, unit, &world_snapshot);
-            ai_decisions.insert(entity, decision);
+            let mut ctx = DecisionContext {
+                world: &world_snapshots[&unit.team],
+                pathfinder: pathfinders.get_mut(&unit.team).unwrap(),
+                actor: entity,
+                debug: &mut turn_world.debug,
+            };
+            if let Some(controller) = ai.0.get_mut(&unit.team) {
+                ai_decisions.insert(entity, controller.decide(entity, unit, &mut ctx));
+                
+                // Communication Phase - only teammates can be addressed
+                for message in controller.communicate(entity, unit, &mut ctx) {
+                    let addressable = message.to.is_none_or(|to| {
+                        all_units.iter().any(|(e, t, _)| *e == to && *t == unit.team)
+                    });
+                    if addressable {
+                        turn_world.comms.send(unit.team, entity, message, &config.communication);
+                    }
+                }
+            }
+        }
+        
+        let structures_query = queries.p3();
+        for (entity, structure, _, _) in &structures_query {
+            if !structure.is_complete() || structure.production.is_some() {
+                continue;
+            }
+            let mut ctx = DecisionContext {
+                world: &world_snapshots[&structure.team],
+                pathfinder: pathfinders.get_mut(&structure.team).unwrap(),
+                actor: entity,
+                debug: &mut turn_world.debug,
+            };
+            if let Some(controller) = ai.0.get_mut(&structure.team) {
+                ai_decisions.insert(entity, controller.decide_structure(entity, structure, &mut ctx));
+            }
         }
         
         (occupied, ai_decisions)
     };
     
-    // Apply movements
+    if let Some(ref mut recorder) = replay_recorder {
+        let ids: HashMap<Entity, UnitId> = queries.p0().iter()
+            .map(|(entity, _, _, _, id)| (entity, *id))
+            .collect();
+        for team in turn_world.roster.teams() {
+            for message in turn_world.comms.outbox(team) {
+                recorder.record_message(team, message, &ids);
+            }
+        }
+    }
+    if should_log && config.communication.enabled {
+        let traffic: Vec<String> = turn_world.comms.traffic()
+            .map(|(name, sent)| format!("{} {}", name, sent))
+            .collect();
+        println!("[COMMS] Messages sent: {}", traffic.join(", "));
+    }
+    
+    // Apply movements and gathering
     let mut moves = Vec::new();
+    let mut gathers = Vec::new();
+    let mut builds = Vec::new();
     {
         let mut units_mut = queries.p0();
-        for (entity, unit, mut pos) in &mut units_mut {
+        for (entity, unit, mut pos, mut last_action, unit_id) in &mut units_mut {
             let old_coord = pos.coord;
             
             if let Some(action) = ai_decisions.get(&entity) {
                 // Record action for replay
                 if let Some(ref mut recorder) = replay_recorder {
-                    recorder.record_action(entity, unit.team, *action);
+                    recorder.record_action(*unit_id, unit.team, *action);
                 }
                 
-                match action {
+                let result = match action {
                     Action::Move(dq, dr) => {
                         let new_q = old_coord.q + dq;
                         let new_r = old_coord.r + dr;
                         let new_coord = (new_q, new_r);
                         
-                        // Check bounds and collision
-                        if new_q.abs() < config.game.map_width/2 && 
-                           new_r.abs() < config.game.map_height/2 && 
+                        // Check bounds, terrain and collision
+                        if turn_world.terrain.is_passable(HexCoord::new(new_q, new_r)) && 
                            !occupied.contains(&new_coord) {
                             occupied.remove(&(old_coord.q, old_coord.r));
                             occupied.insert(new_coord);
                             pos.coord.q = new_q;
                             pos.coord.r = new_r;
                             moves.push((unit.team, old_coord, pos.coord));
+                            ActionResult::Moved
+                        } else {
+                            ActionResult::Blocked
                         }
                     }
-                    Action::Stay => {
-                        // Unit chose to stay
+                    Action::Gather => {
+                        // Only workers can harvest; anyone else just holds position
+                        let gathered = if unit.unit_type == UnitType::Worker {
+                            let TurnWorld { resources, stockpiles, .. } = &mut turn_world;
+                            resolve_gather(
+                                resources,
+                                stockpiles,
+                                unit.team,
+                                old_coord,
+                                config.economy.gather_rate,
+                            )
+                        } else {
+                            None
+                        };
+                        match gathered {
+                            Some((kind, amount)) => {
+                                gathers.push((unit.team, old_coord, kind, amount));
+                                ActionResult::Gathered(kind, amount)
+                            }
+                            None => ActionResult::NothingToGather,
+                        }
+                    }
+                    Action::Build(kind, at) => {
+                        // Workers build on a free hex next to them, paying up front
+                        let cost = config.production.structure_cost(*kind);
+                        if config.production.enabled
+                            && unit.unit_type == UnitType::Worker
+                            && hex_distance(old_coord, *at) == 1
+                            && can_build_at(&turn_world.terrain, &turn_world.resources, &occupied, *at)
+                            && turn_world.stockpiles.0.entry(unit.team)
+                                .or_insert_with(Resource::new)
+                                .spend(&cost.resources())
+                        {
+                            occupied.insert((at.q, at.r));
+                            turn_world.spawn_requests.send(SpawnRequest::Structure {
+                                team: unit.team,
+                                kind: *kind,
+                                coord: *at,
+                                build_turns: cost.build_turns,
+                            });
+                            builds.push((unit.team, *kind, *at));
+                            ActionResult::BuildStarted
+                        } else {
+                            ActionResult::BuildRejected
+                        }
                     }
+                    Action::Stay => ActionResult::Held,
+                    // Only structures produce
+                    Action::Produce(_) => ActionResult::Ignored,
+                };
+                
+                *last_action = LastAction { turn, action: *action, result };
+                if let Some(event) = action_event(unit_id.0, unit.team, old_coord, action, result) {
+                    turn_world.match_events.send(MatchEvent::new(turn, event));
                 }
             }
         }
     }
     
+    // Production Phase - construction, training, and new orders
+    let mut trained = Vec::new();
+    {
+        let mut structures_mut = queries.p3();
+        for (entity, mut structure, pos, structure_id) in &mut structures_mut {
+            if let Some(unit_type) = advance_production(&mut structure) {
+                // A finished unit waits inside until a neighbouring hex is free
+                if let Some(coord) = free_neighbor(&turn_world.terrain, &occupied, pos.coord) {
+                    occupied.insert((coord.q, coord.r));
+                    turn_world.spawn_requests.send(SpawnRequest::Unit {
+                        team: structure.team,
+                        unit_type,
+                        coord,
+                    });
+                    structure.production = None;
+                    trained.push((structure.team, unit_type, coord));
+                }
+            }
+            
+            if let Some(action) = ai_decisions.get(&entity) {
+                if let Some(ref mut recorder) = replay_recorder {
+                    recorder.record_action(*structure_id, structure.team, *action);
+                }
+                
+                if let Action::Produce(unit_type) = action {
+                    let stockpile = turn_world.stockpiles.0.entry(structure.team).or_insert_with(Resource::new);
+                    if try_produce(&mut structure, *unit_type, stockpile, &config.production) {
+                        turn_world.match_events.send(MatchEvent::new(turn, MatchEventKind::ProductionStarted {
+                            team: structure.team,
+                            at: pos.coord,
+                            unit_type: *unit_type,
+                        }));
+                        if should_log {
+                            println!("   {} {:?} started training a {:?}", 
+                                turn_world.roster.tag(structure.team), structure.kind, unit_type);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    // Objective Phase - capture points change hands and owners score
+    let captures = {
+        let positions: Vec<(Team, HexCoord)> = queries.p1().iter().map(|(_, u, p)| (u.team, p.coord)).collect();
+        let TurnWorld { capture_points, scores, match_events, .. } = &mut turn_world;
+        let captures = capture_points.update(&positions, config.objectives.capture_turns);
+        for (team, at) in &captures {
+            match_events.send(MatchEvent::new(turn, MatchEventKind::Captured { team: *team, at: *at }));
+        }
+        for point in &capture_points.points {
+            if let Some(owner) = point.owner {
+                scores.add_points(owner, config.objectives.points_per_turn);
+            }
+        }
+        captures
+    };
+    
     // Log movements
     if should_log {
+        let roster = &turn_world.roster;
         for (team, old, new) in moves {
             println!("   {} Unit: ({}, {}) -> ({}, {})", 
-                team.tag(), old.q, old.r, new.q, new.r);
+                roster.tag(team), old.q, old.r, new.q, new.r);
+        }
+        for (team, at, kind, amount) in &gathers {
+            println!("   {} Worker at ({}, {}) gathered {} {:?}", 
+                roster.tag(*team), at.q, at.r, amount, kind);
+        }
+        for (team, kind, at) in &builds {
+            println!("   {} Worker started a {:?} at ({}, {})", 
+                roster.tag(*team), kind, at.q, at.r);
+        }
+        for (team, at) in &captures {
+            println!("   {} captured the point at ({}, {})", 
+                roster.tag(*team), at.q, at.r);
+        }
+        for (team, unit_type, at) in &trained {
+            println!("   {} {:?} ready at ({}, {})", 
+                roster.tag(*team), unit_type, at.q, at.r);
         }
     }
     
+    // Nodes regrow a little every turn
+    turn_world.resources.regenerate(config.economy.regen_per_turn);
+    
     // Combat Phase
     if should_log {
         println!("\n[COMBAT] Combat Phase:");
@@ -156,14 +436,36 @@ fn game_turn_system(
     
     let combat_pairs = {
         let units_query = queries.p1();
-        check_combat(&units_query)
+        turn_world.spatial_index.rebuild(units_query.iter().map(|(e, u, p)| (e, u.team, p.coord)));
+        check_combat(&turn_world.spatial_index, &turn_world.roster, config.combat.attack_range)
     };
+    let structure_attacks = {
+        let targets: Vec<_> = queries.p3().iter().map(|(e, s, p, _)| (e, s.team, p.coord)).collect();
+        check_structure_attacks(&turn_world.spatial_index, &turn_world.roster, &targets, config.combat.attack_range)
+    };
+    
+    for (attacker, structure) in &structure_attacks {
+        combat_events.send(CombatEvent {
+            attacker: *attacker,
+            defender: *structure,
+            damage: config.combat.base_damage,
+        });
+    }
+    if should_log {
+        let structures_query = queries.p3();
+        for (_, structure) in &structure_attacks {
+            if let Ok((_, s, p, _)) = structures_query.get(*structure) {
+                println!("   {} {:?} at ({}, {}) under attack",
+                    turn_world.roster.tag(s.team), s.kind, p.coord.q, p.coord.r);
+            }
+        }
+    }
     
-    if combat_pairs.is_empty() {
+    if combat_pairs.is_empty() && structure_attacks.is_empty() {
         if should_log {
             println!("   No combat this turn");
         }
-    } else {
+    } else if !combat_pairs.is_empty() {
         // Log combat and send events
         {
             let units_query = queries.p1();
@@ -184,8 +486,8 @@ fn game_turn_system(
                 if should_log {
                     if let Ok([(_, u1, p1), (_, u2, p2)]) = units_query.get_many([*e1, *e2]) {
                         println!("   {} unit at ({}, {}) fights {} unit at ({}, {})",
-                            u1.team.tag(), p1.coord.q, p1.coord.r,
-                            u2.team.tag(), p2.coord.q, p2.coord.r);
+                            turn_world.roster.tag(u1.team), p1.coord.q, p1.coord.r,
+                            turn_world.roster.tag(u2.team), p2.coord.q, p2.coord.r);
                     }
                 }
             }
@@ -193,27 +495,28 @@ fn game_turn_system(
     }
     
     // Status report every 5 turns (only in visual mode)
-    if should_log && *turn % 5 == 0 {
+    if should_log && turn % 5 == 0 {
         println!("\n[STATUS] Status Report:");
         let units_query = queries.p1();
-        let (reds, blues): (Vec<_>, Vec<_>) = units_query.iter()
-            .map(|(_, u, p)| (u.team, p.coord, u.health))
-            .partition(|(t, _, _)| *t == Team::Red);
-        
-        println!("   [RED]  {} units", reds.len());
-        for (i, (_, c, h)) in reds.iter().enumerate() {
-            println!("      {} ({}, {}) {:.0} HP", i + 1, c.q, c.r, h);
-        }
-        
-        println!("   [BLUE] {} units", blues.len());
-        for (i, (_, c, h)) in blues.iter().enumerate() {
-            println!("      {} ({}, {}) {:.0} HP", i + 1, c.q, c.r, h);
+        for team in turn_world.roster.teams() {
+            let members: Vec<_> = units_query.iter()
+                .filter(|(_, u, _)| u.team == team)
+                .map(|(_, u, p)| (p.coord, u.health))
+                .collect();
+            
+            println!("   {} {} units", turn_world.roster.tag(team), members.len());
+            for (i, (c, h)) in members.iter().enumerate() {
+                println!("      {} ({}, {}) {:.0} HP", i + 1, c.q, c.r, h);
+            }
         }
     }
     
+    // Messages sent this turn reach their teams next turn
+    turn_world.comms.deliver();
+    
     // Record end of turn for replay
     if let Some(ref mut recorder) = replay_recorder {
-        recorder.end_turn(*turn, 0); // TODO: Add proper RNG seed
+        recorder.end_turn(turn, 0); // TODO: Add proper RNG seed
     }
     
     // End profiling
@@ -223,3 +526,22 @@ fn game_turn_system(
 }
 
 
+
+/// The log entry for a unit's action, if it did anything worth one.
+fn action_event(unit: u32, team: Team, from: HexCoord, action: &Action, result: ActionResult) -> Option<MatchEventKind> {
+    match (*action, result) {
+        (Action::Move(dq, dr), ActionResult::Moved) => {
+            Some(MatchEventKind::Moved { unit, from, to: from.offset(dq, dr) })
+        }
+        (Action::Move(dq, dr), ActionResult::Blocked) => {
+            Some(MatchEventKind::Blocked { unit, at: from, toward: from.offset(dq, dr) })
+        }
+        (Action::Gather, ActionResult::Gathered(kind, amount)) => {
+            Some(MatchEventKind::Gathered { unit, kind, amount })
+        }
+        (Action::Build(kind, at), ActionResult::BuildStarted) => {
+            Some(MatchEventKind::BuildStarted { unit, team, kind, at })
+        }
+        _ => None,
+    }
+}
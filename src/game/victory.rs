@@ -1,64 +1,346 @@
 ﻿use bevy::prelude::*;
-use crate::units::{Unit, Dead, Team};
+use bevy::ecs::system::SystemParam;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::units::{Unit, Dead, Team, TeamRoster, Structure, StructureKind, UnitType};
+use crate::world::resource::Resource;
+use crate::config::GameConfig;
+use crate::game::events::{MatchEvent, MatchEventKind};
+use crate::config::game_config::{ProductionSettings, Tiebreak, VictoryRule};
 
-#[derive(Resource)]
-pub struct GameOver(pub bool);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WinReason {
+    Elimination,
+    Timeout,
+    Objective,
+    Forfeit,
+    /// Reached `points_to_win` first.
+    Points,
+}
+
+/// Where one team stood when the match ended.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamScore {
+    pub team: Team,
+    pub units: u32,
+    pub total_health: f32,
+    #[serde(default)]
+    pub damage_dealt: f32,
+    pub kills: u32,
+    pub points: u32,
+}
+
+/// How the match ended. Inserted as a resource and sent as an event exactly
+/// once; everything that needs the winner reads it from here.
+#[derive(Resource, Event, Clone, Debug, Serialize, Deserialize)]
+pub struct MatchResult {
+    /// The winning team and any allies. Empty for a draw.
+    #[serde(default)]
+    pub winners: Vec<Team>,
+    pub reason: WinReason,
+    pub turn: u32,
+    pub scores: Vec<TeamScore>,
+}
+
+impl MatchResult {
+    pub fn is_draw(&self) -> bool {
+        self.winners.is_empty()
+    }
+
+    pub fn headline(&self, roster: &TeamRoster) -> String {
+        match self.winners.as_slice() {
+            [] => "DRAW!".to_string(),
+            [team] => format!("{} TEAM WINS!", roster.name(*team)).to_uppercase(),
+            teams => {
+                let names: Vec<&str> = teams.iter().map(|t| roster.name(*t)).collect();
+                format!("{} WIN!", names.join(" & ")).to_uppercase()
+            }
+        }
+    }
+
+    pub fn summary(&self, roster: &TeamRoster) -> String {
+        let scores: Vec<String> = self.scores.iter()
+            .map(|s| format!("{} {} units, {:.0} HP, {:.0} dmg, {} pts", roster.tag(s.team), s.units, s.total_health, s.damage_dealt, s.points))
+            .collect();
+        format!("{} ({:?}, turn {}) {}", self.headline(roster), self.reason, self.turn, scores.join(" | "))
+    }
+}
+
+/// Running tallies kept during the match, for points victories and final scores.
+#[derive(Resource, Default)]
+pub struct MatchScores {
+    kills: HashMap<Team, u32>,
+    points: HashMap<Team, u32>,
+    damage: HashMap<Team, f32>,
+}
+
+impl MatchScores {
+    pub fn add_points(&mut self, team: Team, points: u32) {
+        *self.points.entry(team).or_default() += points;
+    }
+
+    pub fn record_damage(&mut self, team: Team, amount: f32) {
+        *self.damage.entry(team).or_default() += amount;
+    }
+
+    pub fn damage(&self, team: Team) -> f32 {
+        self.damage.get(&team).copied().unwrap_or(0.0)
+    }
+
+    pub fn record_kill(&mut self, team: Team, points: u32) {
+        *self.kills.entry(team).or_default() += 1;
+        *self.points.entry(team).or_default() += points;
+    }
+
+    pub fn kills(&self, team: Team) -> u32 {
+        self.kills.get(&team).copied().unwrap_or(0)
+    }
+
+    pub fn points(&self, team: Team) -> u32 {
+        self.points.get(&team).copied().unwrap_or(0)
+    }
+}
+
+/// What each team has banked, beyond what is on the map.
+#[derive(SystemParam)]
+pub struct Standings<'w> {
+    pub scores: Res<'w, MatchScores>,
+    pub stockpiles: Res<'w, crate::game::TeamStockpiles>,
+    pub roster: Res<'w, TeamRoster>,
+}
+
+/// End the match now and decide it on the turn-limit tiebreaks, as if
+/// `max_turns` had been reached. Batch runs send this to games that run too long.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct AdjudicateMatch;
 
 pub struct VictoryPlugin;
 
 impl Plugin for VictoryPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(GameOver(false))
-            .add_systems(Update, check_victory);
+            .init_resource::<MatchScores>()
+            .add_event::<MatchResult>()
+            .add_event::<AdjudicateMatch>()
+            .add_systems(Update, check_victory.run_if(not(game_over)));
     }
 }
 
 pub fn check_victory(
+    mut commands: Commands,
     turn_state: Res<crate::game::TurnState>,
-    config: Res<crate::config::GameConfig>,
+    config: Res<GameConfig>,
     units: Query<&Unit, Without<Dead>>,
-    mut game_over: ResMut<GameOver>,
+    structures: Query<&Structure, Without<Dead>>,
+    standings: Standings,
+    (mut results, mut match_events, mut adjudicate): (EventWriter<MatchResult>, EventWriter<MatchEvent>, EventReader<AdjudicateMatch>),
 ) {
-    if game_over.0 {
-        return;
-    }
-    
-    // Check turn limit
-    if turn_state.turn >= config.game.max_turns {
-        // println!("\n[TIMEOUT] Game ended - turn limit reached!");
-        game_over.0 = true;
-        return;
-    }
-    
-    let (mut red_count, mut blue_count) = (0, 0);
-    
-    for unit in &units {
-        match unit.team {
-            Team::Red => red_count += 1,
-            Team::Blue => blue_count += 1,
+    let victory = &config.victory;
+    let roster = &standings.roster;
+    let team_scores: Vec<TeamScore> = roster.teams()
+        .map(|team| {
+            let team_units: Vec<_> = units.iter().filter(|u| u.team == team).collect();
+            TeamScore {
+                team,
+                units: team_units.len() as u32,
+                total_health: team_units.iter().fold(0.0, |hp, u| hp + u.health.max(0.0)),
+                damage_dealt: standings.scores.damage(team),
+                kills: standings.scores.kills(team),
+                points: standings.scores.points(team),
+            }
+        })
+        .collect();
+
+    // Work out who is out of the match, and why
+    let bases_in_play = config.production.enabled && config.production.start_with_base;
+    let mut out: Vec<(Team, WinReason)> = Vec::new();
+    for score in &team_scores {
+        let team_structures: Vec<_> = structures.iter().filter(|s| s.team == score.team).collect();
+
+        if victory.has(VictoryRule::Objective) && bases_in_play
+            && !team_structures.iter().any(|s| s.kind == StructureKind::Base)
+        {
+            out.push((score.team, WinReason::Objective));
+        } else if victory.has(VictoryRule::Elimination) && score.units == 0 && team_structures.is_empty() {
+            out.push((score.team, WinReason::Elimination));
+        } else if score.units == 0
+            && !team_structures.is_empty()
+            && !can_rebuild(&team_structures, &standings.stockpiles.get(score.team), &config.production)
+        {
+            // Nothing left to fight with and no way to get more
+            out.push((score.team, WinReason::Forfeit));
         }
     }
-    
-    match (red_count, blue_count) {
-        (0, 0) => {
-            // println!("\n[DRAW] DRAW! Both teams eliminated!");
-            game_over.0 = true;
+
+    // Allies win and lose together, so everything from here is judged per side
+    let side_scores = side_totals(roster, &team_scores);
+    let mut decided = None;
+
+    if !out.is_empty() {
+        let alive: Vec<Team> = roster.teams()
+            .filter(|t| !out.iter().any(|(o, _)| o == t))
+            .collect();
+        let sides_left = side_scores.iter()
+            .filter(|s| alive.iter().any(|t| roster.allied(*t, s.team)))
+            .count();
+        if sides_left <= 1 {
+            let reason = [WinReason::Objective, WinReason::Elimination, WinReason::Forfeit]
+                .into_iter()
+                .find(|r| out.iter().any(|(_, o)| o == r))
+                .unwrap();
+            decided = Some((alive.first().copied(), reason));
         }
-        (0, _) => {
-            // println!("\n[BLUE] BLUE TEAM WINS!");
-            game_over.0 = true;
+    }
+
+    if decided.is_none() && victory.has(VictoryRule::Points) {
+        let best = side_scores.iter().map(|s| s.points).max().unwrap_or(0);
+        if best >= victory.points_to_win {
+            decided = Some((sole_leader(&side_scores, |s| s.points as f32), WinReason::Points));
         }
-        (_, 0) => {
-            // println!("\n[RED]  RED TEAM WINS!");
-            game_over.0 = true;
+    }
+
+    let out_of_time = adjudicate.read().count() > 0 || turn_state.turn >= config.game.max_turns;
+    if decided.is_none() && out_of_time {
+        decided = Some((adjudicate_timeout(&side_scores, &victory.timeout_tiebreaks), WinReason::Timeout));
+    }
+
+    if let Some((winner, reason)) = decided {
+        let result = MatchResult {
+            winners: winner.map(|team| roster.side_members(team)).unwrap_or_default(),
+            reason,
+            turn: turn_state.turn,
+            scores: team_scores,
+        };
+        match_events.send(MatchEvent::new(result.turn, MatchEventKind::MatchEnded {
+            winners: result.winners.clone(),
+            reason,
+        }));
+        results.send(result.clone());
+        commands.insert_resource(result);
+    }
+}
+
+/// Scores summed over each side, keyed by the side's first team. In
+/// free-for-all every team is its own side and this is `scores` unchanged.
+fn side_totals(roster: &TeamRoster, scores: &[TeamScore]) -> Vec<TeamScore> {
+    let mut sides: Vec<TeamScore> = Vec::new();
+    for score in scores {
+        match sides.iter_mut().find(|s| roster.allied(s.team, score.team)) {
+            Some(side) => {
+                side.units += score.units;
+                side.total_health += score.total_health;
+                side.damage_dealt += score.damage_dealt;
+                side.kills += score.kills;
+                side.points += score.points;
+            }
+            None => sides.push(score.clone()),
         }
-        _ => {}
     }
+    sides
+}
+
+/// The team strictly ahead on `key`, or `None` when the lead is shared.
+fn sole_leader(scores: &[TeamScore], key: impl Fn(&TeamScore) -> f32) -> Option<Team> {
+    let best = scores.iter().map(&key).fold(f32::MIN, f32::max);
+    let mut leaders = scores.iter().filter(|s| key(s) == best);
+    match (leaders.next(), leaders.next()) {
+        (Some(leader), None) => Some(leader.team),
+        _ => None,
+    }
+}
+
+/// Walk the tiebreaks in order and return the first clear leader.
+fn adjudicate_timeout(scores: &[TeamScore], tiebreaks: &[Tiebreak]) -> Option<Team> {
+    tiebreaks.iter().find_map(|tiebreak| match tiebreak {
+        Tiebreak::Units => sole_leader(scores, |s| s.units as f32),
+        Tiebreak::Health => sole_leader(scores, |s| s.total_health),
+        Tiebreak::Damage => sole_leader(scores, |s| s.damage_dealt),
+        Tiebreak::Points => sole_leader(scores, |s| s.points as f32),
+    })
 }
 
-pub fn game_over(game_over: Res<GameOver>) -> bool {
-    game_over.0
+/// Whether a team with no units can still get some: something in training,
+/// or a finished structure that can afford to start.
+fn can_rebuild(
+    structures: &[&Structure],
+    stockpile: &Resource,
+    settings: &ProductionSettings,
+) -> bool {
+    structures.iter().any(|s| {
+        !s.is_complete()
+            || s.production.is_some()
+            || UnitType::ALL.into_iter().any(|t| s.kind.can_produce(t) && stockpile.can_afford(&settings.unit_cost(t).resources()))
+    })
 }
 
+pub fn game_over(result: Option<Res<MatchResult>>) -> bool {
+    result.is_some()
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::game_config::VictoryMode;
+
+    fn score(team: u8, units: u32, points: u32) -> TeamScore {
+        TeamScore { team: Team(team), units, total_health: units as f32 * 10.0, damage_dealt: 5.0, kills: 1, points }
+    }
+
+    fn roster(mode: VictoryMode) -> TeamRoster {
+        let mut config = GameConfig::default();
+        config.victory.mode = mode;
+        let team = config.teams[0].clone();
+        config.teams = [Some(7), None, Some(7)]
+            .into_iter()
+            .map(|alliance| crate::config::game_config::TeamSettings { alliance, ..team.clone() })
+            .collect();
+        TeamRoster::from_config(&config)
+    }
+
+    #[test]
+    fn side_totals_add_up_allies() {
+        let scores = [score(0, 2, 10), score(1, 4, 3), score(2, 1, 6)];
+        let sides = side_totals(&roster(VictoryMode::Alliances), &scores);
+        assert_eq!(sides.len(), 2);
+        assert_eq!((sides[0].team, sides[0].units, sides[0].points, sides[0].kills), (Team(0), 3, 16, 2));
+        assert_eq!(sides[0].total_health, 30.0);
+        assert_eq!(sides[0].damage_dealt, 10.0);
+        assert_eq!((sides[1].team, sides[1].units, sides[1].points), (Team(1), 4, 3));
+    }
+
+    #[test]
+    fn side_totals_leave_free_for_all_scores_alone() {
+        let scores = [score(0, 2, 10), score(1, 4, 3), score(2, 1, 6)];
+        let sides = side_totals(&roster(VictoryMode::FreeForAll), &scores);
+        let teams: Vec<_> = sides.iter().map(|s| (s.team, s.units, s.points)).collect();
+        assert_eq!(teams, vec![(Team(0), 2, 10), (Team(1), 4, 3), (Team(2), 1, 6)]);
+    }
+
+    fn structure(kind: StructureKind, build_turns_left: u32) -> Structure {
+        Structure { team: Team(0), kind, health: 100.0, max_health: 100.0, build_turns_left, production: None }
+    }
+
+    #[test]
+    fn a_team_can_rebuild_from_what_it_has_left() {
+        let settings = ProductionSettings::default();
+        let broke = Resource { energy: 0, metal: 0, knowledge: 0 };
+        let worker_money = Resource { energy: settings.worker.energy, metal: 0, knowledge: 0 };
+
+        let base = structure(StructureKind::Base, 0);
+        assert!(can_rebuild(&[&base], &worker_money, &settings));
+        assert!(!can_rebuild(&[&base], &broke, &settings));
+        assert!(!can_rebuild(&[], &worker_money, &settings));
+
+        // A barracks can't train workers, whatever the team can afford
+        let barracks = structure(StructureKind::Barracks, 0);
+        assert!(!can_rebuild(&[&barracks], &worker_money, &settings));
+
+        // Something still under construction or in training counts
+        let building = structure(StructureKind::Barracks, 5);
+        assert!(can_rebuild(&[&building], &broke, &settings));
+        let mut training = structure(StructureKind::Barracks, 0);
+        training.production = Some(crate::units::ProductionOrder { unit_type: UnitType::Fighter, turns_left: 3 });
+        assert!(can_rebuild(&[&barracks, &training], &broke, &settings));
+    }
+}
@@ -11,31 +11,72 @@ mod units;
 mod ui;
 mod config;
 mod performance;
+mod cli;
 
 use world::hex_grid::HexGridPlugin;
+use world::SpatialIndexPlugin;
 use units::{SpawningPlugin, MovementPlugin, HealthPlugin};
-use game::{TurnManagerPlugin, CombatPlugin, VictoryPlugin};
-use ui::{HudPlugin, OverlayPlugin};
+use game::{MatchEventsPlugin, TurnManagerPlugin, CombatPlugin, VictoryPlugin, EconomyPlugin, ProductionPlugin, ObjectivesPlugin, SessionPlugin, SimControl, ResetMatch};
+use ui::{HudPlugin, OverlayPlugin, FogOverlayPlugin, CommOverlayPlugin, CameraControlPlugin, SelectionPlugin, InspectorPlugin, IntentOverlayPlugin};
 use ai::AiIntegrationPlugin;
-use config::{ConfigPlugin, SimulationConfig, SimulationMode, GameConfig};
+use config::{ConfigPlugin, SimulationMode, GameConfig};
 use performance::{MetricsPlugin, ProfilerPlugin};
-use engine::{HeadlessPlugin, StateSerializationPlugin, ReplayPlugin};
+use engine::{HeadlessPlugin, StateSerializationPlugin, MatchLogPlugin, ReplayPlugin};
+use cli::{Cli, Command};
 
 fn main() {
-    // Check for batch mode
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 && args[1] == "batch" {
-        let num_games = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(100);
-        let parallel = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(4);
-        engine::run_batch_games(num_games, parallel);
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.command == Command::Help {
+        println!("{}", cli::USAGE);
         return;
     }
+    let (config, sim_config) = match cli.load_configs() {
+        Ok(configs) => configs,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    
+    match &cli.command {
+        Command::Batch => {
+            engine::run_batch_games(config, sim_config, cli.games, cli.parallel);
+            return;
+        }
+        Command::Tournament => {
+            engine::batch_runner::run_tournament(config, sim_config, &cli.controllers, cli.games, cli.parallel);
+            return;
+        }
+        Command::Convert(input, output) => {
+            engine::replay_format::convert(input, output);
+            return;
+        }
+        Command::Replay(path) => {
+            ui::replay_viewer::run_replay_viewer(path, config);
+            return;
+        }
+        Command::Verify(path) => {
+            if !engine::replay::verify(path, &config) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Command::Run | Command::Play | Command::Headless | Command::Help => {}
+    }
     
-    // Load simulation config to determine mode
-    let sim_config = SimulationConfig::load().unwrap_or_default();
     let is_headless = sim_config.modes.default == SimulationMode::Headless;
     
     let mut app = App::new();
+    // Go in before the plugins, which read them rather than the files
+    app
+        .insert_resource(config)
+        .insert_resource(sim_config.clone());
     
     // Configure plugins based on mode
     if is_headless {
@@ -71,14 +112,22 @@ fn main() {
         ConfigPlugin,
         
         // Core game
-        HexGridPlugin,
-        SpawningPlugin,
-        MovementPlugin,
-        HealthPlugin,
-        TurnManagerPlugin,
-        CombatPlugin,
-        VictoryPlugin,
-        AiIntegrationPlugin,
+        (
+            HexGridPlugin,
+            SpatialIndexPlugin,
+            MatchEventsPlugin,
+            SpawningPlugin,
+            MovementPlugin,
+            HealthPlugin,
+            TurnManagerPlugin,
+            CombatPlugin,
+            VictoryPlugin,
+            EconomyPlugin,
+            ProductionPlugin,
+            ObjectivesPlugin,
+            SessionPlugin,
+            AiIntegrationPlugin,
+        ),
         
         // Performance monitoring
         MetricsPlugin,
@@ -86,6 +135,7 @@ fn main() {
         
         // State management
         StateSerializationPlugin,
+        MatchLogPlugin,
         ReplayPlugin,
     ));
     
@@ -94,14 +144,17 @@ fn main() {
         app.add_plugins((
             HudPlugin,
             OverlayPlugin,
+            FogOverlayPlugin,
+            CommOverlayPlugin,
+            CameraControlPlugin,
+            SelectionPlugin,
+            InspectorPlugin,
+            IntentOverlayPlugin,
         ))
         .add_systems(Startup, setup_visual)
         .add_systems(Update, handle_input);
     }
     
-    // Insert simulation config as resource
-    app.insert_resource(sim_config);
-    
     // Add exit system for headless mode
     if is_headless {
         app.add_systems(Update, check_headless_exit);
@@ -135,7 +188,7 @@ fn setup_visual(mut commands: Commands) {
     // Controls text
     commands.spawn(
         TextBundle::from_section(
-            "AI vs AI | SPACE: pause | Arrows: pan | R: reset | ESC: exit",
+            "AI vs AI | SPACE: pause | .: step | +/-: speed | Wheel: zoom | Drag/Arrows: pan | Click: select | T: follow | Home: fit | F: fog | C: comms | I: intents | R: reset | ESC: exit",
             TextStyle {
                 font_size: 24.0,
                 color: Color::rgb(0.7, 0.7, 0.7),
@@ -154,83 +207,73 @@ fn setup_visual(mut commands: Commands) {
 fn handle_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut exit: EventWriter<AppExit>,
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    time: Res<Time>,
+    mut control: ResMut<SimControl>,
+    mut reset: EventWriter<ResetMatch>,
 ) {
     if keyboard_input.just_pressed(KeyCode::Escape) {
         exit.send(AppExit);
     }
     
     if keyboard_input.just_pressed(KeyCode::Space) {
-        println!("Game paused/resumed!");
+        control.toggle_pause();
+        println!("Game {}", if control.paused { "paused" } else { "resumed" });
     }
     
-    if keyboard_input.just_pressed(KeyCode::KeyR) {
-        println!("Reset game!");
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        control.request_step();
     }
     
-    // Camera movement
-    if let Ok(mut camera_transform) = camera_query.get_single_mut() {
-        let speed = 300.0 * time.delta_seconds();
-        
-        if keyboard_input.pressed(KeyCode::ArrowLeft) {
-            camera_transform.translation.x -= speed;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowRight) {
-            camera_transform.translation.x += speed;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowUp) {
-            camera_transform.translation.y += speed;
-        }
-        if keyboard_input.pressed(KeyCode::ArrowDown) {
-            camera_transform.translation.y -= speed;
-        }
+    if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        control.faster();
+        println!("Speed: {}x", control.speed());
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        control.slower();
+        println!("Speed: {}x", control.speed());
+    }
+    
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        reset.send(ResetMatch);
     }
 }
 
 // Exit after game ends in headless mode
 fn check_headless_exit(
-    game_over: Res<crate::game::GameOver>,
+    result: Option<Res<crate::game::MatchResult>>,
     mut exit: EventWriter<AppExit>,
     mut metrics: ResMut<crate::performance::PerformanceMetrics>,
-    state_recorder: Res<crate::engine::StateRecorder>,
+    (state_recorder, match_log): (Res<crate::engine::StateRecorder>, Res<crate::engine::MatchLog>),
     replay_recorder: Res<crate::engine::ReplayRecorder>,
-    config: Res<GameConfig>,
+    (config, roster): (Res<GameConfig>, Res<crate::units::TeamRoster>),
     mut checked: Local<bool>,
 ) {
-    if game_over.0 && !*checked {
+    let Some(result) = result else {
+        return;
+    };
+    
+    if !*checked {
         *checked = true;
         println!("\nGame ended in headless mode.");
+        println!("Result: {}", result.summary(&roster));
         
         // Create data directory if it doesn't exist
-        std::fs::create_dir_all("data/states").ok();
-        std::fs::create_dir_all("data/replays").ok();
+        std::fs::create_dir_all(&config.replay.directory).ok();
         
-        // Save game state
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-        let state_filename = format!("game_state_{}.json", timestamp);
-        let state_path = std::path::Path::new("data").join("states").join(&state_filename);
-        
-        match state_recorder.save_to_file(&state_path) {
-            Ok(_) => println!("Game state saved to: {}", state_path.display()),
-            Err(e) => println!("Failed to save game state: {}", e),
+        // Game state was streamed while the match ran
+        if let Some(state_path) = state_recorder.path() {
+            println!("Game state saved to: {} ({} snapshots)", state_path.display(), state_recorder.snapshot_count());
+        }
+        if let Some(log_path) = match_log.path() {
+            println!("Match events saved to: {} ({} events)", log_path.display(), match_log.event_count());
         }
         
-        // Save replay
-        let replay_filename = format!("game_replay_{}.json", timestamp);
-        let replay_path = std::path::Path::new("data").join("replays").join(&replay_filename);
-        
-        let map_config = engine::replay::ReplayMapConfig {
-            width: config.game.map_width,
-            height: config.game.map_height,
-            units_per_team: config.game.units_per_team,
-        };
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
         
-        // Determine outcome
-        // TODO: Get actual outcome from game state
-        let outcome = Some(engine::replay::GameOutcome::Draw);
+        // Save replay
+        let replay_filename = format!("game_replay_{}.{}", timestamp, config.replay.format.extension());
+        let replay_path = std::path::Path::new(&config.replay.directory).join(&replay_filename);
         
-        match replay_recorder.save_replay(&replay_path, map_config, outcome) {
+        match replay_recorder.save_replay(&replay_path, &config, Some(&result)) {
             Ok(_) => println!("Replay saved to: {}", replay_path.display()),
             Err(e) => println!("Failed to save replay: {}", e),
         }
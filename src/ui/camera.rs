@@ -0,0 +1,189 @@
+﻿use bevy::prelude::*;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::window::PrimaryWindow;
+use crate::config::GameConfig;
+use crate::ui::selection::Selection;
+use crate::units::movement::hex_to_world_pos;
+use crate::world::HexGrid;
+use crate::world::hex_grid::HEX_SIZE;
+
+const MIN_ZOOM: f32 = 0.2;
+const MAX_ZOOM: f32 = 5.0;
+/// Cursor travel, in pixels, before a press counts as a drag rather than a click.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+#[derive(Resource, Default)]
+pub struct CameraControl {
+    /// Keep the selected unit centred.
+    pub follow: bool,
+    /// Whether the current (or just released) left press moved far enough to pan.
+    pub dragged: bool,
+    drag_origin: Option<Vec2>,
+    last_cursor: Option<Vec2>,
+    /// Map size the view was last fitted to.
+    fitted_size: Option<(i32, i32)>,
+}
+
+pub struct CameraControlPlugin;
+
+impl Plugin for CameraControlPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CameraControl>()
+            .add_systems(Update, (
+                camera_keys,
+                fit_camera_to_map.after(camera_keys),
+                zoom_camera,
+                drag_pan_camera,
+                follow_selected.after(camera_keys).after(drag_pan_camera),
+            ));
+    }
+}
+
+/// Arrows pan, T toggles follow, Home re-fits the whole map.
+fn camera_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut control: ResMut<CameraControl>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        control.follow = !control.follow;
+    }
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        control.fitted_size = None;
+    }
+
+    let Ok((mut transform, projection)) = cameras.get_single_mut() else {
+        return;
+    };
+
+    // Pan speed is in screen space, so it feels the same at any zoom
+    let speed = 300.0 * projection.scale * time.delta_seconds();
+    let mut delta = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        delta.x -= speed;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowRight) {
+        delta.x += speed;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowUp) {
+        delta.y += speed;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowDown) {
+        delta.y -= speed;
+    }
+    if delta != Vec2::ZERO {
+        transform.translation += delta.extend(0.0);
+        control.follow = false;
+    }
+}
+
+/// Frame the whole map on start, whenever its configured size changes, and on request.
+fn fit_camera_to_map(
+    config: Res<GameConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut control: ResMut<CameraControl>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let size = (config.game.map_width, config.game.map_height);
+    if control.fitted_size == Some(size) {
+        return;
+    }
+    let (Ok(window), Ok((mut transform, mut projection))) = (windows.get_single(), cameras.get_single_mut()) else {
+        return;
+    };
+
+    let (min, max) = HexGrid::from_config(&config).coords()
+        .map(|c| hex_to_world_pos(c.q, c.r))
+        .fold((Vec2::MAX, Vec2::MIN), |(min, max), p| (min.min(p), max.max(p)));
+    if min.x > max.x {
+        return;
+    }
+    let extent = (max - min) + Vec2::splat(HEX_SIZE * 2.0);
+    let center = (min + max) / 2.0;
+
+    // Leave room for the HUD around the edges
+    let scale = (extent.x / window.width()).max(extent.y / window.height()) * 1.15;
+    projection.scale = scale.clamp(MIN_ZOOM, MAX_ZOOM);
+    transform.translation.x = center.x;
+    transform.translation.y = center.y;
+    control.fitted_size = Some(size);
+}
+
+fn zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    mut projections: Query<&mut OrthographicProjection, With<Camera>>,
+) {
+    let Ok(mut projection) = projections.get_single_mut() else {
+        return;
+    };
+
+    for event in wheel.read() {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 40.0,
+        };
+        projection.scale = (projection.scale * 0.9_f32.powf(lines)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+/// Left-drag pans. A press that barely moves is left for selection as a click.
+pub(crate) fn drag_pan_camera(
+    buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut control: ResMut<CameraControl>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+
+    if buttons.just_pressed(MouseButton::Left) {
+        control.drag_origin = cursor;
+        control.last_cursor = cursor;
+        control.dragged = false;
+    }
+    if !buttons.pressed(MouseButton::Left) {
+        control.drag_origin = None;
+        return;
+    }
+
+    let (Some(origin), Some(cursor)) = (control.drag_origin, cursor) else {
+        return;
+    };
+    if !control.dragged && origin.distance(cursor) < DRAG_THRESHOLD {
+        return;
+    }
+    control.dragged = true;
+    control.follow = false;
+
+    if let (Some(last), Ok((mut transform, projection))) = (control.last_cursor, cameras.get_single_mut()) {
+        // Screen y grows downward, world y upward
+        let delta = (cursor - last) * projection.scale;
+        transform.translation.x -= delta.x;
+        transform.translation.y += delta.y;
+    }
+    control.last_cursor = Some(cursor);
+}
+
+fn follow_selected(
+    time: Res<Time>,
+    control: Res<CameraControl>,
+    selection: Res<Selection>,
+    targets: Query<&Transform, Without<Camera>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    if !control.follow {
+        return;
+    }
+    let (Some(target), Ok(mut camera)) = (selection.entity.and_then(|e| targets.get(e).ok()), cameras.get_single_mut()) else {
+        return;
+    };
+
+    // Ease toward the unit rather than snapping with every hex step
+    let t = (time.delta_seconds() * 5.0).min(1.0);
+    let goal = target.translation.truncate();
+    let current = camera.translation.truncate();
+    let next = current.lerp(goal, t);
+    camera.translation.x = next.x;
+    camera.translation.y = next.y;
+}
@@ -0,0 +1,209 @@
+﻿use bevy::prelude::*;
+use std::collections::HashMap;
+use crate::ai::CommChannel;
+use crate::game::TurnState;
+use crate::game::turn_manager::game_turn_system;
+use crate::units::{Unit, UnitId, HexPosition, Dead, Team, TeamRoster};
+use crate::units::movement::hex_to_world_pos;
+use crate::world::HexCoord;
+
+/// One message as drawn on the map: where it came from and who it reached.
+#[derive(Clone, Debug)]
+pub struct MessageTrace {
+    pub team: Team,
+    /// Sender's `UnitId`, for the panel.
+    pub from_id: u32,
+    pub from: HexCoord,
+    /// Every recipient's hex. A broadcast reaches the whole team.
+    pub to: Vec<HexCoord>,
+    pub broadcast: bool,
+    pub data: Vec<f32>,
+}
+
+impl MessageTrace {
+    /// Euclidean norm of the vector.
+    pub fn magnitude(&self) -> f32 {
+        self.data.iter().map(|v| v * v).sum::<f32>().sqrt()
+    }
+}
+
+/// Messages the overlay draws: those sent on turn `turn`. Filled from the
+/// live channel during a match; a replay viewer fills it from the recorded
+/// frame of the same turn instead.
+#[derive(Resource, Default)]
+pub struct CommTraces {
+    pub turn: u32,
+    pub traces: Vec<MessageTrace>,
+}
+
+#[derive(Resource, Default)]
+pub struct CommOverlay {
+    pub enabled: bool,
+    /// Index into `CommTraces::traces` shown in the panel.
+    pub selected: usize,
+}
+
+#[derive(Component)]
+pub struct CommPanel;
+
+pub struct CommOverlayPlugin;
+
+impl Plugin for CommOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CommTraces>()
+            .init_resource::<CommOverlay>()
+            .add_systems(Startup, spawn_comm_panel)
+            .add_systems(Update, (
+                trace_live_messages.after(game_turn_system),
+                handle_comm_keys,
+                draw_comm_lines.after(trace_live_messages).after(handle_comm_keys),
+                update_comm_panel.after(trace_live_messages).after(handle_comm_keys),
+            ));
+    }
+}
+
+fn spawn_comm_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "Comms: off (C)",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(15.0),
+            bottom: Val::Px(60.0),
+            ..default()
+        }),
+        CommPanel,
+    ));
+}
+
+/// Rebuild the traces once a turn closes, from what each team sent on it.
+fn trace_live_messages(
+    channel: Option<Res<CommChannel>>,
+    turn_state: Res<TurnState>,
+    roster: Res<TeamRoster>,
+    units: Query<(Entity, &UnitId, &Unit, &HexPosition), Without<Dead>>,
+    mut traces: ResMut<CommTraces>,
+) {
+    let Some(channel) = channel else {
+        return;
+    };
+    if !channel.is_changed() || traces.turn == turn_state.turn {
+        return;
+    }
+
+    let positions: HashMap<Entity, (UnitId, Team, HexCoord)> = units.iter()
+        .map(|(e, id, u, p)| (e, (*id, u.team, p.coord)))
+        .collect();
+
+    traces.turn = turn_state.turn;
+    traces.traces = roster.teams()
+        .flat_map(|team| channel.sent_last_turn(team).iter().map(move |m| (team, m)))
+        .filter_map(|(team, message)| {
+            let (from_id, _, from) = *positions.get(&message.from)?;
+            let to = match message.to {
+                Some(to) => positions.get(&to).map(|(_, _, c)| *c).into_iter().collect(),
+                None => positions.iter()
+                    .filter(|(e, (_, t, _))| **e != message.from && *t == team)
+                    .map(|(_, (_, _, c))| *c)
+                    .collect(),
+            };
+            Some(MessageTrace {
+                team,
+                from_id: from_id.0,
+                from,
+                to,
+                broadcast: message.to.is_none(),
+                data: message.data.clone(),
+            })
+        })
+        .collect();
+}
+
+/// C toggles the overlay, N steps through this turn's messages.
+fn handle_comm_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<CommOverlay>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        overlay.enabled = !overlay.enabled;
+    }
+    if overlay.enabled && keyboard_input.just_pressed(KeyCode::KeyN) {
+        overlay.selected += 1;
+    }
+}
+
+/// Blue for quiet messages through to red for the loudest one this turn.
+fn magnitude_color(magnitude: f32, max: f32) -> Color {
+    let t = if max > 0.0 { (magnitude / max).clamp(0.0, 1.0) } else { 0.0 };
+    Color::rgb(0.2 + 0.8 * t, 0.6 - 0.4 * t, 1.0 - 0.8 * t)
+}
+
+fn draw_comm_lines(
+    overlay: Res<CommOverlay>,
+    traces: Res<CommTraces>,
+    mut gizmos: Gizmos,
+) {
+    if !overlay.enabled || traces.traces.is_empty() {
+        return;
+    }
+
+    let max = traces.traces.iter().map(MessageTrace::magnitude).fold(0.0, f32::max);
+    let selected = overlay.selected % traces.traces.len();
+    for (i, trace) in traces.traces.iter().enumerate() {
+        let color = if i == selected {
+            Color::WHITE
+        } else {
+            magnitude_color(trace.magnitude(), max).with_a(0.6)
+        };
+        let from = hex_to_world_pos(trace.from.q, trace.from.r);
+        for to in &trace.to {
+            gizmos.line_2d(from, hex_to_world_pos(to.q, to.r), color);
+        }
+        if i == selected {
+            gizmos.circle_2d(from, 20.0, color);
+        }
+    }
+}
+
+fn update_comm_panel(
+    overlay: Res<CommOverlay>,
+    traces: Res<CommTraces>,
+    roster: Res<TeamRoster>,
+    mut panel: Query<&mut Text, With<CommPanel>>,
+) {
+    if !overlay.is_changed() && !traces.is_changed() {
+        return;
+    }
+
+    let message = if !overlay.enabled {
+        "Comms: off (C)".to_string()
+    } else if traces.traces.is_empty() {
+        format!("Comms: turn {}, no messages (C)", traces.turn)
+    } else {
+        let selected = overlay.selected % traces.traces.len();
+        let trace = &traces.traces[selected];
+        let target = if trace.broadcast {
+            "all".to_string()
+        } else {
+            format!("{} unit(s)", trace.to.len())
+        };
+        let values: Vec<String> = trace.data.iter().map(|v| format!("{:.3}", v)).collect();
+        format!(
+            "Comms: turn {}, message {}/{} (C, N: next)\n{} #{} at ({}, {}) -> {}, |v| = {:.3}\n[{}]",
+            traces.turn, selected + 1, traces.traces.len(),
+            roster.tag(trace.team), trace.from_id, trace.from.q, trace.from.r, target, trace.magnitude(),
+            values.join(", "),
+        )
+    };
+
+    for mut text in &mut panel {
+        text.sections[0].value = message.clone();
+    }
+}
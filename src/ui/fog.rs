@@ -0,0 +1,146 @@
+﻿use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use crate::config::GameConfig;
+use crate::game::TurnState;
+use crate::units::{Team, TeamRoster};
+use crate::units::movement::hex_to_world_pos;
+use crate::world::{HexCoord, HexGrid, TeamVisibility};
+use crate::world::hex_grid::{create_hex_mesh, HEX_SIZE};
+
+/// Which team's perception the fog overlay shows. `None` shows the whole map.
+#[derive(Resource, Default)]
+pub struct FogView(pub Option<Team>);
+
+#[derive(Component)]
+pub struct FogTile {
+    pub coord: HexCoord,
+}
+
+#[derive(Component)]
+pub struct FogLabel;
+
+pub struct FogOverlayPlugin;
+
+impl Plugin for FogOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FogView>()
+            .add_systems(Startup, spawn_fog_tiles)
+            .add_systems(Update, (
+                cycle_fog_view,
+                update_fog_tiles.after(cycle_fog_view),
+                draw_last_known.after(cycle_fog_view),
+            ));
+    }
+}
+
+fn spawn_fog_tiles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<GameConfig>,
+) {
+    let mesh = meshes.add(create_hex_mesh(HEX_SIZE));
+    let material = materials.add(ColorMaterial::from(Color::rgba(0.0, 0.0, 0.0, 0.7)));
+    
+    for coord in HexGrid::from_config(&config).coords() {
+        let pos = hex_to_world_pos(coord.q, coord.r);
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.clone().into(),
+                material: material.clone(),
+                // Above units and their health bars so hidden enemies are covered
+                transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 5.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            FogTile { coord },
+        ));
+    }
+    
+    commands.spawn((
+        TextBundle::from_section(
+            "Fog: off (F)",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(15.0),
+            top: Val::Px(15.0),
+            ..default()
+        }),
+        FogLabel,
+    ));
+}
+
+fn cycle_fog_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut view: ResMut<FogView>,
+    roster: Res<TeamRoster>,
+    mut label: Query<&mut Text, With<FogLabel>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    
+    // Off -> each team in turn -> off
+    view.0 = match view.0 {
+        None => roster.teams().next(),
+        Some(team) => roster.teams()
+            .skip_while(|t| *t != team)
+            .nth(1),
+    };
+    
+    for mut text in &mut label {
+        text.sections[0].value = match view.0 {
+            Some(team) => format!("Fog: {} view (F)", roster.tag(team)),
+            None => "Fog: off (F)".to_string(),
+        };
+    }
+}
+
+fn update_fog_tiles(
+    view: Res<FogView>,
+    visibility: Res<TeamVisibility>,
+    mut tiles: Query<(&FogTile, &mut Visibility)>,
+) {
+    if !view.is_changed() && !visibility.is_changed() {
+        return;
+    }
+    
+    for (tile, mut vis) in &mut tiles {
+        let fogged = view.0.is_some_and(|team| !visibility.is_visible(team, tile.coord));
+        *vis = if fogged { Visibility::Visible } else { Visibility::Hidden };
+    }
+}
+
+/// Outline the hexes where the viewed team remembers seeing enemies.
+fn draw_last_known(
+    view: Res<FogView>,
+    visibility: Res<TeamVisibility>,
+    turn_state: Res<TurnState>,
+    roster: Res<TeamRoster>,
+    mut gizmos: Gizmos,
+) {
+    let Some(team) = view.0 else {
+        return;
+    };
+    let Some(vision) = visibility.team(team) else {
+        return;
+    };
+    
+    for seen in vision.last_known.values() {
+        if vision.visible.contains(&seen.coord) {
+            continue;
+        }
+        // Older sightings fade out
+        let age = turn_state.turn.saturating_sub(seen.turn) as f32;
+        let alpha = (1.0 - age / 20.0).max(0.25);
+        let pos = hex_to_world_pos(seen.coord.q, seen.coord.r);
+        gizmos.circle_2d(pos, 15.0, roster.color(seen.team).with_a(alpha));
+    }
+}
@@ -1,6 +1,8 @@
 ﻿use bevy::prelude::*;
-use crate::game::TurnState;
-use crate::units::{Unit, Team};
+use crate::game::{TurnState, SimControl};
+use crate::game::CapturePoints;
+use crate::game::victory::MatchScores;
+use crate::units::{Unit, TeamRoster};
 
 #[derive(Component)]
 pub struct TurnText;
@@ -8,13 +10,16 @@ pub struct TurnText;
 #[derive(Component)]
 pub struct CountText;
 
+#[derive(Component)]
+pub struct ObjectiveText;
+
 pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_systems(Startup, spawn_hud)
-            .add_systems(Update, update_hud);
+            .add_systems(Update, (update_hud, update_objective_hud));
     }
 }
 
@@ -56,28 +61,78 @@ fn spawn_hud(mut commands: Commands) {
         }),
         CountText,
     ));
+    
+    // Capture points and score
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(15.0),
+            top: Val::Px(80.0),
+            ..default()
+        }),
+        ObjectiveText,
+    ));
 }
 
 fn update_hud(
     turn_state: Res<TurnState>,
+    control: Res<SimControl>,
     units: Query<&Unit>,
+    roster: Res<TeamRoster>,
     mut turn_text: Query<&mut Text, (With<TurnText>, Without<CountText>)>,
     mut count_text: Query<&mut Text, (With<CountText>, Without<TurnText>)>,
 ) {
     // Update turn text
+    let playback = if control.paused { "PAUSED".to_string() } else { format!("{}x", control.speed()) };
     for mut text in &mut turn_text {
-        text.sections[0].value = format!("Turn: {} | Time: {:.1}s", 
-            turn_state.turn, turn_state.time);
+        text.sections[0].value = format!("Turn: {} | Time: {:.1}s | {}", 
+            turn_state.turn, turn_state.time, playback);
     }
     
     // Count units
-    let (red_count, blue_count) = units.iter()
-        .fold((0, 0), |(r, b), unit| {
-            if unit.team == Team::Red { (r + 1, b) } else { (r, b + 1) }
-        });
+    let counts: Vec<String> = roster.iter()
+        .map(|info| {
+            let count = units.iter().filter(|u| u.team == info.team).count();
+            format!("{}: {}", info.name, count)
+        })
+        .collect();
     
     // Update count text
     for mut text in &mut count_text {
-        text.sections[0].value = format!("Red: {} • Blue: {}", red_count, blue_count);
+        text.sections[0].value = counts.join(" • ");
+    }
+}
+
+fn update_objective_hud(
+    capture_points: Res<CapturePoints>,
+    scores: Res<MatchScores>,
+    roster: Res<TeamRoster>,
+    mut objective_text: Query<&mut Text, With<ObjectiveText>>,
+) {
+    if capture_points.points.is_empty() {
+        return;
+    }
+    let standings: Vec<String> = roster.iter()
+        .map(|info| format!("{}: {} ({} held)", info.name, scores.points(info.team), capture_points.owned_by(info.team)))
+        .collect();
+    let mut lines = vec![format!("Points - {}", standings.join(" • "))];
+    for point in &capture_points.points {
+        let owner = point.owner.map_or("-", |t| roster.name(t));
+        let status = match point.capturing {
+            Some(team) => format!(" ({} capturing {})", roster.name(team), point.progress),
+            None => String::new(),
+        };
+        lines.push(format!("  ({}, {}): {}{}", point.coord.q, point.coord.r, owner, status));
+    }
+    for mut text in &mut objective_text {
+        text.sections[0].value = lines.join("\n");
     }
 }
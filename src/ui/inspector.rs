@@ -0,0 +1,129 @@
+﻿use bevy::prelude::*;
+use crate::ai::AiDebug;
+use crate::game::CapturePoints;
+use crate::ui::selection::Selection;
+use crate::units::{Unit, UnitId, HexPosition, LastAction, Structure, Dead, TeamRoster};
+use crate::world::{HexCoord, TerrainMap};
+use crate::world::resource::ResourceField;
+
+#[derive(Component)]
+pub struct InspectorPanel;
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, spawn_inspector_panel)
+            .add_systems(Update, update_inspector_panel);
+    }
+}
+
+fn spawn_inspector_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(15.0),
+            top: Val::Px(50.0),
+            ..default()
+        }),
+        InspectorPanel,
+    ));
+}
+
+/// Everything the panel can say about the selected hex.
+struct TileView<'a> {
+    terrain: &'a TerrainMap,
+    resources: &'a ResourceField,
+    capture_points: &'a CapturePoints,
+    roster: &'a TeamRoster,
+}
+
+impl TileView<'_> {
+    fn describe(&self, coord: HexCoord, structure: Option<&Structure>) -> Vec<String> {
+        let terrain = self.terrain.get(coord);
+        let mut lines = vec![
+            format!("Hex ({}, {})", coord.q, coord.r),
+            match terrain.move_cost() {
+                Some(cost) => format!("Terrain: {:?}, move cost {}", terrain, cost),
+                None => format!("Terrain: {:?}, impassable", terrain),
+            },
+        ];
+
+        if let Some(node) = self.resources.node_at(coord) {
+            lines.push(format!("Resource: {:?} {}/{}", node.kind, node.amount, node.max_amount));
+        }
+        if let Some(structure) = structure {
+            let state = if !structure.is_complete() {
+                format!("building, {} turn(s) left", structure.build_turns_left)
+            } else if let Some(order) = structure.production {
+                format!("training {:?}, {} turn(s) left", order.unit_type, order.turns_left)
+            } else {
+                "idle".to_string()
+            };
+            lines.push(format!("Structure: {} {:?}, {:.0}/{:.0} HP, {}",
+                self.roster.tag(structure.team), structure.kind, structure.health, structure.max_health, state));
+        }
+        if let Some(point) = self.capture_points.points.iter().find(|p| p.coord == coord) {
+            let owner = point.owner.map_or("nobody".to_string(), |t| self.roster.tag(t));
+            let mut line = format!("Capture point: held by {}", owner);
+            if let Some(team) = point.capturing {
+                line.push_str(&format!(", {} capturing ({})", self.roster.tag(team), point.progress));
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+fn update_inspector_panel(
+    (selection, debug): (Res<Selection>, Res<AiDebug>),
+    units: Query<(&UnitId, &Unit, &HexPosition, Option<&LastAction>), Without<Dead>>,
+    structures: Query<(&Structure, &HexPosition)>,
+    (terrain, resources, capture_points): (Res<TerrainMap>, Res<ResourceField>, Res<CapturePoints>),
+    roster: Res<TeamRoster>,
+    mut panel: Query<&mut Text, With<InspectorPanel>>,
+) {
+    let mut lines = Vec::new();
+
+    let selected = selection.entity.and_then(|e| units.get(e).ok().map(|unit| (e, unit)));
+    if let Some((entity, (id, unit, pos, last_action))) = selected {
+        let controller = roster.get(unit.team).map_or("?", |info| info.controller.as_str());
+        lines.push(format!("Unit #{} {} {:?}", id.0, roster.tag(unit.team), unit.unit_type));
+        lines.push(format!("Health: {:.0}/{:.0}", unit.health, unit.max_health));
+        lines.push(format!("Position: ({}, {})", pos.coord.q, pos.coord.r));
+        lines.push(format!("Controller: {}", controller));
+        match last_action.filter(|a| a.turn > 0) {
+            Some(last) => {
+                lines.push(format!("Last action (turn {}): {:?}", last.turn, last.action));
+                lines.push(format!("Result: {:?}", last.result));
+            }
+            None => lines.push("Last action: none yet".to_string()),
+        }
+        lines.extend(debug.notes(entity).map(|note| format!("AI: {}", note)));
+    } else if let Some(coord) = selection.hex {
+        let structure = structures.iter().find(|(_, p)| p.coord == coord).map(|(s, _)| s);
+        let view = TileView {
+            terrain: &terrain,
+            resources: &resources,
+            capture_points: &capture_points,
+            roster: &roster,
+        };
+        lines = view.describe(coord, structure);
+    }
+
+    let message = lines.join("\n");
+    for mut text in &mut panel {
+        if text.sections[0].value != message {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
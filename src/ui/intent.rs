@@ -0,0 +1,128 @@
+﻿use bevy::prelude::*;
+use crate::ai::{AiDebug, DebugShape};
+use crate::game::TurnState;
+use crate::game::combat_system::CombatEvent;
+use crate::units::{Unit, HexPosition, LastAction, Dead, TeamRoster};
+use crate::units::movement::hex_to_world_pos;
+use crate::world::HexCoord;
+use crate::world::actions::{Action, ActionResult};
+
+/// Shows what every unit chose this turn, plus whatever the controllers drew
+/// through `DecisionContext`.
+#[derive(Resource, Default)]
+pub struct IntentOverlay {
+    pub enabled: bool,
+}
+
+/// Attacker and defender hexes for this turn's combat.
+#[derive(Resource, Default)]
+pub struct Strikes {
+    turn: u32,
+    lines: Vec<(HexCoord, HexCoord)>,
+}
+
+pub struct IntentOverlayPlugin;
+
+impl Plugin for IntentOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<IntentOverlay>()
+            .init_resource::<Strikes>()
+            .add_systems(Update, (
+                toggle_intents,
+                record_strikes,
+                draw_intents.after(toggle_intents).after(record_strikes),
+            ));
+    }
+}
+
+/// I toggles the overlay. Controllers only pay for debug shapes while it's on.
+fn toggle_intents(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<IntentOverlay>,
+    mut debug: ResMut<AiDebug>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyI) {
+        overlay.enabled = !overlay.enabled;
+        debug.enabled = overlay.enabled;
+    }
+}
+
+fn record_strikes(
+    mut events: EventReader<CombatEvent>,
+    turn_state: Res<TurnState>,
+    positions: Query<&HexPosition>,
+    mut strikes: ResMut<Strikes>,
+) {
+    if strikes.turn != turn_state.turn {
+        strikes.turn = turn_state.turn;
+        strikes.lines.clear();
+    }
+    for event in events.read() {
+        if let (Ok(from), Ok(to)) = (positions.get(event.attacker), positions.get(event.defender)) {
+            strikes.lines.push((from.coord, to.coord));
+        }
+    }
+}
+
+fn world_pos(coord: HexCoord) -> Vec2 {
+    hex_to_world_pos(coord.q, coord.r)
+}
+
+fn draw_intents(
+    overlay: Res<IntentOverlay>,
+    turn_state: Res<TurnState>,
+    units: Query<(&Unit, &HexPosition, &LastAction), Without<Dead>>,
+    (strikes, debug): (Res<Strikes>, Res<AiDebug>),
+    roster: Res<TeamRoster>,
+    mut gizmos: Gizmos,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    for (unit, pos, last) in &units {
+        if last.turn != turn_state.turn {
+            continue;
+        }
+        let color = roster.color(unit.team);
+        let here = world_pos(pos.coord);
+        match (last.action, last.result) {
+            (Action::Move(dq, dr), ActionResult::Moved) => {
+                gizmos.arrow_2d(world_pos(pos.coord.offset(-dq, -dr)), here, color);
+            }
+            (Action::Move(dq, dr), _) => {
+                // Wanted to move but couldn't: a red stub toward the blocked hex
+                let target = world_pos(pos.coord.offset(dq, dr));
+                gizmos.arrow_2d(here, here.lerp(target, 0.5), Color::RED);
+            }
+            (Action::Build(_, site), result) => {
+                let color = if result == ActionResult::BuildStarted { color } else { Color::RED };
+                gizmos.line_2d(here, world_pos(site), color);
+                gizmos.rect_2d(world_pos(site), 0.0, Vec2::splat(16.0), color);
+            }
+            (Action::Gather, ActionResult::Gathered(..)) => {
+                gizmos.circle_2d(here, 8.0, Color::GOLD);
+            }
+            _ => {
+                gizmos.circle_2d(here, 6.0, color);
+            }
+        }
+    }
+
+    for (from, to) in &strikes.lines {
+        gizmos.line_2d(world_pos(*from), world_pos(*to), Color::RED);
+    }
+
+    for (_, shape) in debug.shapes() {
+        match shape {
+            DebugShape::Line { from, to, color } => {
+                gizmos.line_2d(world_pos(*from), world_pos(*to), color.with_a(0.5));
+            }
+            DebugShape::Circle { at, color } => {
+                gizmos.circle_2d(world_pos(*at), 18.0, *color);
+            }
+            DebugShape::Note(_) => {}
+        }
+    }
+}
@@ -1,5 +1,18 @@
 ﻿pub mod hud;
 pub mod overlay;
+pub mod fog;
+pub mod comms;
+pub mod camera;
+pub mod selection;
+pub mod inspector;
+pub mod intent;
+pub mod replay_viewer;
 
 pub use hud::HudPlugin;
 pub use overlay::OverlayPlugin;
+pub use fog::FogOverlayPlugin;
+pub use comms::CommOverlayPlugin;
+pub use camera::CameraControlPlugin;
+pub use selection::SelectionPlugin;
+pub use inspector::InspectorPlugin;
+pub use intent::IntentOverlayPlugin;
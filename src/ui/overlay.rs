@@ -1,6 +1,6 @@
 ﻿use bevy::prelude::*;
-use crate::game::GameOver;
-use crate::units::{Unit, Team};
+use crate::game::MatchResult;
+use crate::units::TeamRoster;
 
 #[derive(Component)]
 pub struct VictoryBanner;
@@ -9,30 +9,22 @@ pub struct OverlayPlugin;
 
 impl Plugin for OverlayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, victory_banner_system);
+        app.add_systems(Update, (victory_banner_system, clear_victory_banner));
     }
 }
 
 fn victory_banner_system(
-    game_over: Res<GameOver>,
-    units: Query<&Unit>,
-    mut spawned: Local<bool>,
+    result: Option<Res<MatchResult>>,
+    roster: Res<TeamRoster>,
     mut commands: Commands,
 ) {
-    if game_over.0 && !*spawned {
-        *spawned = true;
+    let Some(result) = result else {
+        return;
+    };
+    
+    if result.is_added() {
         
-        let (red_count, blue_count) = units.iter()
-            .fold((0, 0), |(r, b), unit| {
-                if unit.team == Team::Red { (r + 1, b) } else { (r, b + 1) }
-            });
-        
-        let message = match (red_count, blue_count) {
-            (0, 0) => "DRAW!",
-            (0, _) => "BLUE TEAM WINS!",
-            (_, 0) => "RED TEAM WINS!",
-            _ => "GAME CONTINUES...", // This was the unreachable case
-        };
+        let message = format!("{}\n{:?}", result.headline(&roster), result.reason);
         
         commands.spawn((
             TextBundle::from_section(
@@ -53,3 +45,16 @@ fn victory_banner_system(
         ));
     }
 }
+
+fn clear_victory_banner(
+    mut resets: EventReader<crate::game::ResetMatch>,
+    banners: Query<Entity, With<VictoryBanner>>,
+    mut commands: Commands,
+) {
+    if resets.read().count() == 0 {
+        return;
+    }
+    for banner in &banners {
+        commands.entity(banner).despawn();
+    }
+}
@@ -0,0 +1,435 @@
+﻿use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::ui::RelativeCursorPosition;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::config::{ConfigPlugin, GameConfig, SimulationConfig};
+use crate::engine::replay::{PieceKind, ReplayPlayer};
+use crate::game::{TurnState, SimControl};
+use crate::ui::{CameraControlPlugin, CommOverlayPlugin};
+use crate::ui::comms::{CommTraces, MessageTrace};
+use crate::ui::selection::Selection;
+use crate::units::{Team, TeamRoster, UnitType};
+use crate::units::movement::hex_to_world_pos;
+use crate::world::hex_grid::HexGridPlugin;
+
+/// Open `path` in a window and play it back. `config` is the running
+/// config, checked against the one the replay was recorded with.
+pub fn run_replay_viewer(path: &Path, config: GameConfig) {
+    let player = match ReplayPlayer::load(path) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("Failed to load replay {}: {}", path.display(), e);
+            return;
+        }
+    };
+    if player.replay().is_from_newer_version() {
+        eprintln!("Replay {} was recorded by a newer version; refusing to play it", path.display());
+        return;
+    }
+    println!("Viewing replay {} ({} turns)", path.display(), player.last_turn());
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "AI Battle Arena - Replay".to_string(),
+                resolution: (1280., 720.).into(),
+                ..default()
+            }),
+            ..default()
+        })
+    );
+    app.insert_resource(config);
+    app.add_plugins(ConfigPlugin);
+
+    let differences = player.replay().rule_differences(app.world.resource::<GameConfig>());
+    if !differences.is_empty() {
+        println!("Warning: this replay was recorded under different rules:");
+        for difference in &differences {
+            println!("  {}", difference);
+        }
+    }
+
+    match &player.replay().setup {
+        // Play back under the recorded rules, on the recorded map
+        Some(setup) => {
+            app.insert_resource(setup.map.terrain_map(&setup.config));
+            app.insert_resource(setup.map.resource_field());
+            app.insert_resource(setup.config.clone());
+        }
+        // Older replays only know the map size
+        None => {
+            let map = &player.replay().map_config;
+            let mut config = app.world.resource_mut::<GameConfig>();
+            config.game.map_width = map.width;
+            config.game.map_height = map.height;
+        }
+    }
+
+    app
+        .insert_resource(SimulationConfig::default())
+        .insert_resource(TurnState { turn: 0, time: 0.0 })
+        .insert_resource(player)
+        .add_plugins((
+            HexGridPlugin,
+            CameraControlPlugin,
+            CommOverlayPlugin,
+            ReplayViewerPlugin,
+        ))
+        .run();
+}
+
+#[derive(Component)]
+struct ReplayPieceMarker;
+
+#[derive(Component)]
+struct ReplayLabel;
+
+#[derive(Component)]
+struct TimelineBar;
+
+#[derive(Component)]
+struct TimelineCursor;
+
+/// Spawned entity for each piece id on the board, plus shared render assets.
+#[derive(Resource, Default)]
+struct PieceEntities {
+    entities: HashMap<u32, Entity>,
+    materials: HashMap<Team, Handle<ColorMaterial>>,
+    synced_turn: Option<u32>,
+}
+
+/// Viewer-only playback state on top of `SimControl`.
+#[derive(Resource, Default)]
+struct ReplayControl {
+    /// Digits typed so far for a jump-to-turn.
+    jump: String,
+    elapsed: f32,
+}
+
+pub struct ReplayViewerPlugin;
+
+impl Plugin for ReplayViewerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimControl>()
+            .init_resource::<Selection>()
+            .init_resource::<PieceEntities>()
+            .init_resource::<ReplayControl>()
+            .add_systems(Startup, setup_viewer)
+            .add_systems(Update, (
+                handle_replay_keys,
+                seek_on_timeline_click,
+                advance_playback.after(handle_replay_keys),
+                sync_pieces.after(advance_playback).after(seek_on_timeline_click),
+                draw_health.after(sync_pieces),
+                update_replay_label.after(sync_pieces),
+            ));
+    }
+}
+
+fn setup_viewer(mut commands: Commands, player: Res<ReplayPlayer>) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            bottom: Val::Px(50.0),
+            ..default()
+        }),
+        ReplayLabel,
+    ));
+
+    // Timeline: a mark for every turn with combat, taller and red where something died
+    let last = player.last_turn().max(1) as f32;
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(20.0),
+                right: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                height: Val::Px(20.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.15, 0.15, 0.15, 0.8).into(),
+            ..default()
+        },
+        RelativeCursorPosition::default(),
+        TimelineBar,
+    )).with_children(|bar| {
+        for (turn, _, kills) in player.combat_turns() {
+            let (height, color) = if kills > 0 {
+                (100.0, Color::RED)
+            } else {
+                (50.0, Color::ORANGE)
+            };
+            bar.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(turn as f32 / last * 100.0),
+                    bottom: Val::Px(0.0),
+                    width: Val::Px(2.0),
+                    height: Val::Percent(height),
+                    ..default()
+                },
+                background_color: color.into(),
+                ..default()
+            });
+        }
+        bar.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(0.0),
+                    width: Val::Px(3.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                ..default()
+            },
+            TimelineCursor,
+        ));
+    });
+}
+
+/// SPACE play/pause, `.`/`,` step, +/- speed, digits then Enter jump to a turn.
+fn handle_replay_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut control: ResMut<SimControl>,
+    mut replay_control: ResMut<ReplayControl>,
+    mut player: ResMut<ReplayPlayer>,
+    mut exit: EventWriter<bevy::app::AppExit>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        exit.send(bevy::app::AppExit);
+    }
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        // Playing from the end starts over
+        if control.paused && player.turn() >= player.last_turn() {
+            player.seek(0);
+        }
+        control.toggle_pause();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        control.faster();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        control.slower();
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        control.paused = true;
+        let next = player.turn() + 1;
+        player.seek(next);
+    }
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        control.paused = true;
+        let previous = player.turn().saturating_sub(1);
+        player.seek(previous);
+    }
+
+    const DIGITS: [(KeyCode, KeyCode, char); 10] = [
+        (KeyCode::Digit0, KeyCode::Numpad0, '0'), (KeyCode::Digit1, KeyCode::Numpad1, '1'),
+        (KeyCode::Digit2, KeyCode::Numpad2, '2'), (KeyCode::Digit3, KeyCode::Numpad3, '3'),
+        (KeyCode::Digit4, KeyCode::Numpad4, '4'), (KeyCode::Digit5, KeyCode::Numpad5, '5'),
+        (KeyCode::Digit6, KeyCode::Numpad6, '6'), (KeyCode::Digit7, KeyCode::Numpad7, '7'),
+        (KeyCode::Digit8, KeyCode::Numpad8, '8'), (KeyCode::Digit9, KeyCode::Numpad9, '9'),
+    ];
+    for (key, numpad, digit) in DIGITS {
+        if keyboard_input.any_just_pressed([key, numpad]) && replay_control.jump.len() < 6 {
+            replay_control.jump.push(digit);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        replay_control.jump.pop();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+        if let Ok(turn) = std::mem::take(&mut replay_control.jump).parse() {
+            control.paused = true;
+            player.seek(turn);
+        }
+    }
+}
+
+fn seek_on_timeline_click(
+    buttons: Res<ButtonInput<MouseButton>>,
+    bar: Query<&RelativeCursorPosition, With<TimelineBar>>,
+    mut control: ResMut<SimControl>,
+    mut player: ResMut<ReplayPlayer>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(position) = bar.iter().find(|c| c.mouse_over()).and_then(|c| c.normalized) else {
+        return;
+    };
+    control.paused = true;
+    let turn = (position.x.clamp(0.0, 1.0) * player.last_turn() as f32).round() as u32;
+    player.seek(turn);
+}
+
+/// One turn every `game.tick_rate` seconds, scaled by the playback speed.
+fn advance_playback(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut control: ResMut<SimControl>,
+    mut replay_control: ResMut<ReplayControl>,
+    mut player: ResMut<ReplayPlayer>,
+) {
+    if control.paused {
+        replay_control.elapsed = 0.0;
+        return;
+    }
+
+    replay_control.elapsed += time.delta_seconds() * control.speed();
+    while replay_control.elapsed >= config.game.tick_rate {
+        replay_control.elapsed -= config.game.tick_rate;
+        if player.turn() >= player.last_turn() {
+            control.paused = true;
+            break;
+        }
+        let next = player.turn() + 1;
+        player.seek(next);
+    }
+}
+
+/// Bring the drawn pieces and comm traces in line with the player's board.
+fn sync_pieces(
+    mut commands: Commands,
+    player: Res<ReplayPlayer>,
+    roster: Res<TeamRoster>,
+    (mut meshes, mut materials): (ResMut<Assets<Mesh>>, ResMut<Assets<ColorMaterial>>),
+    mut spawned: ResMut<PieceEntities>,
+    mut transforms: Query<&mut Transform, With<ReplayPieceMarker>>,
+    (mut turn_state, mut traces): (ResMut<TurnState>, ResMut<CommTraces>),
+) {
+    if spawned.synced_turn == Some(player.turn()) {
+        return;
+    }
+    spawned.synced_turn = Some(player.turn());
+    turn_state.turn = player.turn();
+
+    let board = player.board();
+    let gone: Vec<u32> = spawned.entities.keys()
+        .filter(|id| !board.pieces.contains_key(id))
+        .copied()
+        .collect();
+    for id in gone {
+        if let Some(entity) = spawned.entities.remove(&id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for piece in board.pieces.values() {
+        let pos = hex_to_world_pos(piece.coord.q, piece.coord.r);
+        if let Some(mut transform) = spawned.entities.get(&piece.id).and_then(|e| transforms.get_mut(*e).ok()) {
+            transform.translation.x = pos.x;
+            transform.translation.y = pos.y;
+            continue;
+        }
+
+        let mesh = match piece.kind {
+            PieceKind::Unit(UnitType::Worker) => meshes.add(Circle::new(10.0)),
+            PieceKind::Unit(_) => meshes.add(Circle::new(15.0)),
+            PieceKind::Structure(_) => meshes.add(Rectangle::new(36.0, 36.0)),
+        };
+        let material = spawned.materials
+            .entry(piece.team)
+            .or_insert_with(|| materials.add(ColorMaterial::from(roster.color(piece.team))))
+            .clone();
+        let entity = commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.into(),
+                material,
+                transform: Transform::from_translation(pos.extend(1.0)),
+                ..default()
+            },
+            ReplayPieceMarker,
+        )).id();
+        spawned.entities.insert(piece.id, entity);
+    }
+
+    // Messages sent this turn, drawn from sender to whoever they reached
+    traces.turn = player.turn();
+    traces.traces = player.frame(player.turn())
+        .map(|frame| frame.messages.iter()
+            .filter_map(|message| {
+                let from = board.pieces.get(&message.from)?;
+                let to = match message.to {
+                    Some(to) => board.pieces.get(&to).map(|p| p.coord).into_iter().collect(),
+                    None => board.pieces.values()
+                        .filter(|p| p.id != message.from && p.team == message.team
+                            && matches!(p.kind, PieceKind::Unit(_)))
+                        .map(|p| p.coord)
+                        .collect(),
+                };
+                Some(MessageTrace {
+                    team: message.team,
+                    from_id: message.from,
+                    from: from.coord,
+                    to,
+                    broadcast: message.to.is_none(),
+                    data: message.data.clone(),
+                })
+            })
+            .collect())
+        .unwrap_or_default();
+}
+
+fn draw_health(player: Res<ReplayPlayer>, mut gizmos: Gizmos) {
+    for piece in player.board().pieces.values() {
+        let pos = hex_to_world_pos(piece.coord.q, piece.coord.r) + Vec2::new(-20.0, 24.0);
+        let fraction = (piece.health / piece.max_health).clamp(0.0, 1.0);
+        gizmos.line_2d(pos, pos + Vec2::new(40.0, 0.0), Color::DARK_GRAY);
+        gizmos.line_2d(pos, pos + Vec2::new(40.0 * fraction, 0.0), Color::GREEN);
+    }
+}
+
+fn update_replay_label(
+    player: Res<ReplayPlayer>,
+    control: Res<SimControl>,
+    replay_control: Res<ReplayControl>,
+    roster: Res<TeamRoster>,
+    mut label: Query<&mut Text, With<ReplayLabel>>,
+    mut cursor: Query<&mut Style, With<TimelineCursor>>,
+) {
+    let state = if control.paused { "PAUSED".to_string() } else { format!("{}x", control.speed()) };
+    let jump = if replay_control.jump.is_empty() {
+        String::new()
+    } else {
+        format!(" | jump to {}_", replay_control.jump)
+    };
+    let outcome = if player.turn() >= player.last_turn() {
+        player.replay().match_result.as_ref()
+            .map(|result| format!(" | {}", result.headline(&roster)))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let message = format!(
+        "Replay turn {}/{} {}{}{}\nSPACE: play | ,/.: step | +/-: speed | 0-9 ENTER: jump | click timeline: seek | C: comms",
+        player.turn(), player.last_turn(), state, jump, outcome,
+    );
+    for mut text in &mut label {
+        if text.sections[0].value != message {
+            text.sections[0].value = message.clone();
+        }
+    }
+
+    let last = player.last_turn().max(1) as f32;
+    for mut style in &mut cursor {
+        style.left = Val::Percent(player.turn() as f32 / last * 100.0);
+    }
+}
@@ -0,0 +1,83 @@
+﻿use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::ui::camera::{self, CameraControl};
+use crate::units::{Unit, HexPosition, Dead};
+use crate::units::movement::{hex_to_world_pos, world_to_hex};
+use crate::world::{HexCoord, TerrainMap};
+
+/// What was picked with the last left click: the hex, and the unit standing
+/// on it if there was one and it is still alive.
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub entity: Option<Entity>,
+    pub hex: Option<HexCoord>,
+}
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Selection>()
+            .add_systems(Update, (
+                select_on_click.after(camera::drag_pan_camera),
+                clear_dead_selection,
+                draw_selection,
+            ));
+    }
+}
+
+/// World position under the cursor, if it is over the window.
+pub fn cursor_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, transform) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(transform, cursor)
+}
+
+fn select_on_click(
+    buttons: Res<ButtonInput<MouseButton>>,
+    control: Res<CameraControl>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    units: Query<(Entity, &HexPosition, &Unit), Without<Dead>>,
+    terrain: Res<TerrainMap>,
+    mut selection: ResMut<Selection>,
+) {
+    // Releasing a drag pans the camera; only a still click selects
+    if !buttons.just_released(MouseButton::Left) || control.dragged {
+        return;
+    }
+    let Some(pos) = cursor_world_pos(&windows, &cameras) else {
+        return;
+    };
+
+    let coord = world_to_hex(pos);
+    selection.hex = Some(coord).filter(|c| terrain.in_bounds(*c));
+    selection.entity = units.iter()
+        .find(|(_, p, _)| p.coord == coord)
+        .map(|(e, _, _)| e);
+}
+
+fn clear_dead_selection(
+    mut selection: ResMut<Selection>,
+    units: Query<(), (With<Unit>, Without<Dead>)>,
+) {
+    if selection.entity.is_some_and(|e| units.get(e).is_err()) {
+        selection.entity = None;
+    }
+}
+
+fn draw_selection(
+    selection: Res<Selection>,
+    units: Query<&HexPosition>,
+    mut gizmos: Gizmos,
+) {
+    if let Some(pos) = selection.entity.and_then(|e| units.get(e).ok()) {
+        gizmos.circle_2d(hex_to_world_pos(pos.coord.q, pos.coord.r), 24.0, Color::YELLOW);
+    } else if let Some(hex) = selection.hex {
+        gizmos.circle_2d(hex_to_world_pos(hex.q, hex.r), 26.0, Color::rgba(1.0, 1.0, 1.0, 0.5));
+    }
+}
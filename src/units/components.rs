@@ -1,5 +1,7 @@
 ﻿use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
 use crate::world::HexCoord;
+use crate::world::actions::{Action, ActionResult};
 
 #[derive(Component)]
 pub struct Unit {
@@ -17,31 +19,94 @@ pub struct HexPosition {
 #[derive(Component)]
 pub struct Dead;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub enum Team {
-    Red,
-    Blue,
+/// Per-match number of a unit or structure, handed out in spawn order.
+/// Unlike `Entity` it is never reused within a match, and it is the one id
+/// the inspector, comms panel, match log and replays all show.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UnitId(pub u32);
+
+/// The action a unit took on its most recent turn and how it turned out.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LastAction {
+    /// 0 until the unit has acted.
+    pub turn: u32,
+    pub action: Action,
+    pub result: ActionResult,
+}
+
+impl Default for LastAction {
+    fn default() -> Self {
+        Self {
+            turn: 0,
+            action: Action::Stay,
+            result: ActionResult::Held,
+        }
+    }
+}
+
+/// How far a unit can see, in hexes. Line of sight is still checked against terrain.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Vision {
+    pub range: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// A side in the match: an index into the `TeamRoster`, which holds its name,
+/// colour and controller. Serialized as the bare index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct Team(pub u8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum UnitType {
     Worker,
+    #[default]
     Fighter,
     Scout,
 }
 
-impl Team {
-    pub fn color(&self) -> Color {
+impl UnitType {
+    pub const ALL: [UnitType; 3] = [UnitType::Worker, UnitType::Fighter, UnitType::Scout];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructureKind {
+    Base,
+    Barracks,
+}
+
+impl StructureKind {
+    /// Bases train workers, barracks train combat units.
+    pub fn can_produce(&self, unit_type: UnitType) -> bool {
         match self {
-            Team::Red => Color::rgb(0.8, 0.2, 0.2),
-            Team::Blue => Color::rgb(0.2, 0.2, 0.8),
+            StructureKind::Base => unit_type == UnitType::Worker,
+            StructureKind::Barracks => matches!(unit_type, UnitType::Fighter | UnitType::Scout),
         }
     }
+}
 
-    pub fn tag(&self) -> &'static str {
-        match self {
-            Team::Red => "[RED]",
-            Team::Blue => "[BLUE]",
-        }
+/// A unit currently being trained by a structure.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ProductionOrder {
+    pub unit_type: UnitType,
+    pub turns_left: u32,
+}
+
+/// A building placed on a hex. Blocks movement and can be attacked like a unit.
+#[derive(Component, Clone, Debug)]
+pub struct Structure {
+    pub team: Team,
+    pub kind: StructureKind,
+    pub health: f32,
+    pub max_health: f32,
+    /// Turns until construction finishes; 0 once the structure is operational.
+    pub build_turns_left: u32,
+    pub production: Option<ProductionOrder>,
+}
+
+impl Structure {
+    pub fn is_complete(&self) -> bool {
+        self.build_turns_left == 0
     }
 }
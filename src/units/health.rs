@@ -1,6 +1,6 @@
 ﻿use bevy::prelude::*;
 use bevy::sprite::Sprite;
-use crate::units::{Unit, Dead};
+use crate::units::{Unit, Dead, Structure};
 
 #[derive(Component)]
 pub struct HealthBar;
@@ -31,10 +31,15 @@ pub fn mark_dead_units(
 pub fn update_health_bars(
     mut bars: Query<(&Parent, &mut Sprite), With<HealthBar>>,
     units: Query<&Unit>,
+    structures: Query<&Structure>,
 ) {
     for (parent, mut sprite) in &mut bars {
-        if let Ok(unit) = units.get(parent.get()) {
-            let ratio = (unit.health / unit.max_health).clamp(0.0, 1.0);
+        let health = units.get(parent.get())
+            .map(|u| (u.health, u.max_health))
+            .or_else(|_| structures.get(parent.get()).map(|s| (s.health, s.max_health)));
+        
+        if let Ok((health, max_health)) = health {
+            let ratio = (health / max_health).clamp(0.0, 1.0);
             
             // Scale width
             if let Some(size) = sprite.custom_size.as_mut() {
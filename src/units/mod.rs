@@ -2,8 +2,10 @@
 pub mod spawning;
 pub mod movement;
 pub mod health;
+pub mod teams;
 
 pub use components::*;
+pub use teams::TeamRoster;
 pub use spawning::SpawningPlugin;
 pub use movement::MovementPlugin;
 pub use health::HealthPlugin;
@@ -1,5 +1,6 @@
 ﻿use bevy::prelude::*;
 use crate::world::HexCoord;
+use crate::world::hex_grid::HEX_SIZE;
 use crate::units::{HexPosition, Dead};
 
 pub struct MovementPlugin;
@@ -10,9 +11,10 @@ impl Plugin for MovementPlugin {
     }
 }
 
-pub fn update_unit_positions(
-    mut query: Query<(&HexPosition, &mut Transform), (Changed<HexPosition>, Without<Dead>)>,
-) {
+/// Living units whose hex changed since the last frame.
+type MovedUnits<'w, 's> = Query<'w, 's, (&'static HexPosition, &'static mut Transform), (Changed<HexPosition>, Without<Dead>)>;
+
+pub fn update_unit_positions(mut query: MovedUnits) {
     for (hex_pos, mut transform) in &mut query {
         let world_pos = hex_to_world_pos(hex_pos.coord.q, hex_pos.coord.r);
         transform.translation.x = world_pos.x;
@@ -21,13 +23,72 @@ pub fn update_unit_positions(
 }
 
 pub fn hex_to_world_pos(q: i32, r: i32) -> Vec2 {
-    const HEX_SIZE: f32 = 30.0;
     Vec2::new(
         HEX_SIZE * (f32::sqrt(3.0) * q as f32 + f32::sqrt(3.0) / 2.0 * r as f32),
         HEX_SIZE * (3.0 / 2.0 * r as f32),
     )
 }
 
+/// Inverse of `hex_to_world_pos`: the hex containing a world-space point.
+pub fn world_to_hex(pos: Vec2) -> HexCoord {
+    let q = (f32::sqrt(3.0) / 3.0 * pos.x - 1.0 / 3.0 * pos.y) / HEX_SIZE;
+    let r = (2.0 / 3.0 * pos.y) / HEX_SIZE;
+    
+    // Round in cube coordinates, fixing up whichever component drifted most
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    HexCoord::new(rq as i32, rr as i32)
+}
+
 pub fn hex_distance(a: HexCoord, b: HexCoord) -> i32 {
     ((a.q - b.q).abs() + (a.q + a.r - b.q - b.r).abs() + (a.r - b.r).abs()) / 2
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::world::HexGrid;
+
+    /// A map that isn't square and isn't the default size.
+    fn grid() -> HexGrid {
+        let mut config = GameConfig::default();
+        config.game.map_width = 37;
+        config.game.map_height = 23;
+        HexGrid::from_config(&config)
+    }
+
+    #[test]
+    fn world_to_hex_inverts_hex_to_world_pos() {
+        let grid = grid();
+        for coord in grid.coords() {
+            let center = hex_to_world_pos(coord.q, coord.r);
+            assert_eq!(world_to_hex(center), coord);
+
+            // Anywhere inside the hex's inner circle picks the same hex
+            let inner = HEX_SIZE * f32::sqrt(3.0) / 2.0 * 0.95;
+            for angle in (0..12).map(|i| i as f32 * std::f32::consts::PI / 6.0) {
+                let pos = center + Vec2::new(angle.cos(), angle.sin()) * inner;
+                assert_eq!(world_to_hex(pos), coord, "{:?} near {:?}", pos, coord);
+            }
+        }
+    }
+
+    #[test]
+    fn clicks_past_the_edge_land_outside_the_map() {
+        let grid = grid();
+        let edge = HexCoord::new(grid.width / 2 - 1, 0);
+        assert!(grid.contains(world_to_hex(hex_to_world_pos(edge.q, edge.r))));
+
+        let past = hex_to_world_pos(edge.q + 1, 0);
+        assert!(!grid.contains(world_to_hex(past)));
+        let below = hex_to_world_pos(0, -(grid.height / 2));
+        assert!(!grid.contains(world_to_hex(below)));
+    }
+}
@@ -1,115 +1,169 @@
 ﻿use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
 use bevy::sprite::{MaterialMesh2dBundle, Sprite, SpriteBundle};
 use crate::world::HexCoord;
-use crate::units::{Unit, HexPosition, Team, UnitType};
+use crate::world::TerrainMap;
+use crate::units::{Unit, HexPosition, Team, UnitType, Vision, TeamRoster, UnitId, LastAction};
 use crate::units::health::HealthBar;
 use crate::units::movement::hex_to_world_pos;
 use crate::config::{SimulationConfig, SimulationMode, GameConfig};
+use crate::game::TurnState;
+use crate::game::events::{MatchEvent, MatchEventKind};
 
 pub struct SpawningPlugin;
 
 impl Plugin for SpawningPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_initial_units);
+        app
+            .add_systems(Startup, spawn_initial_units.after(crate::world::terrain::generate_terrain))
+            .add_systems(PreUpdate, spawn_initial_units
+                .in_set(crate::game::session::MatchSetup)
+                .after(crate::game::session::reset_match))
+            .init_resource::<UnitIds>();
     }
 }
 
-fn spawn_initial_units(
-    mut commands: Commands,
-    mut meshes: Option<ResMut<Assets<Mesh>>>,
-    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
-    sim_config: Res<SimulationConfig>,
+/// Next `UnitId` to hand out. Reset along with the rest of the match.
+#[derive(Resource, Default)]
+pub struct UnitIds {
+    next: u32,
+}
+
+/// Everything it takes to bring a unit into the match. Every unit gets its
+/// `UnitId` and is logged as `Spawned` right here, so it can act, and be
+/// logged acting, on the first turn that sees it.
+#[derive(SystemParam)]
+pub struct UnitSpawner<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    pub meshes: Option<ResMut<'w, Assets<Mesh>>>,
+    pub materials: Option<ResMut<'w, Assets<ColorMaterial>>>,
+    ids: ResMut<'w, UnitIds>,
+    match_events: EventWriter<'w, MatchEvent>,
+    turn_state: Res<'w, TurnState>,
+    sim_config: Res<'w, SimulationConfig>,
+    config: Res<'w, GameConfig>,
+    roster: Res<'w, TeamRoster>,
+}
+
+impl UnitSpawner<'_, '_> {
+    pub fn is_visual(&self) -> bool {
+        self.sim_config.modes.default == SimulationMode::Visual
+    }
+
+    /// Take the next id, for a structure; units get theirs from `spawn`.
+    pub fn next_id(&mut self) -> UnitId {
+        let id = UnitId(self.ids.next);
+        self.ids.next += 1;
+        id
+    }
+
+    pub fn spawn(&mut self, team: Team, unit_type: UnitType, coord: HexCoord) -> UnitId {
+        let id = self.next_id();
+
+        let is_visual = self.is_visual();
+        let entity = spawn_unit(
+            &mut self.commands,
+            self.meshes.as_deref_mut().zip(self.materials.as_deref_mut()).filter(|_| is_visual),
+            coord,
+            team,
+            self.roster.color(team),
+            unit_type,
+            Vision { range: self.config.vision.unit_range },
+        );
+        self.commands.entity(entity).insert((id, LastAction::default()));
+        self.match_events.send(MatchEvent::new(self.turn_state.turn, MatchEventKind::Spawned {
+            unit: id.0,
+            team,
+            unit_type,
+            at: coord,
+        }));
+        id
+    }
+}
+
+/// How far from its spawn point a team's starting units may be placed.
+const SPAWN_RADIUS: i32 = 4;
+
+pub(crate) fn spawn_initial_units(
+    mut spawner: UnitSpawner,
     game_config: Res<GameConfig>,
+    roster: Res<TeamRoster>,
+    terrain: Res<TerrainMap>,
 ) {
-    let is_visual = sim_config.modes.default == SimulationMode::Visual;
+    let is_visual = spawner.is_visual();
     let units_per_team = game_config.game.units_per_team;
+    let workers_per_team = if game_config.economy.enabled { game_config.economy.workers_per_team } else { 0 };
+    // The spawn hex itself is left for the team's base
+    let base_reserved = game_config.production.enabled && game_config.production.start_with_base;
     
-    if sim_config.modes.default == SimulationMode::Visual { 
+    if is_visual { 
         println!("[START] GAME START - Spawning {} units per team!", units_per_team); 
         println!("----------------------------------------"); 
     }
     
-    // Spawn Red team
-    for i in 0..units_per_team {
-        let coord = HexCoord { 
-            q: -8 + (i as i32) * 2, 
-            r: -5 
-        };
-        if sim_config.modes.default == SimulationMode::Visual { 
-            println!("[RED]  Spawning RED  Fighter {} at ({}, {})", i + 1, coord.q, coord.r); 
-        }
+    for info in roster.iter() {
+        let mut hexes = info.spawn.spiral(SPAWN_RADIUS)
+            .filter(|c| terrain.is_passable(*c) && !(base_reserved && *c == info.spawn));
         
-        if is_visual {
-            // Visual mode with meshes
-            if let (Some(ref mut meshes), Some(ref mut materials)) = (&mut meshes, &mut materials) {
-                let unit_mesh = meshes.add(Circle::new(15.0));
-                spawn_visual_unit(
-                    &mut commands,
-                    coord,
-                    Team::Red,
-                    UnitType::Fighter,
-                    unit_mesh,
-                    materials.add(ColorMaterial::from(Team::Red.color())),
-                );
-            }
-        } else {
-            // Headless mode without visuals
-            spawn_headless_unit(
-                &mut commands,
-                coord,
-                Team::Red,
-                UnitType::Fighter,
-            );
-        }
-    }
-    
-    // Spawn Blue team
-    for i in 0..units_per_team {
-        let coord = HexCoord { 
-            q: -8 + (i as i32) * 2, 
-            r: 5 
-        };
-        if sim_config.modes.default == SimulationMode::Visual { 
-            println!("[BLUE] Spawning BLUE Fighter {} at ({}, {})", i + 1, coord.q, coord.r); 
-        }
+        // Workers closest to home, fighters around them
+        let unit_types = std::iter::repeat_n(UnitType::Worker, workers_per_team)
+            .chain(std::iter::repeat_n(UnitType::Fighter, units_per_team));
         
-        if is_visual {
-            // Visual mode with meshes
-            if let (Some(ref mut meshes), Some(ref mut materials)) = (&mut meshes, &mut materials) {
-                let unit_mesh = meshes.add(Circle::new(15.0));
-                spawn_visual_unit(
-                    &mut commands,
-                    coord,
-                    Team::Blue,
-                    UnitType::Fighter,
-                    unit_mesh,
-                    materials.add(ColorMaterial::from(Team::Blue.color())),
-                );
+        for (i, unit_type) in unit_types.enumerate() {
+            let Some(coord) = hexes.next() else {
+                warn!("No room left around {}'s spawn point", info.name);
+                break;
+            };
+            if is_visual { 
+                println!("{} Spawning {} {:?} {} at ({}, {})", 
+                    roster.tag(info.team), info.name, unit_type, i + 1, coord.q, coord.r); 
             }
-        } else {
-            // Headless mode without visuals
-            spawn_headless_unit(
-                &mut commands,
-                coord,
-                Team::Blue,
-                UnitType::Fighter,
-            );
+            
+            spawner.spawn(info.team, unit_type, coord);
         }
     }
     
-    if sim_config.modes.default == SimulationMode::Visual { 
+    if is_visual { 
         println!("----------------------------------------"); 
     }
 }
 
+/// Spawn one unit with visuals when render assets are available, headless otherwise.
+fn spawn_unit(
+    commands: &mut Commands,
+    assets: Option<(&mut Assets<Mesh>, &mut Assets<ColorMaterial>)>,
+    coord: HexCoord,
+    team: Team,
+    color: Color,
+    unit_type: UnitType,
+    vision: Vision,
+) -> Entity {
+    if let Some((meshes, materials)) = assets {
+        let radius = if unit_type == UnitType::Worker { 10.0 } else { 15.0 };
+        let unit_mesh = meshes.add(Circle::new(radius));
+        spawn_visual_unit(
+            commands,
+            coord,
+            team,
+            unit_type,
+            vision,
+            unit_mesh,
+            materials.add(ColorMaterial::from(color)),
+        )
+    } else {
+        spawn_headless_unit(commands, coord, team, unit_type, vision)
+    }
+}
+
 fn spawn_visual_unit(
     commands: &mut Commands,
     coord: HexCoord,
     team: Team,
     unit_type: UnitType,
+    vision: Vision,
     mesh: Handle<Mesh>,
     material: Handle<ColorMaterial>,
-) {
+) -> Entity {
     let world_pos = hex_to_world_pos(coord.q, coord.r);
     
     let unit_entity = commands.spawn((
@@ -126,6 +180,7 @@ fn spawn_visual_unit(
             max_health: 100.0,
         },
         HexPosition { coord },
+        vision,
     )).id();
     
     // Spawn health bar as child
@@ -143,6 +198,7 @@ fn spawn_visual_unit(
     )).id();
     
     commands.entity(unit_entity).add_child(health_bar);
+    unit_entity
 }
 
 fn spawn_headless_unit(
@@ -150,7 +206,8 @@ fn spawn_headless_unit(
     coord: HexCoord,
     team: Team,
     unit_type: UnitType,
-) {
+    vision: Vision,
+) -> Entity {
     // In headless mode, just spawn the unit data without visuals
     commands.spawn((
         Unit {
@@ -160,7 +217,8 @@ fn spawn_headless_unit(
             max_health: 100.0,
         },
         HexPosition { coord },
+        vision,
         // Add a transform even in headless for spatial queries
         Transform::default(),
-    ));
+    )).id()
 }
@@ -0,0 +1,126 @@
+﻿use bevy::prelude::*;
+use crate::config::GameConfig;
+use crate::config::game_config::VictoryMode;
+use crate::units::Team;
+use crate::world::HexCoord;
+
+/// Display and control details of one team, built from its `TeamSettings`.
+#[derive(Clone, Debug)]
+pub struct TeamInfo {
+    pub team: Team,
+    pub name: String,
+    pub color: Color,
+    pub controller: String,
+    pub spawn: HexCoord,
+    /// Which side the team is on. Unique per team in free-for-all.
+    pub side: u32,
+}
+
+/// Every team in the match, indexed by `Team`.
+#[derive(Resource, Clone, Debug)]
+pub struct TeamRoster {
+    teams: Vec<TeamInfo>,
+}
+
+impl TeamRoster {
+    pub fn from_config(config: &GameConfig) -> Self {
+        let alliances = config.victory.mode == VictoryMode::Alliances;
+        let teams = config.teams.iter()
+            .enumerate()
+            .map(|(i, settings)| {
+                // Teams without an alliance get a side of their own, clear of any alliance id
+                let side = settings.alliance
+                    .filter(|_| alliances)
+                    .unwrap_or(u32::MAX - i as u32);
+                TeamInfo {
+                    team: Team(i as u8),
+                    name: settings.name.clone(),
+                    color: Color::rgb(settings.color[0], settings.color[1], settings.color[2]),
+                    controller: settings.controller.clone(),
+                    spawn: HexCoord::new(settings.spawn.0, settings.spawn.1),
+                    side,
+                }
+            })
+            .collect();
+        Self { teams }
+    }
+
+    pub fn teams(&self) -> impl Iterator<Item = Team> + '_ {
+        self.teams.iter().map(|info| info.team)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TeamInfo> {
+        self.teams.iter()
+    }
+
+    pub fn get(&self, team: Team) -> Option<&TeamInfo> {
+        self.teams.get(team.0 as usize)
+    }
+
+    pub fn name(&self, team: Team) -> &str {
+        self.get(team).map_or("Unknown", |info| info.name.as_str())
+    }
+
+    pub fn color(&self, team: Team) -> Color {
+        self.get(team).map_or(Color::GRAY, |info| info.color)
+    }
+
+    /// Log prefix such as `[RED]`.
+    pub fn tag(&self, team: Team) -> String {
+        format!("[{}]", self.name(team).to_uppercase())
+    }
+
+    pub fn side(&self, team: Team) -> u32 {
+        self.get(team).map_or(u32::MAX - team.0 as u32, |info| info.side)
+    }
+
+    /// Same team, or allies under `victory.mode = "alliances"`.
+    pub fn allied(&self, a: Team, b: Team) -> bool {
+        a == b || self.side(a) == self.side(b)
+    }
+
+    /// Every team on the same side as `team`, itself included.
+    pub fn side_members(&self, team: Team) -> Vec<Team> {
+        self.teams().filter(|t| self.allied(team, *t)).collect()
+    }
+}
+
+/// Derive the roster once every plugin has had its chance to replace `GameConfig`.
+pub fn setup_team_roster(mut commands: Commands, config: Res<GameConfig>) {
+    commands.insert_resource(TeamRoster::from_config(&config));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roster(mode: VictoryMode) -> TeamRoster {
+        let mut config = GameConfig::default();
+        config.victory.mode = mode;
+        let team = config.teams[0].clone();
+        config.teams = [Some(1), Some(2), Some(1), None]
+            .into_iter()
+            .map(|alliance| crate::config::game_config::TeamSettings { alliance, ..team.clone() })
+            .collect();
+        TeamRoster::from_config(&config)
+    }
+
+    #[test]
+    fn alliances_put_teams_on_one_side() {
+        let roster = roster(VictoryMode::Alliances);
+        assert!(roster.allied(Team(0), Team(2)));
+        assert!(!roster.allied(Team(0), Team(1)));
+        assert!(!roster.allied(Team(3), Team(0)));
+        assert!(roster.allied(Team(3), Team(3)));
+        assert_eq!(roster.side_members(Team(2)), vec![Team(0), Team(2)]);
+        assert_eq!(roster.side_members(Team(3)), vec![Team(3)]);
+    }
+
+    #[test]
+    fn free_for_all_ignores_alliances() {
+        let roster = roster(VictoryMode::FreeForAll);
+        assert!(!roster.allied(Team(0), Team(2)));
+        assert!(roster.allied(Team(1), Team(1)));
+        assert_eq!(roster.side_members(Team(0)), vec![Team(0)]);
+    }
+}
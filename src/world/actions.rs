@@ -1,11 +1,37 @@
 ﻿use serde::{Serialize, Deserialize};
+use crate::units::{StructureKind, UnitType};
+use crate::world::HexCoord;
+use crate::world::resource::ResourceKind;
 
 /// High-level commands returned by an `AiController`.
 ///
 /// For Sprint 1 we only need `Stay` and `Move`.  
 /// `Move` stores axial **dq, dr** – i.e. the delta from the unit's current hex.
+/// `Gather` harvests a resource node on or next to a worker's hex.
+/// `Build` has a worker found a structure on an adjacent hex, and
+/// `Produce` queues a unit at a structure.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Action {
     Stay,
     Move(i32 /*dq*/, i32 /*dr*/),
+    Gather,
+    Build(StructureKind, HexCoord),
+    Produce(UnitType),
+}
+
+/// What became of a unit's `Action` once the turn resolved it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActionResult {
+    Held,
+    Moved,
+    /// The target hex was impassable or occupied.
+    Blocked,
+    Gathered(ResourceKind, i32),
+    /// Not a worker, or no node left in reach.
+    NothingToGather,
+    BuildStarted,
+    /// Wrong unit type, bad site or not enough resources.
+    BuildRejected,
+    /// The action doesn't apply to units, e.g. `Produce`.
+    Ignored,
 }
@@ -1,33 +1,129 @@
 ﻿use bevy::prelude::*;
 use bevy::sprite::MaterialMesh2dBundle;
 use bevy::render::render_asset::RenderAssetUsages;
-use crate::config::{SimulationConfig, SimulationMode};
+use serde::{Serialize, Deserialize};
+use crate::config::{SimulationConfig, SimulationMode, GameConfig};
+use crate::world::terrain::{TerrainMap, generate_terrain};
 
-const HEX_SIZE: f32 = 30.0;
-const GRID_WIDTH: i32 = 20;
-const GRID_HEIGHT: i32 = 15;
+pub(crate) const HEX_SIZE: f32 = 30.0;
 
-#[derive(Component, Clone, Copy, Debug)]
+/// The six axial neighbour offsets, in the order every system iterates them.
+pub const HEX_DIRECTIONS: [(i32, i32); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct HexCoord {
     pub q: i32,  // Axial coordinates
     pub r: i32,
 }
 
+impl HexCoord {
+    pub fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn offset(&self, dq: i32, dr: i32) -> Self {
+        Self { q: self.q + dq, r: self.r + dr }
+    }
+
+    pub fn neighbors(&self) -> impl Iterator<Item = HexCoord> + '_ {
+        HEX_DIRECTIONS.iter().map(move |(dq, dr)| self.offset(*dq, *dr))
+    }
+
+    /// Hexes at exactly distance `radius`, walking round from the south-west corner.
+    pub fn ring(&self, radius: i32) -> Vec<HexCoord> {
+        if radius == 0 {
+            return vec![*self];
+        }
+
+        let (sq, sr) = HEX_DIRECTIONS[4];
+        let mut hex = self.offset(sq * radius, sr * radius);
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for (dq, dr) in HEX_DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.offset(dq, dr);
+            }
+        }
+        ring
+    }
+
+    /// Every hex within `radius`, nearest rings first.
+    pub fn spiral(&self, radius: i32) -> impl Iterator<Item = HexCoord> + '_ {
+        (0..=radius).flat_map(move |r| self.ring(r))
+    }
+
+    /// Every hex on the straight line from `self` to `other`, both ends included.
+    pub fn line_to(&self, other: HexCoord) -> Vec<HexCoord> {
+        let n = crate::units::movement::hex_distance(*self, other);
+        if n == 0 {
+            return vec![*self];
+        }
+
+        // Nudge the endpoints off hex edges so ties always round the same way
+        let (aq, ar) = (self.q as f32 + 1e-6, self.r as f32 + 1e-6);
+        let (bq, br) = (other.q as f32 + 1e-6, other.r as f32 + 1e-6);
+
+        (0..=n)
+            .map(|i| {
+                let t = i as f32 / n as f32;
+                hex_round(aq + (bq - aq) * t, ar + (br - ar) * t)
+            })
+            .collect()
+    }
+}
+
+fn hex_round(q: f32, r: f32) -> HexCoord {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+
+    HexCoord { q: rq as i32, r: rr as i32 }
+}
+
 #[derive(Component)]
 pub struct HexTile {
     pub coord: HexCoord,
 }
 
+/// Playable bounds of the map, derived from `game.map_width` / `game.map_height`.
+#[derive(Resource, Clone, Copy, Debug)]
 pub struct HexGrid {
     pub width: i32,
     pub height: i32,
 }
 
+impl HexGrid {
+    pub fn from_config(config: &GameConfig) -> Self {
+        Self {
+            width: config.game.map_width,
+            height: config.game.map_height,
+        }
+    }
+
+    /// Same bounds rule the turn manager has always used for moves.
+    pub fn contains(&self, coord: HexCoord) -> bool {
+        coord.q.abs() < self.width / 2 && coord.r.abs() < self.height / 2
+    }
+
+    /// Every hex inside the playable bounds.
+    pub fn coords(&self) -> impl Iterator<Item = HexCoord> {
+        let max_q = self.width / 2 - 1;
+        let max_r = self.height / 2 - 1;
+        (-max_q..=max_q).flat_map(move |q| (-max_r..=max_r).map(move |r| HexCoord { q, r }))
+    }
+}
+
 pub struct HexGridPlugin;
 
 impl Plugin for HexGridPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_hex_grid);
+        app.add_systems(Startup, (generate_terrain, spawn_hex_grid).chain());
     }
 }
 
@@ -35,7 +131,8 @@ fn spawn_hex_grid(
     mut commands: Commands,
     meshes: Option<ResMut<Assets<Mesh>>>,
     materials: Option<ResMut<Assets<ColorMaterial>>>,
-    sim_config: Res<SimulationConfig>,
+    (config, sim_config): (Res<GameConfig>, Res<SimulationConfig>),
+    terrain: Res<TerrainMap>,
 ) {
     // Only spawn visual hex grid in visual mode
     if sim_config.modes.default != SimulationMode::Visual {
@@ -49,22 +146,19 @@ fn spawn_hex_grid(
         let mesh_handle = meshes.add(hex_mesh);
         
         // Spawn hexagons
-        for q in -GRID_WIDTH/2..GRID_WIDTH/2 {
-            for r in -GRID_HEIGHT/2..GRID_HEIGHT/2 {
-                let pos = hex_to_world_pos(q, r);
-                
-                commands.spawn((
-                    MaterialMesh2dBundle {
-                        mesh: mesh_handle.clone().into(),
-                        material: materials.add(ColorMaterial::from(Color::rgb(0.2, 0.3, 0.4))),
-                        transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 0.0)),
-                        ..default()
-                    },
-                    HexTile { 
-                        coord: HexCoord { q, r } 
-                    },
-                ));
-            }
+        for coord in HexGrid::from_config(&config).coords() {
+            let pos = hex_to_world_pos(coord.q, coord.r);
+            let color = terrain.get(coord).color();
+            
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: mesh_handle.clone().into(),
+                    material: materials.add(ColorMaterial::from(color)),
+                    transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 0.0)),
+                    ..default()
+                },
+                HexTile { coord },
+            ));
         }
     }
 }
@@ -75,7 +169,7 @@ fn hex_to_world_pos(q: i32, r: i32) -> Vec2 {
     Vec2::new(x, y)
 }
 
-fn create_hex_mesh(size: f32) -> Mesh {
+pub(crate) fn create_hex_mesh(size: f32) -> Mesh {
     let mut mesh = Mesh::new(
         bevy::render::render_resource::PrimitiveTopology::TriangleList,
         RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD
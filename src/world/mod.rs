@@ -1,8 +1,14 @@
 ﻿pub mod hex_grid;
 pub mod resource;
 pub mod actions;
+pub mod terrain;
+pub mod pathfinding;
+pub mod visibility;
 
 pub use hex_grid::*;
+pub use terrain::TerrainMap;
+pub use pathfinding::Pathfinder;
+pub use visibility::TeamVisibility;
 
 pub mod spatial_index;
 pub use spatial_index::{SpatialIndex, SpatialIndexPlugin};
\ No newline at end of file
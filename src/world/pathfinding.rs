@@ -0,0 +1,368 @@
+﻿use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::world::actions::Action;
+use crate::world::hex_grid::HexCoord;
+use crate::world::terrain::TerrainMap;
+use crate::units::movement::hex_distance;
+
+/// Knobs for a single path query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct PathOptions {
+    /// Treat hexes occupied at the start of the turn as walls (start and goal excepted).
+    pub avoid_units: bool,
+    /// Give up on paths more expensive than this.
+    pub max_cost: Option<u32>,
+}
+
+impl PathOptions {
+    pub fn avoiding_units() -> Self {
+        Self { avoid_units: true, ..Default::default() }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    /// Hexes to walk through, excluding the start and including the goal.
+    pub steps: Vec<HexCoord>,
+}
+
+impl Path {
+    pub fn next_step(&self) -> Option<HexCoord> {
+        self.steps.first().copied()
+    }
+
+    /// The `Action` that takes a unit standing on `from` onto the first step.
+    pub fn first_move(&self, from: HexCoord) -> Action {
+        match self.next_step() {
+            Some(next) => Action::Move(next.q - from.q, next.r - from.r),
+            None => Action::Stay,
+        }
+    }
+}
+
+/// Distance-to-goal for every reachable hex, shared by any number of units
+/// heading for the same destination.
+#[derive(Clone, Debug)]
+pub struct FlowField {
+    costs: HashMap<HexCoord, u32>,
+}
+
+impl FlowField {
+    pub fn cost(&self, coord: HexCoord) -> Option<u32> {
+        self.costs.get(&coord).copied()
+    }
+
+    /// Cheapest neighbour of `from`, skipping any hex in `blocked`.
+    pub fn best_step(&self, from: HexCoord, blocked: &HashSet<HexCoord>) -> Option<HexCoord> {
+        let here = self.cost(from)?;
+        from.neighbors()
+            .filter(|n| !blocked.contains(n))
+            .filter_map(|n| self.cost(n).map(|c| (n, c)))
+            .filter(|(_, c)| *c < here)
+            .min_by_key(|(_, c)| *c)
+            .map(|(n, _)| n)
+    }
+
+    pub fn direction(&self, from: HexCoord, blocked: &HashSet<HexCoord>) -> Action {
+        match self.best_step(from, blocked) {
+            Some(next) => Action::Move(next.q - from.q, next.r - from.r),
+            None => Action::Stay,
+        }
+    }
+}
+
+/// Per-turn pathfinding over the terrain map.
+///
+/// Built by the turn manager at the start of every turn with that turn's
+/// occupancy, so cached paths and flow fields never outlive the state they
+/// were computed against.
+pub struct Pathfinder<'a> {
+    terrain: &'a TerrainMap,
+    occupied: HashSet<HexCoord>,
+    paths: HashMap<(HexCoord, HexCoord, PathOptions), Option<Path>>,
+    flow_fields: HashMap<Vec<HexCoord>, FlowField>,
+}
+
+impl<'a> Pathfinder<'a> {
+    pub fn new(terrain: &'a TerrainMap, occupied: HashSet<HexCoord>) -> Self {
+        Self {
+            terrain,
+            occupied,
+            paths: HashMap::new(),
+            flow_fields: HashMap::new(),
+        }
+    }
+
+    /// A* from `from` to `to`. Results are cached for the rest of the turn.
+    pub fn find_path(&mut self, from: HexCoord, to: HexCoord, options: PathOptions) -> Option<Path> {
+        let key = (from, to, options);
+        if let Some(cached) = self.paths.get(&key) {
+            return cached.clone();
+        }
+
+        let path = self.search(from, to, options);
+        self.paths.insert(key, path.clone());
+        path
+    }
+
+    fn search(&self, from: HexCoord, to: HexCoord, options: PathOptions) -> Option<Path> {
+        if from == to {
+            return Some(Path { steps: Vec::new() });
+        }
+        if !self.terrain.is_passable(to) {
+            return None;
+        }
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<HexCoord, HexCoord> = HashMap::new();
+        let mut best: HashMap<HexCoord, u32> = HashMap::new();
+
+        best.insert(from, 0);
+        open.push(Reverse((hex_distance(from, to) as u32, 0u32, from.q, from.r)));
+
+        while let Some(Reverse((_, cost, q, r))) = open.pop() {
+            let current = HexCoord { q, r };
+            if current == to {
+                return Some(Path {
+                    steps: reconstruct(&came_from, from, to),
+                });
+            }
+            if cost > best.get(&current).copied().unwrap_or(u32::MAX) {
+                continue;
+            }
+
+            for next in current.neighbors() {
+                let Some(step_cost) = self.terrain.move_cost(next) else {
+                    continue;
+                };
+                if options.avoid_units && next != to && self.occupied.contains(&next) {
+                    continue;
+                }
+
+                let new_cost = cost + step_cost;
+                if options.max_cost.is_some_and(|max| new_cost > max) {
+                    continue;
+                }
+                if new_cost < best.get(&next).copied().unwrap_or(u32::MAX) {
+                    best.insert(next, new_cost);
+                    came_from.insert(next, current);
+                    let estimate = new_cost + hex_distance(next, to) as u32;
+                    open.push(Reverse((estimate, new_cost, next.q, next.r)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Dijkstra outward from every goal at once, ignoring occupancy so the
+    /// field stays valid while units move through it.
+    pub fn flow_field(&mut self, goals: &[HexCoord]) -> &FlowField {
+        let mut key = goals.to_vec();
+        key.sort_by_key(|c| (c.q, c.r));
+        key.dedup();
+
+        if !self.flow_fields.contains_key(&key) {
+            let field = self.build_flow_field(&key);
+            self.flow_fields.insert(key.clone(), field);
+        }
+        &self.flow_fields[&key]
+    }
+
+    fn build_flow_field(&self, goals: &[HexCoord]) -> FlowField {
+        let mut costs = HashMap::new();
+        let mut open = BinaryHeap::new();
+
+        for goal in goals {
+            costs.insert(*goal, 0);
+            open.push(Reverse((0u32, goal.q, goal.r)));
+        }
+
+        while let Some(Reverse((cost, q, r))) = open.pop() {
+            let current = HexCoord { q, r };
+            if cost > costs.get(&current).copied().unwrap_or(u32::MAX) {
+                continue;
+            }
+
+            // Walking current -> goal enters `current`'s downhill neighbour, so the
+            // cost of stepping from `next` into `current` is `current`'s terrain.
+            let Some(enter_cost) = self.terrain.move_cost(current) else {
+                continue;
+            };
+            for next in current.neighbors() {
+                if !self.terrain.is_passable(next) {
+                    continue;
+                }
+                let new_cost = cost + enter_cost;
+                if new_cost < costs.get(&next).copied().unwrap_or(u32::MAX) {
+                    costs.insert(next, new_cost);
+                    open.push(Reverse((new_cost, next.q, next.r)));
+                }
+            }
+        }
+
+        FlowField { costs }
+    }
+}
+
+fn reconstruct(came_from: &HashMap<HexCoord, HexCoord>, from: HexCoord, to: HexCoord) -> Vec<HexCoord> {
+    let mut steps = vec![to];
+    let mut current = to;
+    while let Some(prev) = came_from.get(&current) {
+        if *prev == from {
+            break;
+        }
+        steps.push(*prev);
+        current = *prev;
+    }
+    steps.reverse();
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::hex_grid::HexGrid;
+    use crate::world::terrain::TerrainType;
+
+    /// A 9x9 hex map (q and r in -4..=4) with the given non-plains tiles.
+    fn map(tiles: impl IntoIterator<Item = (HexCoord, TerrainType)>) -> TerrainMap {
+        TerrainMap::from_tiles(HexGrid { width: 10, height: 10 }, tiles)
+    }
+
+    /// Terrain of every hex in column `q` whose `r` falls in `rows`.
+    fn column(q: i32, rows: std::ops::RangeInclusive<i32>, terrain: TerrainType) -> Vec<(HexCoord, TerrainType)> {
+        rows.map(|r| (HexCoord::new(q, r), terrain)).collect()
+    }
+
+    fn cost(terrain: &TerrainMap, path: &Path) -> u32 {
+        path.steps.iter().map(|c| terrain.move_cost(*c).unwrap()).sum()
+    }
+
+    #[test]
+    fn open_ground_costs_one_per_hex() {
+        let terrain = map([]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let path = pathfinder.find_path(HexCoord::new(-2, 0), HexCoord::new(2, 0), PathOptions::default()).unwrap();
+
+        assert_eq!(path.steps.len(), 4);
+        assert_eq!(cost(&terrain, &path), 4);
+        assert_eq!(path.steps.last(), Some(&HexCoord::new(2, 0)));
+        assert!(matches!(path.first_move(HexCoord::new(-2, 0)), Action::Move(1, 0)));
+    }
+
+    #[test]
+    fn same_hex_is_an_empty_path() {
+        let terrain = map([]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let path = pathfinder.find_path(HexCoord::new(1, 1), HexCoord::new(1, 1), PathOptions::default()).unwrap();
+
+        assert!(path.steps.is_empty());
+        assert!(matches!(path.first_move(HexCoord::new(1, 1)), Action::Stay));
+    }
+
+    #[test]
+    fn forest_costs_two_to_enter() {
+        let terrain = map(column(0, -4..=4, TerrainType::Forest));
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let path = pathfinder.find_path(HexCoord::new(-1, 0), HexCoord::new(1, 0), PathOptions::default()).unwrap();
+
+        assert_eq!(path.steps.len(), 2);
+        assert_eq!(cost(&terrain, &path), 3);
+    }
+
+    #[test]
+    fn routes_around_a_wall() {
+        let terrain = map(column(0, -4..=2, TerrainType::Mountain));
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let path = pathfinder.find_path(HexCoord::new(-2, 0), HexCoord::new(2, 0), PathOptions::default()).unwrap();
+
+        assert!(path.steps.iter().all(|c| terrain.is_passable(*c)));
+        assert!(path.steps.iter().any(|c| c.q == 0 && c.r >= 3));
+        assert!(path.steps.len() > 4);
+        assert_eq!(cost(&terrain, &path), path.steps.len() as u32);
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let mut tiles = column(0, -4..=4, TerrainType::Mountain);
+        tiles.push((HexCoord::new(-2, 2), TerrainType::Water));
+        let terrain = map(tiles);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let from = HexCoord::new(-2, 0);
+
+        assert!(pathfinder.find_path(from, HexCoord::new(2, 0), PathOptions::default()).is_none());
+        assert!(pathfinder.find_path(from, HexCoord::new(-2, 2), PathOptions::default()).is_none());
+        assert!(pathfinder.find_path(from, HexCoord::new(-9, 0), PathOptions::default()).is_none());
+    }
+
+    #[test]
+    fn max_cost_cuts_off_longer_paths() {
+        let terrain = map([]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let (from, to) = (HexCoord::new(-2, 0), HexCoord::new(2, 0));
+
+        let within = PathOptions { max_cost: Some(4), ..Default::default() };
+        let beyond = PathOptions { max_cost: Some(3), ..Default::default() };
+        assert!(pathfinder.find_path(from, to, within).is_some());
+        assert!(pathfinder.find_path(from, to, beyond).is_none());
+    }
+
+    #[test]
+    fn avoiding_units_detours_but_still_reaches_an_occupied_goal() {
+        let terrain = map([]);
+        let (from, to) = (HexCoord::new(-1, 0), HexCoord::new(1, 0));
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::from([HexCoord::new(0, 0), to]));
+
+        let through = pathfinder.find_path(from, to, PathOptions::default()).unwrap();
+        assert_eq!(through.steps, vec![HexCoord::new(0, 0), to]);
+
+        let around = pathfinder.find_path(from, to, PathOptions::avoiding_units()).unwrap();
+        assert_eq!(around.steps.len(), 3);
+        assert!(!around.steps.contains(&HexCoord::new(0, 0)));
+        assert_eq!(around.steps.last(), Some(&to));
+    }
+
+    #[test]
+    fn flow_field_counts_the_terrain_walked_into() {
+        let terrain = map([
+            (HexCoord::new(1, 0), TerrainType::Forest),
+            (HexCoord::new(2, 1), TerrainType::Mountain),
+        ]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let field = pathfinder.flow_field(&[HexCoord::new(2, 0)]);
+
+        assert_eq!(field.cost(HexCoord::new(2, 0)), Some(0));
+        assert_eq!(field.cost(HexCoord::new(1, 0)), Some(1));
+        assert_eq!(field.cost(HexCoord::new(0, 0)), Some(3));
+        assert_eq!(field.cost(HexCoord::new(2, 1)), None);
+        assert!(matches!(field.direction(HexCoord::new(1, 0), &HashSet::new()), Action::Move(1, 0)));
+        assert!(matches!(field.direction(HexCoord::new(2, 0), &HashSet::new()), Action::Stay));
+    }
+
+    #[test]
+    fn flow_field_steps_around_blocked_hexes() {
+        let terrain = map([]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let field = pathfinder.flow_field(&[HexCoord::new(2, 0)]);
+        let from = HexCoord::new(0, 1);
+
+        assert_eq!(field.cost(from), Some(2));
+        let blocked = HashSet::from([HexCoord::new(1, 0)]);
+        assert_eq!(field.best_step(from, &blocked), Some(HexCoord::new(1, 1)));
+
+        let boxed_in = HashSet::from([HexCoord::new(1, 0), HexCoord::new(1, 1)]);
+        assert!(matches!(field.direction(from, &boxed_in), Action::Stay));
+    }
+
+    #[test]
+    fn flow_field_heads_for_the_nearest_goal() {
+        let terrain = map([]);
+        let mut pathfinder = Pathfinder::new(&terrain, HashSet::new());
+        let field = pathfinder.flow_field(&[HexCoord::new(3, 0), HexCoord::new(-3, 0)]);
+
+        assert_eq!(field.cost(HexCoord::new(0, 0)), Some(3));
+        assert!(matches!(field.direction(HexCoord::new(-1, 0), &HashSet::new()), Action::Move(-1, 0)));
+        assert!(matches!(field.direction(HexCoord::new(1, 0), &HashSet::new()), Action::Move(1, 0)));
+    }
+}
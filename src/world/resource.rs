@@ -1,4 +1,11 @@
-﻿#[derive(Debug, Clone)]
+﻿use rand::Rng;
+use serde::{Serialize, Deserialize};
+use crate::config::GameConfig;
+use crate::world::hex_grid::HexCoord;
+use crate::world::terrain::TerrainMap;
+use crate::units::movement::hex_distance;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
     pub energy: i32,
     pub metal: i32,
@@ -13,4 +20,191 @@ impl Resource {
             knowledge: 0,
         }
     }
+
+    pub fn can_afford(&self, cost: &Resource) -> bool {
+        self.energy >= cost.energy && self.metal >= cost.metal && self.knowledge >= cost.knowledge
+    }
+
+    /// Deduct `cost` if the stockpile covers it. Returns whether it did.
+    pub fn spend(&mut self, cost: &Resource) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.energy -= cost.energy;
+        self.metal -= cost.metal;
+        self.knowledge -= cost.knowledge;
+        true
+    }
+
+    pub fn add(&mut self, kind: ResourceKind, amount: i32) {
+        match kind {
+            ResourceKind::Energy => self.energy += amount,
+            ResourceKind::Metal => self.metal += amount,
+            ResourceKind::Knowledge => self.knowledge += amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    Energy,
+    Metal,
+    Knowledge,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 3] = [ResourceKind::Energy, ResourceKind::Metal, ResourceKind::Knowledge];
+}
+
+/// A harvestable deposit sitting on one hex.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceNode {
+    pub coord: HexCoord,
+    pub kind: ResourceKind,
+    pub amount: i32,
+    pub max_amount: i32,
+}
+
+impl ResourceNode {
+    pub fn is_depleted(&self) -> bool {
+        self.amount <= 0
+    }
+}
+
+/// Every resource node on the map.
+#[derive(bevy::prelude::Resource, Debug, Clone, Default)]
+pub struct ResourceField {
+    pub nodes: Vec<ResourceNode>,
+}
+
+impl ResourceField {
+    /// Give every team `nodes_per_kind` nodes of each kind. Each set is
+    /// placed at one distance, the same from every team's spawn, on passable
+    /// hexes outside the spawn areas and no nearer any other team's spawn, so
+    /// however the spawns are laid out no team starts closer to the economy.
+    pub fn generate(config: &GameConfig, terrain: &TerrainMap, rng: &mut impl Rng) -> Self {
+        let economy = &config.economy;
+        let mut field = Self::default();
+        if !economy.enabled {
+            return field;
+        }
+
+        let spawns = config.spawn_points();
+        let clearance = config.terrain.spawn_clearance;
+        let spread = (terrain.grid.width.min(terrain.grid.height) / 4).max(1);
+
+        for kind in ResourceKind::ALL {
+            for _ in 0..economy.nodes_per_kind {
+                // A handful of retries is plenty on any sensible map
+                for _ in 0..16 {
+                    let distance = rng.gen_range(clearance + 1..=clearance + spread);
+                    let Some(set) = field.equidistant_set(&spawns, distance, terrain, rng) else {
+                        continue;
+                    };
+                    field.nodes.extend(set.into_iter().map(|coord| ResourceNode {
+                        coord,
+                        kind,
+                        amount: economy.node_amount,
+                        max_amount: economy.node_amount,
+                    }));
+                    break;
+                }
+            }
+        }
+
+        field
+    }
+
+    /// One free hex per spawn, each exactly `distance` from its own spawn
+    /// and at least that far from the others, or `None` if some spawn has
+    /// no such hex.
+    fn equidistant_set(
+        &self,
+        spawns: &[HexCoord],
+        distance: i32,
+        terrain: &TerrainMap,
+        rng: &mut impl Rng,
+    ) -> Option<Vec<HexCoord>> {
+        let mut set: Vec<HexCoord> = Vec::with_capacity(spawns.len());
+        for spawn in spawns {
+            let candidates: Vec<HexCoord> = spawn.ring(distance).into_iter()
+                .filter(|c| terrain.is_passable(*c) && self.node_at(*c).is_none() && !set.contains(c))
+                .filter(|c| spawns.iter().all(|s| hex_distance(*s, *c) >= distance))
+                .collect();
+            if candidates.is_empty() {
+                return None;
+            }
+            set.push(candidates[rng.gen_range(0..candidates.len())]);
+        }
+        Some(set)
+    }
+
+    pub fn node_at(&self, coord: HexCoord) -> Option<&ResourceNode> {
+        self.nodes.iter().find(|n| n.coord == coord)
+    }
+
+    /// Regrow every node by `amount`, up to its starting size.
+    pub fn regenerate(&mut self, amount: i32) {
+        for node in &mut self.nodes {
+            node.amount = (node.amount + amount).min(node.max_amount);
+        }
+    }
+
+    /// Refill every node, as at the start of a match.
+    pub fn replenish(&mut self) {
+        for node in &mut self.nodes {
+            node.amount = node.max_amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use crate::world::hex_grid::HexGrid;
+
+    fn economy_config() -> GameConfig {
+        let mut config = GameConfig::default();
+        config.economy.enabled = true;
+        config
+    }
+
+    /// Every team gets each set's node at the same distance from its spawn,
+    /// and no other spawn is closer to it.
+    fn assert_fair(config: &GameConfig) {
+        let spawns = config.spawn_points();
+        let terrain = TerrainMap::from_tiles(HexGrid::from_config(config), []);
+        for seed in 0..20 {
+            let field = ResourceField::generate(config, &terrain, &mut SmallRng::seed_from_u64(seed));
+            assert_eq!(field.nodes.len(), spawns.len() * ResourceKind::ALL.len() * config.economy.nodes_per_kind);
+            for set in field.nodes.chunks(spawns.len()) {
+                let distance = hex_distance(spawns[0], set[0].coord);
+                assert!(distance > config.terrain.spawn_clearance);
+                for (spawn, node) in spawns.iter().zip(set) {
+                    assert_eq!(node.kind, set[0].kind);
+                    assert_eq!(hex_distance(*spawn, node.coord), distance);
+                    assert!(spawns.iter().all(|s| hex_distance(*s, node.coord) >= distance));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn two_opposed_spawns_get_equal_nodes() {
+        assert_fair(&economy_config());
+    }
+
+    #[test]
+    fn three_off_centre_spawns_get_equal_nodes() {
+        let mut config = economy_config();
+        let mut third = config.teams[0].clone();
+        third.name = "Green".to_string();
+        third.spawn = (6, -1);
+        config.teams.push(third);
+        config.teams[0].spawn = (-3, -4);
+        assert_fair(&config);
+    }
 }
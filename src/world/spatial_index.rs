@@ -1,40 +1,86 @@
 ﻿use bevy::prelude::*;
-use bevy::ecs::query::QueryIter;
 use std::collections::HashMap;
+use crate::units::Team;
+use crate::units::movement::hex_distance;
 use crate::world::HexCoord;
 
 /// Spatial index for fast proximity queries
-#[derive(Resource, Default)]
+#[derive(Resource, Default, Clone)]
 pub struct SpatialIndex {
-    grid: HashMap<(i32, i32), Vec<Entity>>,
+    grid: HashMap<(i32, i32), Vec<(Entity, Team)>>,
+    /// Bounding box of everything inserted, so ring searches know when to stop.
+    bounds: Option<(HexCoord, HexCoord)>,
 }
 
 impl SpatialIndex {
     pub fn clear(&mut self) {
         self.grid.clear();
+        self.bounds = None;
     }
     
-    pub fn insert(&mut self, coord: HexCoord, entity: Entity) {
+    pub fn insert(&mut self, coord: HexCoord, entity: Entity, team: Team) {
         self.grid.entry((coord.q, coord.r))
-            .or_insert_with(Vec::new)
-            .push(entity);
+            .or_default()
+            .push((entity, team));
+        
+        self.bounds = Some(match self.bounds {
+            None => (coord, coord),
+            Some((min, max)) => (
+                HexCoord::new(min.q.min(coord.q), min.r.min(coord.r)),
+                HexCoord::new(max.q.max(coord.q), max.r.max(coord.r)),
+            ),
+        });
+    }
+    
+    pub fn rebuild(&mut self, units: impl IntoIterator<Item = (Entity, Team, HexCoord)>) {
+        self.clear();
+        for (entity, team, coord) in units {
+            self.insert(coord, entity, team);
+        }
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Team, HexCoord)> + '_ {
+        self.grid.iter().flat_map(|((q, r), entities)| {
+            entities.iter().map(move |(e, t)| (*e, *t, HexCoord::new(*q, *r)))
+        })
+    }
+    
+    /// Units within hex distance `range` of `coord` (the centre hex included).
+    pub fn get_neighbors(&self, coord: HexCoord, range: i32) -> impl Iterator<Item = (Entity, Team, HexCoord)> + '_ {
+        (-range..=range).flat_map(move |dq| {
+            // Clamp r so that |dq| + |dr| + |dq + dr| <= 2 * range
+            let min_dr = (-range).max(-dq - range);
+            let max_dr = range.min(-dq + range);
+            (min_dr..=max_dr).flat_map(move |dr| self.units_at(coord.offset(dq, dr)))
+        })
+    }
+    
+    pub fn units_at(&self, coord: HexCoord) -> impl Iterator<Item = (Entity, Team, HexCoord)> + '_ {
+        self.grid.get(&(coord.q, coord.r))
+            .into_iter()
+            .flatten()
+            .map(move |(e, t)| (*e, *t, coord))
     }
     
-    pub fn get_neighbors(&self, coord: HexCoord, range: i32) -> Vec<Entity> {
-        let mut neighbors = Vec::new();
+    /// Closest unit whose team `is_enemy`, searched ring by ring outward from `coord`.
+    pub fn nearest_enemy(&self, coord: HexCoord, is_enemy: impl Fn(Team) -> bool) -> Option<(Entity, HexCoord)> {
+        let (min, max) = self.bounds?;
+        let max_range = [min.q, max.q].iter()
+            .flat_map(|q| [min.r, max.r].map(|r| hex_distance(coord, HexCoord::new(*q, r))))
+            .max()
+            .unwrap_or(0);
         
-        for q in -range..=range {
-            for r in -range..=range {
-                let check_q = coord.q + q;
-                let check_r = coord.r + r;
-                
-                if let Some(entities) = self.grid.get(&(check_q, check_r)) {
-                    neighbors.extend(entities);
-                }
+        for radius in 0..=max_range {
+            let found = coord.ring(radius)
+                .into_iter()
+                .flat_map(|c| self.units_at(c))
+                .find(|(_, t, _)| is_enemy(*t));
+            if let Some((entity, _, at)) = found {
+                return Some((entity, at));
             }
         }
         
-        neighbors
+        None
     }
 }
 
@@ -50,10 +96,77 @@ impl Plugin for SpatialIndexPlugin {
 
 fn update_spatial_index(
     mut index: ResMut<SpatialIndex>,
-    units: Query<(Entity, &crate::units::HexPosition), Without<crate::units::Dead>>,
+    units: Query<(Entity, &crate::units::Unit, &crate::units::HexPosition), Without<crate::units::Dead>>,
 ) {
-    index.clear();
-    for (entity, pos) in &units {
-        index.insert(pos.coord, entity);
+    index.rebuild(units.iter().map(|(entity, unit, pos)| (entity, unit.team, pos.coord)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(n: u32) -> Entity {
+        Entity::from_raw(n)
+    }
+
+    /// One unit of `Team(0)` on every hex of a square around the origin.
+    fn filled(half: i32) -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        let mut n = 0;
+        for q in -half..=half {
+            for r in -half..=half {
+                index.insert(HexCoord::new(q, r), entity(n), Team(0));
+                n += 1;
+            }
+        }
+        index
+    }
+
+    #[test]
+    fn neighbors_cover_exactly_the_hex_range() {
+        let index = filled(6);
+        let centre = HexCoord::new(1, -1);
+        for range in 0..=3 {
+            let found: Vec<HexCoord> = index.get_neighbors(centre, range).map(|(_, _, c)| c).collect();
+            assert_eq!(found.len() as i32, 1 + 3 * range * (range + 1));
+            assert!(found.iter().all(|c| hex_distance(centre, *c) <= range));
+        }
+    }
+
+    #[test]
+    fn square_corners_are_out_of_range() {
+        let index = filled(3);
+        let origin = HexCoord::new(0, 0);
+        let found: Vec<HexCoord> = index.get_neighbors(origin, 2).map(|(_, _, c)| c).collect();
+        // Same axial offsets, but only one diagonal of the square is within two hexes
+        assert!(found.contains(&HexCoord::new(2, -2)));
+        assert!(found.contains(&HexCoord::new(-2, 2)));
+        assert!(!found.contains(&HexCoord::new(2, 2)));
+        assert!(!found.contains(&HexCoord::new(-2, -2)));
+    }
+
+    #[test]
+    fn nearest_enemy_goes_by_hex_distance() {
+        let mut index = SpatialIndex::default();
+        index.insert(HexCoord::new(0, 1), entity(1), Team(0));
+        // Closer by the square's reckoning, four hexes away
+        index.insert(HexCoord::new(2, 2), entity(2), Team(1));
+        // Three hexes away
+        index.insert(HexCoord::new(3, -3), entity(3), Team(1));
+        index.insert(HexCoord::new(-5, 0), entity(4), Team(1));
+
+        let nearest = index.nearest_enemy(HexCoord::new(0, 0), |t| t != Team(0));
+        assert_eq!(nearest, Some((entity(3), HexCoord::new(3, -3))));
+    }
+
+    #[test]
+    fn nearest_enemy_skips_allies_and_finds_the_far_edge() {
+        let mut index = SpatialIndex::default();
+        index.insert(HexCoord::new(1, 0), entity(1), Team(0));
+        index.insert(HexCoord::new(-7, 6), entity(2), Team(1));
+        let from = HexCoord::new(7, -6);
+        assert_eq!(index.nearest_enemy(from, |t| t == Team(1)), Some((entity(2), HexCoord::new(-7, 6))));
+        assert_eq!(index.nearest_enemy(from, |t| t == Team(2)), None);
+        assert_eq!(SpatialIndex::default().nearest_enemy(from, |_| true), None);
     }
 }
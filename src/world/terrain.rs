@@ -0,0 +1,163 @@
+﻿use bevy::prelude::*;
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::config::GameConfig;
+use crate::world::hex_grid::{HexCoord, HexGrid};
+use crate::world::resource::ResourceField;
+use crate::units::movement::hex_distance;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerrainType {
+    Plains,
+    Forest,
+    Mountain,
+    Water,
+}
+
+impl TerrainType {
+    /// Cost to enter a hex of this terrain, or `None` if it cannot be entered.
+    pub fn move_cost(&self) -> Option<u32> {
+        match self {
+            TerrainType::Plains => Some(1),
+            TerrainType::Forest => Some(2),
+            TerrainType::Mountain | TerrainType::Water => None,
+        }
+    }
+
+    pub fn is_passable(&self) -> bool {
+        self.move_cost().is_some()
+    }
+
+    /// Whether this terrain hides whatever lies behind it. The blocking hex
+    /// itself is still visible.
+    pub fn blocks_sight(&self) -> bool {
+        matches!(self, TerrainType::Forest | TerrainType::Mountain)
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            TerrainType::Plains => Color::rgb(0.2, 0.3, 0.4),
+            TerrainType::Forest => Color::rgb(0.15, 0.35, 0.2),
+            TerrainType::Mountain => Color::rgb(0.45, 0.4, 0.35),
+            TerrainType::Water => Color::rgb(0.1, 0.2, 0.55),
+        }
+    }
+}
+
+/// Terrain for every hex of the map. Hexes that were never generated are plains.
+#[derive(Resource, Clone, Debug)]
+pub struct TerrainMap {
+    pub grid: HexGrid,
+    tiles: HashMap<HexCoord, TerrainType>,
+}
+
+impl TerrainMap {
+    pub fn flat(grid: HexGrid) -> Self {
+        Self {
+            grid,
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn generate(config: &GameConfig, rng: &mut impl Rng) -> Self {
+        let grid = HexGrid::from_config(config);
+        let settings = &config.terrain;
+        let mut map = Self::flat(grid);
+        let spawns = config.spawn_points();
+        let objectives = config.objectives.coords();
+
+        for coord in grid.coords() {
+            let near_spawn = spawns.iter().any(|s| hex_distance(*s, coord) <= settings.spawn_clearance);
+            if near_spawn || objectives.contains(&coord) {
+                continue;
+            }
+
+            let roll: f32 = rng.gen();
+            let terrain = if roll < settings.mountain_density {
+                TerrainType::Mountain
+            } else if roll < settings.mountain_density + settings.water_density {
+                TerrainType::Water
+            } else if roll < settings.mountain_density + settings.water_density + settings.forest_density {
+                TerrainType::Forest
+            } else {
+                continue;
+            };
+            map.tiles.insert(coord, terrain);
+        }
+
+        map
+    }
+
+    /// A map laid out elsewhere, e.g. read back from a replay.
+    pub fn from_tiles(grid: HexGrid, tiles: impl IntoIterator<Item = (HexCoord, TerrainType)>) -> Self {
+        Self {
+            grid,
+            tiles: tiles.into_iter().collect(),
+        }
+    }
+
+    /// Every hex that isn't plains.
+    pub fn tiles(&self) -> impl Iterator<Item = (HexCoord, TerrainType)> + '_ {
+        self.tiles.iter().map(|(c, t)| (*c, *t))
+    }
+
+    pub fn get(&self, coord: HexCoord) -> TerrainType {
+        self.tiles.get(&coord).copied().unwrap_or(TerrainType::Plains)
+    }
+
+    pub fn in_bounds(&self, coord: HexCoord) -> bool {
+        self.grid.contains(coord)
+    }
+
+    /// Cost to step into `coord`, `None` when out of bounds or impassable.
+    pub fn move_cost(&self, coord: HexCoord) -> Option<u32> {
+        if !self.in_bounds(coord) {
+            return None;
+        }
+        self.get(coord).move_cost()
+    }
+
+    pub fn is_passable(&self, coord: HexCoord) -> bool {
+        self.in_bounds(coord) && self.get(coord).is_passable()
+    }
+
+    /// Hex line of sight: no sight-blocking terrain strictly between the two hexes.
+    pub fn has_line_of_sight(&self, from: HexCoord, to: HexCoord) -> bool {
+        let line = from.line_to(to);
+        line.iter()
+            .skip(1)
+            .take(line.len().saturating_sub(2))
+            .all(|coord| !self.get(*coord).blocks_sight())
+    }
+}
+
+/// Seed the map was generated from, drawn at random unless `terrain.seed` is set.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MapSeed(pub u64);
+
+/// Lay out the map: terrain first, then resource nodes on what is left passable.
+/// A map that is already in place, such as one loaded from a replay, is kept.
+pub fn generate_terrain(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    preset: Option<Res<TerrainMap>>,
+) {
+    if preset.is_some() {
+        return;
+    }
+    let seed = config.terrain.seed.unwrap_or_else(rand::random);
+    let (terrain, resources) = generate_map(&config, seed);
+    commands.insert_resource(terrain);
+    commands.insert_resource(resources);
+    commands.insert_resource(MapSeed(seed));
+}
+
+/// The terrain and resource nodes `seed` gives for this config.
+pub fn generate_map(config: &GameConfig, seed: u64) -> (TerrainMap, ResourceField) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let terrain = TerrainMap::generate(config, &mut rng);
+    let resources = ResourceField::generate(config, &terrain, &mut rng);
+    (terrain, resources)
+}
@@ -0,0 +1,87 @@
+﻿use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+use crate::units::Team;
+use crate::units::movement::hex_distance;
+use crate::world::hex_grid::HexCoord;
+use crate::world::terrain::TerrainMap;
+
+/// Where a team last saw an enemy unit it can no longer see.
+#[derive(Clone, Copy, Debug)]
+pub struct LastKnown {
+    pub entity: Entity,
+    pub team: Team,
+    pub coord: HexCoord,
+    pub turn: u32,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct TeamVision {
+    pub visible: HashSet<HexCoord>,
+    pub last_known: HashMap<Entity, LastKnown>,
+}
+
+/// Per-team fog of war, recomputed by the turn manager at the start of every turn.
+#[derive(Resource, Default)]
+pub struct TeamVisibility {
+    pub enabled: bool,
+    teams: HashMap<Team, TeamVision>,
+}
+
+impl TeamVisibility {
+    pub fn team(&self, team: Team) -> Option<&TeamVision> {
+        self.teams.get(&team)
+    }
+
+    pub fn is_visible(&self, team: Team, coord: HexCoord) -> bool {
+        !self.enabled || self.teams.get(&team).is_some_and(|v| v.visible.contains(&coord))
+    }
+
+    /// Recompute what each team sees from `viewers` (team, position, vision radius)
+    /// and refresh last-known positions of every enemy in `units`.
+    pub fn update(
+        &mut self,
+        turn: u32,
+        terrain: &TerrainMap,
+        viewers: &[(Team, HexCoord, i32)],
+        units: &[(Entity, Team, HexCoord)],
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        for vision in self.teams.values_mut() {
+            vision.visible.clear();
+        }
+
+        for (team, origin, radius) in viewers {
+            let vision = self.teams.entry(*team).or_default();
+            for coord in terrain.grid.coords() {
+                if vision.visible.contains(&coord) || hex_distance(*origin, coord) > *radius {
+                    continue;
+                }
+                if terrain.has_line_of_sight(*origin, coord) {
+                    vision.visible.insert(coord);
+                }
+            }
+        }
+
+        for (team, vision) in self.teams.iter_mut() {
+            // Forget sightings whose hex is in view again but no longer holds that unit
+            vision.last_known.retain(|entity, seen| {
+                !vision.visible.contains(&seen.coord)
+                    || units.iter().any(|(e, _, c)| e == entity && *c == seen.coord)
+            });
+
+            for (entity, unit_team, coord) in units {
+                if unit_team != team && vision.visible.contains(coord) {
+                    vision.last_known.insert(*entity, LastKnown {
+                        entity: *entity,
+                        team: *unit_team,
+                        coord: *coord,
+                        turn,
+                    });
+                }
+            }
+        }
+    }
+}
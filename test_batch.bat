@@ -5,6 +5,6 @@ echo ================================================
 cargo build --release
 if %errorlevel% neq 0 exit /b %errorlevel%
 
-cargo run --release -- batch 100 4
+cargo run --release -- batch --games 100 --parallel 4
 
 pause
@@ -1,4 +1,4 @@
 @echo off
 echo Running single game in visual mode for diagnostics...
-cargo run --release
+cargo run --release -- play
 pause